@@ -0,0 +1,136 @@
+use std::{path::PathBuf, str::FromStr};
+
+pub const USAGE: &str = "\
+Usage: radar-cli [OPTIONS] [SOURCE]
+
+Reads the radar telemetry from SOURCE, prints the distances of each sensor
+as they arrive and optionally records the session.
+
+SOURCE is a serial port (e.g. /dev/ttyACM0), a raw capture saved with
+--raw, or `-` for the standard input (the default).
+
+Options:
+  -b, --baud <RATE>   Configure the serial port with stty before reading
+      --csv <FILE>    Record the messages to a CSV file
+      --json <FILE>   Record the messages to a JSON Lines file
+      --raw <FILE>    Save the raw byte stream, to replay it later
+      --replay        Play SOURCE back at the pace it was recorded
+      --speed <X>     Replay X times faster than recorded (implies --replay)
+  -q, --quiet         Only print the summary at the end
+  -h, --help          Print this help
+";
+
+/// Where the telemetry is read from.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum Source {
+    #[default]
+    Stdin,
+    /// A serial port or a recorded file.
+    Path(PathBuf),
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Options {
+    pub source: Source,
+    pub baud: Option<u32>,
+    pub csv: Option<PathBuf>,
+    pub json: Option<PathBuf>,
+    pub raw: Option<PathBuf>,
+    /// Replay speed, `None` to read as fast as the source allows.
+    pub replay: Option<f64>,
+    pub quiet: bool,
+}
+
+/// What the command line asks for.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Run(Options),
+    Help,
+}
+
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Action, String> {
+    let mut args = args.into_iter();
+    let mut options = Options::default();
+    let mut source = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Action::Help),
+            "-b" | "--baud" => options.baud = Some(value(&arg, args.next())?),
+            "--csv" => options.csv = Some(value(&arg, args.next())?),
+            "--json" => options.json = Some(value(&arg, args.next())?),
+            "--raw" => options.raw = Some(value(&arg, args.next())?),
+            "--replay" => options.replay = options.replay.or(Some(1.0)),
+            "--speed" => {
+                let speed: f64 = value(&arg, args.next())?;
+                // Rejette aussi NaN
+                if !(speed > 0.0 && speed.is_finite()) {
+                    return Err(format!("invalid value for {arg}: {speed}"));
+                }
+                options.replay = Some(speed);
+            }
+            "-q" | "--quiet" => options.quiet = true,
+            _ if arg.starts_with('-') && arg != "-" => return Err(format!("unknown option {arg}")),
+            _ => {
+                if source.is_some() {
+                    return Err("only one source can be read at a time".into());
+                }
+                source = Some(match arg.as_str() {
+                    "-" => Source::Stdin,
+                    path => Source::Path(path.into()),
+                });
+            }
+        }
+    }
+
+    options.source = source.unwrap_or_default();
+    Ok(Action::Run(options))
+}
+
+fn value<T: FromStr>(option: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("{option} needs a value"))?;
+    value.parse().map_err(|_| format!("invalid value for {option}: {value}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_line(line: &str) -> Result<Action, String> {
+        parse(line.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn parses_the_options() {
+        let Ok(Action::Run(options)) = parse_line("/dev/ttyACM0 -b 115200 --csv run.csv --raw run.bin -q") else {
+            panic!("ligne de commande rejetée");
+        };
+        assert_eq!(options.source, Source::Path("/dev/ttyACM0".into()));
+        assert_eq!(options.baud, Some(115_200));
+        assert_eq!(options.csv, Some("run.csv".into()));
+        assert_eq!(options.json, None);
+        assert_eq!(options.raw, Some("run.bin".into()));
+        assert_eq!(options.replay, None);
+        assert!(options.quiet);
+
+        // Sans source, l'entrée standard ; --speed implique --replay
+        assert_eq!(
+            parse_line("--speed 4 --replay"),
+            Ok(Action::Run(Options {
+                replay: Some(4.0),
+                ..Options::default()
+            }))
+        );
+        assert_eq!(parse_line("run.bin --help"), Ok(Action::Help));
+    }
+
+    #[test]
+    fn rejects_bad_command_lines() {
+        assert!(parse_line("--csv").is_err());
+        assert!(parse_line("--baud fast").is_err());
+        assert!(parse_line("--speed 0").is_err());
+        assert!(parse_line("--verbose").is_err());
+        assert!(parse_line("a.bin b.bin").is_err());
+        assert!(parse_line("- a.bin").is_err());
+    }
+}
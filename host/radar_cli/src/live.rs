@@ -0,0 +1,171 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use radar_telemetry::Message;
+
+use crate::record::{fault_name, setting_name};
+
+/// Full scale of the bar graph: the HC-SR04 does not see further.
+const FULL_SCALE_MM: u16 = 4_000;
+const BAR_WIDTH: usize = 40;
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Stats {
+    samples: u32,
+    out_of_range: u32,
+    faults: u32,
+    min_mm: Option<u16>,
+    max_mm: Option<u16>,
+    sum_mm: u64,
+}
+
+/// Per-sensor view of the stream: one line per message as it arrives, and
+/// statistics for the whole session.
+#[derive(Default)]
+pub struct Live {
+    sensors: BTreeMap<u8, Stats>,
+    heartbeats: u32,
+    sweeps: u32,
+    bad_frames: u32,
+}
+
+impl Live {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accounts for `message` and returns the line to display.
+    pub fn update(&mut self, message: &Message) -> String {
+        match message {
+            Message::Sample(sample) => {
+                let stats = self.sensors.entry(sample.sensor).or_default();
+                stats.samples += 1;
+                let (distance, filled) = match sample.distance_mm {
+                    Some(mm) => {
+                        stats.min_mm = Some(stats.min_mm.map_or(mm, |min| min.min(mm)));
+                        stats.max_mm = Some(stats.max_mm.map_or(mm, |max| max.max(mm)));
+                        stats.sum_mm += mm as u64;
+                        (mm.to_string(), mm.min(FULL_SCALE_MM) as usize * BAR_WIDTH / FULL_SCALE_MM as usize)
+                    }
+                    None => {
+                        stats.out_of_range += 1;
+                        ("--".into(), 0)
+                    }
+                };
+                format!(
+                    "[{:>10.3} s] sensor {} {distance:>5} mm |{}{}|",
+                    seconds(sample.timestamp_us),
+                    sample.sensor,
+                    "#".repeat(filled),
+                    " ".repeat(BAR_WIDTH - filled)
+                )
+            }
+            Message::Fault(fault) => {
+                self.sensors.entry(fault.sensor).or_default().faults += 1;
+                format!(
+                    "[{:>10.3} s] sensor {} fault: {}",
+                    seconds(fault.timestamp_us),
+                    fault.sensor,
+                    fault_name(fault.code)
+                )
+            }
+            Message::Heartbeat(heartbeat) => {
+                self.heartbeats += 1;
+                format!(
+                    "heartbeat: up {:.1} s, period {} ms",
+                    heartbeat.uptime_ms as f64 / 1_000.0,
+                    heartbeat.period_ms
+                )
+            }
+            Message::ConfigAck(ack) => format!(
+                "config: {} = {} ({})",
+                setting_name(ack.setting),
+                ack.value,
+                if ack.accepted { "accepted" } else { "rejected" }
+            ),
+            Message::Sweep(sweep) => {
+                self.sweeps += 1;
+                let mut line = format!("sweep {}:", sweep.sweep);
+                for (angle, distance) in sweep.iter() {
+                    match distance {
+                        Some(cm) => write!(line, " {angle}°:{cm}").unwrap(),
+                        None => write!(line, " {angle}°:--").unwrap(),
+                    }
+                }
+                line
+            }
+        }
+    }
+
+    pub fn bad_frame(&mut self) {
+        self.bad_frames += 1;
+    }
+
+    /// Statistics of the session so far, one line per sensor.
+    pub fn summary(&self) -> String {
+        let mut summary = String::new();
+        for (sensor, stats) in &self.sensors {
+            write!(
+                summary,
+                "sensor {sensor}: {} samples, {} out of range, {} faults",
+                stats.samples, stats.out_of_range, stats.faults
+            )
+            .unwrap();
+            if let (Some(min), Some(max)) = (stats.min_mm, stats.max_mm) {
+                let mean = stats.sum_mm / (stats.samples - stats.out_of_range) as u64;
+                write!(summary, ", min {min} mm, mean {mean} mm, max {max} mm").unwrap();
+            }
+            summary.push('\n');
+        }
+        writeln!(
+            summary,
+            "{} heartbeats, {} sweeps, {} bad frames",
+            self.heartbeats, self.sweeps, self.bad_frames
+        )
+        .unwrap();
+        summary
+    }
+}
+
+fn seconds(timestamp_us: u32) -> f64 {
+    timestamp_us as f64 / 1_000_000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use radar_telemetry::{Fault, FaultCode, Sample};
+
+    fn sample(sensor: u8, distance_mm: Option<u16>) -> Message {
+        Message::Sample(Sample {
+            sensor,
+            timestamp_us: 2_500_000,
+            distance_mm,
+        })
+    }
+
+    #[test]
+    fn plots_and_sums_up_the_distances() {
+        let mut live = Live::new();
+        // Mi-échelle : la moitié de la barre
+        let line = live.update(&sample(0, Some(2_000)));
+        assert_eq!(line, format!("[     2.500 s] sensor 0  2000 mm |{}{}|", "#".repeat(20), " ".repeat(20)));
+        // Au-delà de la pleine échelle, la barre est pleine sans déborder
+        assert!(live.update(&sample(0, Some(9_000))).ends_with(&format!("|{}|", "#".repeat(40))));
+        assert!(live.update(&sample(0, None)).contains("   -- mm |  "));
+        live.update(&sample(1, Some(100)));
+        let fault = live.update(&Message::Fault(Fault {
+            sensor: 1,
+            timestamp_us: 2_600_000,
+            code: FaultCode::NoEchoStart,
+        }));
+        assert_eq!(fault, "[     2.600 s] sensor 1 fault: no_echo_start");
+        live.bad_frame();
+
+        assert_eq!(
+            live.summary(),
+            "sensor 0: 3 samples, 1 out of range, 0 faults, min 2000 mm, mean 5500 mm, max 9000 mm\n\
+             sensor 1: 1 samples, 0 out of range, 1 faults, min 100 mm, mean 100 mm, max 100 mm\n\
+             0 heartbeats, 0 sweeps, 1 bad frames\n"
+        );
+    }
+}
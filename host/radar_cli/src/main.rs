@@ -0,0 +1,122 @@
+//! Host tool for the radar telemetry: reads the stream from a serial port,
+//! a file or the standard input, prints the distances of each sensor as
+//! they arrive, and records the session to CSV or JSON Lines.
+//!
+//! With `--raw` the bytes received are saved as is; such a capture is read
+//! back exactly like the radar, and `--replay` plays it at its original
+//! pace.
+
+mod args;
+mod live;
+mod record;
+mod session;
+
+use std::{
+    env,
+    fs::File,
+    io::{self, LineWriter, Read, Write},
+    path::Path,
+    process::{self, ExitCode},
+};
+
+use args::{Action, Options, Source, USAGE};
+use record::{Format, Recorder};
+use session::Session;
+
+fn main() -> ExitCode {
+    let options = match args::parse(env::args().skip(1)) {
+        Ok(Action::Run(options)) => options,
+        Ok(Action::Help) => {
+            print!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(error) => {
+            eprintln!("radar-cli: {error}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(&options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("radar-cli: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    let mut input = open(options)?;
+    if let Some(path) = &options.raw {
+        input = Box::new(Tee {
+            inner: input,
+            copy: create(path)?,
+        });
+    }
+
+    let display = (!options.quiet).then(|| Box::new(io::stdout()) as Box<dyn Write>);
+    let mut session = Session::new(display);
+    for (path, format) in [(&options.csv, Format::Csv), (&options.json, Format::Json)] {
+        if let Some(path) = path {
+            // Ligne par ligne : une session arrêtée par Ctrl-C reste exploitable
+            let out = Box::new(LineWriter::new(create(path)?));
+            let recorder = Recorder::new(out as Box<dyn Write>, format)
+                .map_err(|error| format!("cannot write {}: {error}", path.display()))?;
+            session = session.with_recorder(recorder);
+        }
+    }
+    if let Some(speed) = options.replay {
+        session = session.with_pacing(speed);
+    }
+
+    let result = session.run(input);
+    eprint!("{}", session.summary());
+    result.map_err(|error| error.to_string())
+}
+
+fn open(options: &Options) -> Result<Box<dyn Read>, String> {
+    match &options.source {
+        Source::Stdin if options.baud.is_some() => Err("--baud needs a serial port".into()),
+        Source::Stdin => Ok(Box::new(io::stdin())),
+        Source::Path(path) => {
+            let file = File::open(path).map_err(|error| format!("cannot open {}: {error}", path.display()))?;
+            if let Some(baud) = options.baud {
+                configure_serial(path, baud)?;
+            }
+            Ok(Box::new(file))
+        }
+    }
+}
+
+/// The standard library cannot set up a tty, so leave it to `stty`: raw
+/// mode, so that no byte of the binary stream is altered.
+fn configure_serial(path: &Path, baud: u32) -> Result<(), String> {
+    let status = process::Command::new("stty")
+        .arg("-F")
+        .arg(path)
+        .args([&baud.to_string(), "raw", "-echo"])
+        .status()
+        .map_err(|error| format!("cannot run stty: {error}"))?;
+    if !status.success() {
+        return Err(format!("cannot configure {} at {baud} baud", path.display()));
+    }
+    Ok(())
+}
+
+fn create(path: &Path) -> Result<File, String> {
+    File::create(path).map_err(|error| format!("cannot create {}: {error}", path.display()))
+}
+
+/// Copies the bytes read to a raw capture.
+struct Tee<R, W> {
+    inner: R,
+    copy: W,
+}
+
+impl<R: Read, W: Write> Read for Tee<R, W> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.copy.write_all(&buf[..len])?;
+        Ok(len)
+    }
+}
@@ -0,0 +1,238 @@
+use std::io::{self, Write};
+
+use radar_telemetry::{FaultCode, Message, Setting};
+
+/// File formats a session can be recorded to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// One row per message; the columns a message does not have are empty.
+    Csv,
+    /// One JSON object per line.
+    Json,
+}
+
+/// Columns of the CSV format, in order.
+const COLUMNS: [&str; 14] = [
+    "type",
+    "sensor",
+    "timestamp_us",
+    "distance_mm",
+    "fault",
+    "uptime_ms",
+    "period_ms",
+    "setting",
+    "value",
+    "accepted",
+    "sweep",
+    "first_deg",
+    "step_deg",
+    "distances_cm",
+];
+
+enum Value {
+    Int(i64),
+    Bool(bool),
+    /// Always a plain identifier, never needs escaping.
+    Name(String),
+    Null,
+    List(Vec<Value>),
+}
+
+/// Writes every message received to a file, for later analysis.
+pub struct Recorder<W> {
+    out: W,
+    format: Format,
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(mut out: W, format: Format) -> io::Result<Self> {
+        if format == Format::Csv {
+            writeln!(out, "{}", COLUMNS.join(","))?;
+        }
+        Ok(Self { out, format })
+    }
+
+    pub fn record(&mut self, message: &Message) -> io::Result<()> {
+        let fields = fields(message);
+        match self.format {
+            Format::Csv => {
+                let row: Vec<String> = COLUMNS
+                    .iter()
+                    .map(|column| match fields.iter().find(|(name, _)| name == column) {
+                        Some((_, value)) => csv(value),
+                        None => String::new(),
+                    })
+                    .collect();
+                writeln!(self.out, "{}", row.join(","))
+            }
+            Format::Json => {
+                let members: Vec<String> =
+                    fields.iter().map(|(name, value)| format!("\"{name}\":{}", json(value))).collect();
+                writeln!(self.out, "{{{}}}", members.join(","))
+            }
+        }
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Name of a fault in the recordings and on the display.
+pub fn fault_name(code: FaultCode) -> String {
+    match code {
+        FaultCode::NoEchoStart => "no_echo_start".into(),
+        FaultCode::EchoTooLong => "echo_too_long".into(),
+        FaultCode::OutOfRange => "out_of_range".into(),
+        FaultCode::TooSoon => "too_soon".into(),
+        FaultCode::PinError => "pin_error".into(),
+        FaultCode::NoResponse => "no_response".into(),
+        FaultCode::BadChecksum => "bad_checksum".into(),
+        FaultCode::BusError => "bus_error".into(),
+        FaultCode::WrongDevice => "wrong_device".into(),
+        FaultCode::InvalidReading => "invalid_reading".into(),
+        FaultCode::InvalidConfig => "invalid_config".into(),
+        FaultCode::Misaligned => "misaligned".into(),
+        code => format!("fault_{}", code.to_u8()),
+    }
+}
+
+/// Name of a setting in the recordings and on the display.
+pub fn setting_name(setting: Setting) -> String {
+    match setting {
+        Setting::PeriodMs => "period_ms".into(),
+        Setting::Zones => "zones".into(),
+        Setting::TemperatureDc => "temperature_dc".into(),
+        Setting::Reset => "reset".into(),
+        Setting::Other(code) => format!("setting_{code}"),
+    }
+}
+
+fn fields(message: &Message) -> Vec<(&'static str, Value)> {
+    let optional = |value: Option<u16>| value.map_or(Value::Null, |value| Value::Int(value.into()));
+    match message {
+        Message::Sample(sample) => vec![
+            ("type", Value::Name("sample".into())),
+            ("sensor", Value::Int(sample.sensor.into())),
+            ("timestamp_us", Value::Int(sample.timestamp_us.into())),
+            ("distance_mm", optional(sample.distance_mm)),
+        ],
+        Message::Fault(fault) => vec![
+            ("type", Value::Name("fault".into())),
+            ("sensor", Value::Int(fault.sensor.into())),
+            ("timestamp_us", Value::Int(fault.timestamp_us.into())),
+            ("fault", Value::Name(fault_name(fault.code))),
+        ],
+        Message::Heartbeat(heartbeat) => vec![
+            ("type", Value::Name("heartbeat".into())),
+            ("uptime_ms", Value::Int(heartbeat.uptime_ms.into())),
+            ("period_ms", Value::Int(heartbeat.period_ms.into())),
+        ],
+        Message::ConfigAck(ack) => vec![
+            ("type", Value::Name("config_ack".into())),
+            ("setting", Value::Name(setting_name(ack.setting))),
+            ("value", Value::Int(ack.value.into())),
+            ("accepted", Value::Bool(ack.accepted)),
+        ],
+        Message::Sweep(sweep) => vec![
+            ("type", Value::Name("sweep".into())),
+            ("sweep", Value::Int(sweep.sweep.into())),
+            ("first_deg", Value::Int(sweep.first_deg.into())),
+            ("step_deg", Value::Int(sweep.step_deg.into())),
+            ("distances_cm", Value::List(sweep.iter().map(|(_, distance)| optional(distance)).collect())),
+        ],
+    }
+}
+
+fn csv(value: &Value) -> String {
+    match value {
+        Value::Int(value) => value.to_string(),
+        Value::Bool(value) => value.to_string(),
+        Value::Name(name) => name.clone(),
+        Value::Null => String::new(),
+        // Une cellule vide dans la liste serait ambiguë
+        Value::List(values) => values
+            .iter()
+            .map(|value| match value {
+                Value::Null => "-".into(),
+                value => csv(value),
+            })
+            .collect::<Vec<_>>()
+            .join(" "),
+    }
+}
+
+fn json(value: &Value) -> String {
+    match value {
+        Value::Int(value) => value.to_string(),
+        Value::Bool(value) => value.to_string(),
+        Value::Name(name) => format!("\"{name}\""),
+        Value::Null => "null".into(),
+        Value::List(values) => format!("[{}]", values.iter().map(json).collect::<Vec<_>>().join(",")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use radar_telemetry::{ConfigAck, Fault, Sample, Sweep};
+
+    fn messages() -> [Message; 4] {
+        [
+            Message::Sample(Sample {
+                sensor: 1,
+                timestamp_us: 1_500,
+                distance_mm: Some(842),
+            }),
+            Message::Fault(Fault {
+                sensor: 0,
+                timestamp_us: 1_600,
+                code: FaultCode::Other(42),
+            }),
+            Message::ConfigAck(ConfigAck {
+                setting: Setting::TemperatureDc,
+                value: -5,
+                accepted: false,
+            }),
+            Message::Sweep(Sweep::new(3, -20, 20, [Some(80), None, Some(120)]).unwrap()),
+        ]
+    }
+
+    fn record(format: Format) -> Vec<String> {
+        let mut out = Vec::new();
+        let mut recorder = Recorder::new(&mut out, format).unwrap();
+        for message in messages() {
+            recorder.record(&message).unwrap();
+        }
+        String::from_utf8(out).unwrap().lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn records_to_csv() {
+        let lines = record(Format::Csv);
+        assert_eq!(
+            lines,
+            [
+                "type,sensor,timestamp_us,distance_mm,fault,uptime_ms,period_ms,setting,value,accepted,sweep,first_deg,step_deg,distances_cm",
+                "sample,1,1500,842,,,,,,,,,,",
+                "fault,0,1600,,fault_42,,,,,,,,,",
+                "config_ack,,,,,,,temperature_dc,-5,false,,,,",
+                "sweep,,,,,,,,,,3,-20,20,80 - 120",
+            ]
+        );
+    }
+
+    #[test]
+    fn records_to_json_lines() {
+        let lines = record(Format::Json);
+        assert_eq!(
+            lines,
+            [
+                r#"{"type":"sample","sensor":1,"timestamp_us":1500,"distance_mm":842}"#,
+                r#"{"type":"fault","sensor":0,"timestamp_us":1600,"fault":"fault_42"}"#,
+                r#"{"type":"config_ack","setting":"temperature_dc","value":-5,"accepted":false}"#,
+                r#"{"type":"sweep","sweep":3,"first_deg":-20,"step_deg":20,"distances_cm":[80,null,120]}"#,
+            ]
+        );
+    }
+}
@@ -0,0 +1,194 @@
+use std::{
+    io::{self, Read, Write},
+    thread,
+    time::{Duration, Instant},
+};
+
+use radar_telemetry::{FrameReader, Message, ReadError};
+
+use crate::{live::Live, record::Recorder};
+
+/// Longest gap of the radar clock a replay waits for. Anything longer is a
+/// reset of the radar or a pause of the capture.
+const MAX_GAP_US: u32 = 10_000_000;
+
+/// Tells when each message of a replay is due, from the radar timestamps.
+pub struct Pacer {
+    speed: f64,
+    last_us: Option<u32>,
+    elapsed_us: u64,
+}
+
+impl Pacer {
+    pub fn new(speed: f64) -> Self {
+        Self {
+            speed,
+            last_us: None,
+            elapsed_us: 0,
+        }
+    }
+
+    /// Delay from the start of the replay to the message stamped
+    /// `timestamp_us`.
+    pub fn due(&mut self, timestamp_us: u32) -> Duration {
+        if let Some(last) = self.last_us {
+            // Une horloge qui recule donne un écart énorme, ignoré aussi
+            let gap = timestamp_us.wrapping_sub(last);
+            if gap <= MAX_GAP_US {
+                self.elapsed_us += gap as u64;
+            }
+        }
+        self.last_us = Some(timestamp_us);
+        Duration::from_secs_f64(self.elapsed_us as f64 / 1_000_000.0 / self.speed)
+    }
+}
+
+/// Reads a telemetry stream to its end: displays each message, records it
+/// and keeps the statistics.
+pub struct Session<'a> {
+    live: Live,
+    display: Option<Box<dyn Write + 'a>>,
+    recorders: Vec<Recorder<Box<dyn Write + 'a>>>,
+    pacer: Option<(Pacer, Option<Instant>)>,
+}
+
+impl<'a> Session<'a> {
+    /// `display` receives one line per message, `None` to stay quiet.
+    pub fn new(display: Option<Box<dyn Write + 'a>>) -> Self {
+        Self {
+            live: Live::new(),
+            display,
+            recorders: Vec::new(),
+            pacer: None,
+        }
+    }
+
+    pub fn with_recorder(mut self, recorder: Recorder<Box<dyn Write + 'a>>) -> Self {
+        self.recorders.push(recorder);
+        self
+    }
+
+    /// Plays the stream back at `speed` times the pace it was recorded.
+    pub fn with_pacing(mut self, speed: f64) -> Self {
+        self.pacer = Some((Pacer::new(speed), None));
+        self
+    }
+
+    /// Processes the whole stream. Corrupted frames are counted and
+    /// skipped; an I/O error ends the session.
+    pub fn run(&mut self, input: impl Read) -> io::Result<()> {
+        for read in FrameReader::new(input) {
+            match read {
+                Ok(message) => self.process(&message)?,
+                Err(ReadError::Frame(error)) => {
+                    self.live.bad_frame();
+                    if let Some(display) = &mut self.display {
+                        writeln!(display, "bad frame: {error}")?;
+                    }
+                }
+                Err(ReadError::Io(error)) => return Err(error),
+            }
+        }
+        for recorder in &mut self.recorders {
+            recorder.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn summary(&self) -> String {
+        self.live.summary()
+    }
+
+    fn process(&mut self, message: &Message) -> io::Result<()> {
+        let timestamp_us = match message {
+            Message::Sample(sample) => Some(sample.timestamp_us),
+            Message::Fault(fault) => Some(fault.timestamp_us),
+            _ => None,
+        };
+        if let (Some((pacer, start)), Some(timestamp_us)) = (&mut self.pacer, timestamp_us) {
+            let start = *start.get_or_insert_with(Instant::now);
+            let deadline = start + pacer.due(timestamp_us);
+            thread::sleep(deadline.saturating_duration_since(Instant::now()));
+        }
+
+        let line = self.live.update(message);
+        if let Some(display) = &mut self.display {
+            writeln!(display, "{line}")?;
+        }
+        for recorder in &mut self.recorders {
+            recorder.record(message)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::Format;
+    use radar_telemetry::{Heartbeat, Sample, MAX_FRAME_LEN};
+
+    fn sample(timestamp_us: u32, distance_mm: Option<u16>) -> Message {
+        Message::Sample(Sample {
+            sensor: 0,
+            timestamp_us,
+            distance_mm,
+        })
+    }
+
+    fn frame(message: &Message) -> Vec<u8> {
+        let mut frame = [0; MAX_FRAME_LEN];
+        let len = message.encode(&mut frame).unwrap();
+        frame[..len].to_vec()
+    }
+
+    #[test]
+    fn replays_a_recorded_stream() {
+        // Capture brute : un échantillon, une trame tronquée, un battement
+        // de cœur, un échantillon hors de portée
+        let mut capture = frame(&sample(100_000, Some(1_234)));
+        capture.extend([0x05, 0x01, 0x00]);
+        capture.extend(frame(&Message::Heartbeat(Heartbeat {
+            uptime_ms: 1_000,
+            period_ms: 100,
+        })));
+        capture.extend(frame(&sample(200_000, None)));
+
+        let (mut display, mut csv) = (Vec::new(), Vec::new());
+        let mut session = Session::new(Some(Box::new(&mut display)))
+            .with_recorder(Recorder::new(Box::new(&mut csv) as Box<dyn Write>, Format::Csv).unwrap());
+        session.run(capture.as_slice()).unwrap();
+        let summary = session.summary();
+        drop(session);
+
+        let display = String::from_utf8(display).unwrap();
+        let lines: Vec<_> = display.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("[     0.100 s] sensor 0  1234 mm |"));
+        assert!(lines[1].starts_with("bad frame: "));
+        assert_eq!(lines[2], "heartbeat: up 1.0 s, period 100 ms");
+        assert!(lines[3].contains("   -- mm"));
+
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().count(), 4);
+        assert_eq!(csv.lines().nth(3), Some("sample,0,200000,,,,,,,,,,,"));
+
+        assert_eq!(
+            summary,
+            "sensor 0: 2 samples, 1 out of range, 0 faults, min 1234 mm, mean 1234 mm, max 1234 mm\n\
+             1 heartbeats, 0 sweeps, 1 bad frames\n"
+        );
+    }
+
+    #[test]
+    fn paces_on_the_radar_clock() {
+        let mut pacer = Pacer::new(2.0);
+        assert_eq!(pacer.due(u32::MAX - 99_999), Duration::ZERO);
+        // Le débordement de l'horloge µs ne se voit pas
+        assert_eq!(pacer.due(100_000), Duration::from_millis(100));
+        // Une remise à zéro du radar ou une longue pause ne fait pas attendre
+        assert_eq!(pacer.due(50_000), Duration::from_millis(100));
+        assert_eq!(pacer.due(60_050_000), Duration::from_millis(100));
+        assert_eq!(pacer.due(60_250_000), Duration::from_millis(200));
+    }
+}
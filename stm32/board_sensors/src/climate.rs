@@ -0,0 +1,429 @@
+use embedded_hal::i2c::I2c;
+use ultrasonic_sensor::{EnvironmentSource, SensorError};
+
+const WHO_AM_I: u8 = 0x0F;
+/// Status polls before giving up on a one-shot conversion. A poll is a
+/// short I2C transfer, so this is well over the ~10 ms a conversion takes.
+const READY_POLLS: u32 = 1_000;
+
+fn bus<T, E>(result: Result<T, E>) -> Result<T, SensorError> {
+    result.map_err(|_| SensorError::BusError)
+}
+
+/// Output data rate of the HTS221.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hts221Rate {
+    /// A conversion is only run on request.
+    OneShot = 0b00,
+    Hz1 = 0b01,
+    Hz7 = 0b10,
+    Hz12_5 = 0b11,
+}
+
+/// Factory calibration of an HTS221: two points of its linear transfer
+/// function, for temperature and humidity.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+struct Hts221Calibration {
+    t0_c: f32,
+    t1_c: f32,
+    t0_out: i16,
+    t1_out: i16,
+    h0_pct: f32,
+    h1_pct: f32,
+    h0_out: i16,
+    h1_out: i16,
+}
+
+impl Hts221Calibration {
+    /// Decodes registers 0x30 to 0x3F.
+    fn from_registers(registers: &[u8; 16]) -> Self {
+        let word = |offset: usize| i16::from_le_bytes([registers[offset], registers[offset + 1]]);
+        let msb = registers[5] as u16;
+        Self {
+            h0_pct: registers[0] as f32 / 2.0,
+            h1_pct: registers[1] as f32 / 2.0,
+            t0_c: ((msb & 0x03) << 8 | registers[2] as u16) as f32 / 8.0,
+            t1_c: ((msb & 0x0C) << 6 | registers[3] as u16) as f32 / 8.0,
+            h0_out: word(6),
+            h1_out: word(10),
+            t0_out: word(12),
+            t1_out: word(14),
+        }
+    }
+
+    fn temperature_c(&self, raw: i16) -> f32 {
+        let slope = (self.t1_c - self.t0_c) / (self.t1_out as f32 - self.t0_out as f32);
+        self.t0_c + (raw as f32 - self.t0_out as f32) * slope
+    }
+
+    fn humidity_pct(&self, raw: i16) -> f32 {
+        let slope = (self.h1_pct - self.h0_pct) / (self.h1_out as f32 - self.h0_out as f32);
+        (self.h0_pct + (raw as f32 - self.h0_out as f32) * slope).clamp(0.0, 100.0)
+    }
+}
+
+/// ST HTS221 relative humidity and temperature sensor.
+///
+/// Once [`Self::init`] has read its calibration, it can be handed to
+/// [`EchoSensor::update_environment`](ultrasonic_sensor::EchoSensor::update_environment)
+/// to compensate the speed of sound. In one-shot mode, each reading runs a
+/// conversion and waits for it.
+pub struct Hts221<I> {
+    i2c: I,
+    calibration: Hts221Calibration,
+    rate: Hts221Rate,
+    /// Humidity of the last one-shot conversion, not handed out yet.
+    pending_humidity: Option<f32>,
+}
+
+impl<I> Hts221<I> {
+    pub const ADDRESS: u8 = 0x5F;
+    const ID: u8 = 0xBC;
+    const CTRL_REG1: u8 = 0x20;
+    const CTRL_REG2: u8 = 0x21;
+    const STATUS: u8 = 0x27;
+    const HUMIDITY_OUT_L: u8 = 0x28;
+    const CALIBRATION: u8 = 0x30;
+    /// Set in the register address to read several registers in a row.
+    const AUTO_INCREMENT: u8 = 0x80;
+
+    pub fn new(i2c: I) -> Self {
+        Self {
+            i2c,
+            calibration: Hts221Calibration::default(),
+            rate: Hts221Rate::OneShot,
+            pending_humidity: None,
+        }
+    }
+
+    pub fn release(self) -> I {
+        self.i2c
+    }
+}
+
+impl<I: I2c> Hts221<I> {
+    /// Checks the device's identity, reads its calibration and powers it
+    /// up at `rate`, with block data update so that the two bytes of an
+    /// output always belong to the same conversion.
+    pub fn init(&mut self, rate: Hts221Rate) -> Result<(), SensorError> {
+        if self.read(WHO_AM_I)? != Self::ID {
+            return Err(SensorError::WrongDevice);
+        }
+
+        let mut registers = [0; 16];
+        bus(self.i2c.write_read(Self::ADDRESS, &[Self::CALIBRATION | Self::AUTO_INCREMENT], &mut registers))?;
+        self.calibration = Hts221Calibration::from_registers(&registers);
+
+        // PD | BDU | ODR
+        bus(self.i2c.write(Self::ADDRESS, &[Self::CTRL_REG1, 0x80 | 0x04 | rate as u8]))?;
+        self.rate = rate;
+        Ok(())
+    }
+
+    /// Starts a conversion in one-shot mode.
+    pub fn start_one_shot(&mut self) -> Result<(), SensorError> {
+        bus(self.i2c.write(Self::ADDRESS, &[Self::CTRL_REG2, 0x01]))
+    }
+
+    /// Whether both a new temperature and a new humidity are available.
+    pub fn is_ready(&mut self) -> Result<bool, SensorError> {
+        Ok(self.read(Self::STATUS)? & 0x03 == 0x03)
+    }
+
+    /// Reads the latest conversion, as `(temperature in °C, relative
+    /// humidity in %)`.
+    pub fn read_measurement(&mut self) -> Result<(f32, f32), SensorError> {
+        let mut raw = [0; 4];
+        bus(self.i2c.write_read(Self::ADDRESS, &[Self::HUMIDITY_OUT_L | Self::AUTO_INCREMENT], &mut raw))?;
+        let humidity = i16::from_le_bytes([raw[0], raw[1]]);
+        let temperature = i16::from_le_bytes([raw[2], raw[3]]);
+        Ok((self.calibration.temperature_c(temperature), self.calibration.humidity_pct(humidity)))
+    }
+
+    /// Runs a conversion in one-shot mode, or reads the latest one
+    /// otherwise.
+    pub fn measure(&mut self) -> Result<(f32, f32), SensorError> {
+        if self.rate == Hts221Rate::OneShot {
+            self.start_one_shot()?;
+            let mut polls = 0;
+            while !self.is_ready()? {
+                polls += 1;
+                if polls > READY_POLLS {
+                    return Err(SensorError::NoResponse);
+                }
+            }
+        }
+        self.read_measurement()
+    }
+
+    fn read(&mut self, register: u8) -> Result<u8, SensorError> {
+        let mut value = [0];
+        bus(self.i2c.write_read(Self::ADDRESS, &[register], &mut value))?;
+        Ok(value[0])
+    }
+}
+
+impl<I: I2c> EnvironmentSource for Hts221<I> {
+    fn temperature_c(&mut self) -> Option<f32> {
+        let (celsius, humidity) = self.measure().ok()?;
+        self.pending_humidity = Some(humidity);
+        Some(celsius)
+    }
+
+    /// Reuses the conversion run for [`Self::temperature_c`] when there is
+    /// one, so that updating an environment only converts once.
+    fn humidity_pct(&mut self) -> Option<f32> {
+        match self.pending_humidity.take() {
+            Some(humidity) => Some(humidity),
+            None => self.measure().ok().map(|(_, humidity)| humidity),
+        }
+    }
+}
+
+/// Output data rate of the LPS22HB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lps22hbRate {
+    /// Powered down between conversions run on request.
+    OneShot = 0b000,
+    Hz1 = 0b001,
+    Hz10 = 0b010,
+    Hz25 = 0b011,
+    Hz50 = 0b100,
+    Hz75 = 0b101,
+}
+
+/// ST LPS22HB absolute pressure sensor. It is calibrated in the factory
+/// and also reports its die temperature, which is close enough to the air
+/// temperature for speed-of-sound compensation when the board is not
+/// heating it.
+pub struct Lps22hb<I> {
+    i2c: I,
+    address: u8,
+    rate: Lps22hbRate,
+}
+
+impl<I> Lps22hb<I> {
+    /// SA0 high, as on the B-L475E-IOT01A.
+    pub const DEFAULT_ADDRESS: u8 = 0x5D;
+    const ID: u8 = 0xB1;
+    const CTRL_REG1: u8 = 0x10;
+    const CTRL_REG2: u8 = 0x11;
+    const STATUS: u8 = 0x27;
+    const PRESS_OUT_XL: u8 = 0x28;
+    const TEMP_OUT_L: u8 = 0x2B;
+
+    pub fn new(i2c: I) -> Self {
+        Self {
+            i2c,
+            address: Self::DEFAULT_ADDRESS,
+            rate: Lps22hbRate::OneShot,
+        }
+    }
+
+    pub fn with_address(mut self, address: u8) -> Self {
+        self.address = address;
+        self
+    }
+
+    pub fn release(self) -> I {
+        self.i2c
+    }
+}
+
+impl<I: I2c> Lps22hb<I> {
+    /// Checks the device's identity and starts it at `rate`, with block
+    /// data update.
+    pub fn init(&mut self, rate: Lps22hbRate) -> Result<(), SensorError> {
+        if self.read(WHO_AM_I)? != Self::ID {
+            return Err(SensorError::WrongDevice);
+        }
+
+        // ODR | BDU
+        bus(self.i2c.write(self.address, &[Self::CTRL_REG1, (rate as u8) << 4 | 0x02]))?;
+        self.rate = rate;
+        Ok(())
+    }
+
+    /// Starts a conversion in one-shot mode.
+    pub fn start_one_shot(&mut self) -> Result<(), SensorError> {
+        // IF_ADD_INC | ONE_SHOT
+        bus(self.i2c.write(self.address, &[Self::CTRL_REG2, 0x10 | 0x01]))
+    }
+
+    /// Whether both a new pressure and a new temperature are available.
+    pub fn is_ready(&mut self) -> Result<bool, SensorError> {
+        Ok(self.read(Self::STATUS)? & 0x03 == 0x03)
+    }
+
+    /// Latest pressure in hPa.
+    pub fn read_pressure_hpa(&mut self) -> Result<f32, SensorError> {
+        let mut raw = [0; 3];
+        bus(self.i2c.write_read(self.address, &[Self::PRESS_OUT_XL], &mut raw))?;
+        // 24 bits signés, 4096 LSB/hPa
+        let raw = i32::from_le_bytes([0, raw[0], raw[1], raw[2]]) >> 8;
+        Ok(raw as f32 / 4_096.0)
+    }
+
+    /// Latest temperature in °C.
+    pub fn read_temperature_c(&mut self) -> Result<f32, SensorError> {
+        let mut raw = [0; 2];
+        bus(self.i2c.write_read(self.address, &[Self::TEMP_OUT_L], &mut raw))?;
+        Ok(i16::from_le_bytes(raw) as f32 / 100.0)
+    }
+
+    /// Runs a conversion in one-shot mode and waits for it, or does
+    /// nothing otherwise.
+    pub fn convert(&mut self) -> Result<(), SensorError> {
+        if self.rate != Lps22hbRate::OneShot {
+            return Ok(());
+        }
+        self.start_one_shot()?;
+        let mut polls = 0;
+        while !self.is_ready()? {
+            polls += 1;
+            if polls > READY_POLLS {
+                return Err(SensorError::NoResponse);
+            }
+        }
+        Ok(())
+    }
+
+    fn read(&mut self, register: u8) -> Result<u8, SensorError> {
+        let mut value = [0];
+        bus(self.i2c.write_read(self.address, &[register], &mut value))?;
+        Ok(value[0])
+    }
+}
+
+impl<I: I2c> EnvironmentSource for Lps22hb<I> {
+    fn temperature_c(&mut self) -> Option<f32> {
+        self.convert().ok()?;
+        self.read_temperature_c().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ultrasonic_sensor::{
+        mock::{Bench, EchoScript, MockRegisters},
+        Environment, UltrasonicSensor,
+    };
+
+    /// Registres 0x30 à 0x3F relevés sur un HTS221 : 26 % et 71 % HR, 21 °C
+    /// et 35 °C (bits de poids fort de T1 dans 0x35)
+    const HTS221_CALIBRATION: [u8; 16] = [
+        0x34, 0x8E, 0xA8, 0x18, 0x00, 0xC4, 0x04, 0x00, 0x00, 0x00, 0xD4, 0xD5, 0x22, 0x00, 0xD4, 0x02,
+    ];
+
+    /// HTS221 venant de convertir 28 °C et 50 % HR.
+    fn hts221() -> MockRegisters {
+        let device = MockRegisters::with_increment_bit(0x80);
+        device.set(0, WHO_AM_I, 0xBC);
+        device.load(0, 0x30, &HTS221_CALIBRATION);
+        // H_OUT = -5756, T_OUT = 379
+        device.load(0, 0x28, &[0x84, 0xE9, 0x7B, 0x01]);
+        device.when_written(0, 0x21, 0x01, &[(0, 0x27, 0x03)]);
+        device
+    }
+
+    fn assert_close(value: f32, expected: f32) {
+        assert!((value - expected).abs() < 0.01, "{value} != {expected}");
+    }
+
+    #[test]
+    fn decodes_the_hts221_calibration() {
+        let calibration = Hts221Calibration::from_registers(&HTS221_CALIBRATION);
+
+        assert_eq!((calibration.t0_c, calibration.t1_c), (21.0, 35.0));
+        assert_eq!((calibration.h0_pct, calibration.h1_pct), (26.0, 71.0));
+        assert_eq!((calibration.t0_out, calibration.t1_out), (34, 724));
+        assert_eq!((calibration.h0_out, calibration.h1_out), (4, -10_796));
+        assert_eq!(calibration.humidity_pct(-20_000), 100.0);
+    }
+
+    #[test]
+    fn measures_with_the_hts221() {
+        let device = hts221();
+        let mut sensor = Hts221::new(device.clone());
+        sensor.init(Hts221Rate::OneShot).unwrap();
+        assert_eq!(device.get(0, 0x20), 0x84);
+
+        let (celsius, humidity) = sensor.measure().unwrap();
+        assert_close(celsius, 28.0);
+        assert_close(humidity, 50.0);
+
+        // Une seule conversion pour la température et l'humidité
+        let conversions = || device.writes().iter().filter(|write| **write == (0, 0x21, 0x01)).count();
+        assert_eq!(conversions(), 1);
+        assert!(sensor.temperature_c().is_some());
+        assert!(sensor.humidity_pct().is_some());
+        assert_eq!(conversions(), 2);
+    }
+
+    #[test]
+    fn reads_the_hts221_continuously() {
+        let device = hts221();
+        let mut sensor = Hts221::new(device.clone());
+        sensor.init(Hts221Rate::Hz7).unwrap();
+
+        assert_eq!(device.get(0, 0x20), 0x86);
+        assert_close(sensor.measure().unwrap().0, 28.0);
+        assert!(!device.writes().contains(&(0, 0x21, 0x01)));
+    }
+
+    #[test]
+    fn rejects_other_devices() {
+        let device = hts221();
+        device.set(0, WHO_AM_I, 0xB1);
+        assert_eq!(Hts221::new(device.clone()).init(Hts221Rate::OneShot), Err(SensorError::WrongDevice));
+
+        device.break_bus();
+        assert_eq!(Lps22hb::new(device).init(Lps22hbRate::OneShot), Err(SensorError::BusError));
+    }
+
+    #[test]
+    fn times_out_without_conversion() {
+        let device = hts221();
+        let mut sensor = Hts221::new(device.clone());
+        sensor.init(Hts221Rate::OneShot).unwrap();
+        device.set(0, 0x27, 0x01);
+        device.when_written(0, 0x21, 0x01, &[(0, 0x27, 0x01)]);
+
+        assert_eq!(sensor.measure(), Err(SensorError::NoResponse));
+        assert_eq!(sensor.temperature_c(), None);
+    }
+
+    #[test]
+    fn measures_with_the_lps22hb() {
+        let device = MockRegisters::default();
+        device.set(0, WHO_AM_I, 0xB1);
+        // 1013,25 hPa et 23,5 °C
+        device.load(0, 0x28, &[0x00, 0x54, 0x3F, 0x2E, 0x09]);
+        device.when_written(0, 0x11, 0x11, &[(0, 0x27, 0x03)]);
+        let mut sensor = Lps22hb::new(device.clone());
+        sensor.init(Lps22hbRate::OneShot).unwrap();
+
+        assert_eq!(sensor.temperature_c(), Some(23.5));
+        assert_eq!(sensor.read_pressure_hpa(), Ok(1_013.25));
+        assert_eq!(sensor.humidity_pct(), None);
+
+        sensor.init(Lps22hbRate::Hz25).unwrap();
+        assert_eq!(device.get(0, 0x10), 0x32);
+
+        // Pression négative (sortie en complément à deux)
+        device.load(0, 0x28, &[0x00, 0x00, 0xFF]);
+        assert_eq!(sensor.read_pressure_hpa(), Ok(-16.0));
+    }
+
+    #[test]
+    fn compensates_an_ultrasonic_sensor() {
+        let bench = Bench::new(EchoScript::Silent);
+        let mut sensor = UltrasonicSensor::new(bench.trigger(), bench.echo(), bench.delay());
+        let mut hygrometer = Hts221::new(hts221());
+        hygrometer.init(Hts221Rate::OneShot).unwrap();
+
+        sensor.update_environment(&mut hygrometer);
+        assert_eq!(sensor.environment(), Environment { temperature_dc: 280, humidity_pct: 50 });
+    }
+}
@@ -0,0 +1,325 @@
+use embedded_hal::i2c::I2c;
+use ultrasonic_sensor::SensorError;
+
+use crate::Axes;
+
+/// Heading in degrees, clockwise from magnetic north, of the sensor's X
+/// axis while it lies flat (Z up). `None` if the field has no horizontal
+/// component.
+pub fn heading_deg(field: Axes) -> Option<f32> {
+    if field.x == 0 && field.y == 0 {
+        return None;
+    }
+    Some(normalize_deg(libm::atan2f(field.y as f32, field.x as f32).to_degrees()))
+}
+
+/// Heading of the sensor's X axis whatever its tilt, from the field and
+/// the accelerometer reading at rest (pointing up) in the same frame.
+/// `None` if the field is vertical or the accelerometer is in free fall.
+pub fn tilt_compensated_heading_deg(field: Axes, up: Axes) -> Option<f32> {
+    let field = [field.x as f32, field.y as f32, field.z as f32];
+    let up = [up.x as f32, up.y as f32, up.z as f32];
+    // Est = champ × haut, nord = haut × est : on ne garde que leur
+    // composante sur X, une fois normés
+    let east = cross(field, up);
+    let north = cross(up, east);
+    let (east_norm, north_norm) = (norm(east), norm(north));
+    if east_norm == 0.0 || north_norm == 0.0 {
+        return None;
+    }
+    Some(normalize_deg(libm::atan2f(east[0] / east_norm, north[0] / north_norm).to_degrees()))
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn norm(v: [f32; 3]) -> f32 {
+    libm::sqrtf(v[0] * v[0] + v[1] * v[1] + v[2] * v[2])
+}
+
+/// Brings an angle in [0, 360).
+fn normalize_deg(angle: f32) -> f32 {
+    let angle = angle % 360.0;
+    if angle < 0.0 {
+        angle + 360.0
+    } else {
+        angle
+    }
+}
+
+/// Finds the hard-iron offset, the constant field added by the magnetised
+/// parts around the sensor, from readings taken while the device is turned
+/// a full turn: the offset is the centre of the readings' bounding box.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HardIronCalibration {
+    min: Axes,
+    max: Axes,
+    samples: u32,
+}
+
+impl HardIronCalibration {
+    /// Spread needed on X and Y before trusting the offset: a full turn
+    /// spans twice the horizontal part of the Earth's field, 200 mG at
+    /// least in Europe.
+    const MIN_SPAN_MG: i32 = 300;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update(&mut self, field: Axes) {
+        if self.samples == 0 {
+            self.min = field;
+            self.max = field;
+        } else {
+            self.min = Axes::new(self.min.x.min(field.x), self.min.y.min(field.y), self.min.z.min(field.z));
+            self.max = Axes::new(self.max.x.max(field.x), self.max.y.max(field.y), self.max.z.max(field.z));
+        }
+        self.samples += 1;
+    }
+
+    /// Whether the readings cover enough of a turn.
+    pub fn is_complete(&self) -> bool {
+        self.samples > 0 && self.max.x - self.min.x >= Self::MIN_SPAN_MG && self.max.y - self.min.y >= Self::MIN_SPAN_MG
+    }
+
+    /// Offset to give to [`Lis3mdl::with_hard_iron`], once the readings
+    /// cover enough of a turn. Z is only corrected if it moved as much.
+    pub fn offset(&self) -> Option<Axes> {
+        if !self.is_complete() {
+            return None;
+        }
+        let z = if self.max.z - self.min.z >= Self::MIN_SPAN_MG {
+            (self.max.z + self.min.z) / 2
+        } else {
+            0
+        };
+        Some(Axes::new((self.max.x + self.min.x) / 2, (self.max.y + self.min.y) / 2, z))
+    }
+}
+
+/// Output data rate of the LIS3MDL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MagRate {
+    Hz0_625 = 0b000,
+    Hz1_25 = 0b001,
+    Hz2_5 = 0b010,
+    Hz5 = 0b011,
+    Hz10 = 0b100,
+    Hz20 = 0b101,
+    Hz40 = 0b110,
+    Hz80 = 0b111,
+}
+
+/// Magnetometer full scale, with its `CTRL_REG2` encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MagScale {
+    Gauss4 = 0b00,
+    Gauss8 = 0b01,
+    Gauss12 = 0b10,
+    Gauss16 = 0b11,
+}
+
+impl MagScale {
+    const fn lsb_per_gauss(self) -> i64 {
+        match self {
+            Self::Gauss4 => 6_842,
+            Self::Gauss8 => 3_421,
+            Self::Gauss12 => 2_281,
+            Self::Gauss16 => 1_711,
+        }
+    }
+}
+
+/// ST LIS3MDL magnetometer, in continuous mode.
+///
+/// The readings are corrected by the hard-iron offset given to
+/// [`Self::with_hard_iron`], which [`HardIronCalibration`] finds.
+pub struct Lis3mdl<I> {
+    i2c: I,
+    address: u8,
+    rate: MagRate,
+    scale: MagScale,
+    hard_iron: Axes,
+}
+
+impl<I> Lis3mdl<I> {
+    /// SA1 low, as on the B-L475E-IOT01A.
+    pub const DEFAULT_ADDRESS: u8 = 0x1E;
+    const ID: u8 = 0x3D;
+    const WHO_AM_I: u8 = 0x0F;
+    const CTRL_REG1: u8 = 0x20;
+    const CTRL_REG2: u8 = 0x21;
+    const CTRL_REG3: u8 = 0x22;
+    const CTRL_REG4: u8 = 0x23;
+    const CTRL_REG5: u8 = 0x24;
+    const STATUS: u8 = 0x27;
+    const OUT_X_L: u8 = 0x28;
+    const AUTO_INCREMENT: u8 = 0x80;
+
+    /// 10 Hz, ±4 gauss, no hard-iron correction.
+    pub fn new(i2c: I) -> Self {
+        Self {
+            i2c,
+            address: Self::DEFAULT_ADDRESS,
+            rate: MagRate::Hz10,
+            scale: MagScale::Gauss4,
+            hard_iron: Axes::default(),
+        }
+    }
+
+    pub fn with_address(mut self, address: u8) -> Self {
+        self.address = address;
+        self
+    }
+
+    pub fn with_rate(mut self, rate: MagRate, scale: MagScale) -> Self {
+        self.rate = rate;
+        self.scale = scale;
+        self
+    }
+
+    /// Offset, in mG, subtracted from every reading.
+    pub fn with_hard_iron(mut self, offset: Axes) -> Self {
+        self.hard_iron = offset;
+        self
+    }
+
+    /// Replaces the hard-iron offset, e.g. after a new calibration.
+    pub fn set_hard_iron(&mut self, offset: Axes) {
+        self.hard_iron = offset;
+    }
+
+    pub fn hard_iron(&self) -> Axes {
+        self.hard_iron
+    }
+
+    pub fn release(self) -> I {
+        self.i2c
+    }
+}
+
+impl<I: I2c> Lis3mdl<I> {
+    /// Checks the device's identity and starts the continuous conversions
+    /// in ultra-high performance mode, with block data update.
+    pub fn init(&mut self) -> Result<(), SensorError> {
+        if self.read(Self::WHO_AM_I)? != Self::ID {
+            return Err(SensorError::WrongDevice);
+        }
+
+        // OM = ultra-haute performance sur X/Y, puis sur Z (OMZ)
+        self.write(Self::CTRL_REG1, 0x60 | (self.rate as u8) << 2)?;
+        self.write(Self::CTRL_REG2, (self.scale as u8) << 5)?;
+        self.write(Self::CTRL_REG4, 0x0C)?;
+        self.write(Self::CTRL_REG5, 0x40)?;
+        self.write(Self::CTRL_REG3, 0x00)
+    }
+
+    /// Whether a new sample is available on all three axes.
+    pub fn is_ready(&mut self) -> Result<bool, SensorError> {
+        Ok(self.read(Self::STATUS)? & 0x08 != 0)
+    }
+
+    /// Latest field, in mG, hard-iron offset removed.
+    pub fn read_field(&mut self) -> Result<Axes, SensorError> {
+        let mut raw = [0; 6];
+        self.i2c
+            .write_read(self.address, &[Self::OUT_X_L | Self::AUTO_INCREMENT], &mut raw)
+            .map_err(|_| SensorError::BusError)?;
+        let axis = |offset: usize| {
+            let raw = i16::from_le_bytes([raw[offset], raw[offset + 1]]) as i64;
+            (raw * 1_000 / self.scale.lsb_per_gauss()) as i32
+        };
+        Ok(Axes::new(
+            axis(0) - self.hard_iron.x,
+            axis(2) - self.hard_iron.y,
+            axis(4) - self.hard_iron.z,
+        ))
+    }
+
+    /// Heading of the sensor lying flat, see [`heading_deg`].
+    pub fn read_heading_deg(&mut self) -> Result<Option<f32>, SensorError> {
+        Ok(heading_deg(self.read_field()?))
+    }
+
+    fn write(&mut self, register: u8, value: u8) -> Result<(), SensorError> {
+        self.i2c.write(self.address, &[register, value]).map_err(|_| SensorError::BusError)
+    }
+
+    fn read(&mut self, register: u8) -> Result<u8, SensorError> {
+        let mut value = [0];
+        self.i2c.write_read(self.address, &[register], &mut value).map_err(|_| SensorError::BusError)?;
+        Ok(value[0])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ultrasonic_sensor::mock::MockRegisters;
+
+    fn assert_heading(heading: Option<f32>, expected: f32) {
+        let heading = heading.unwrap();
+        assert!((heading - expected).abs() < 0.5, "{heading} != {expected}");
+    }
+
+    #[test]
+    fn computes_the_heading() {
+        // À plat, X vers le nord puis vers l'est ; le champ plonge de 60°
+        assert_heading(heading_deg(Axes::new(250, 0, -430)), 0.0);
+        assert_heading(heading_deg(Axes::new(0, 250, -430)), 90.0);
+        assert_heading(heading_deg(Axes::new(-177, -177, -430)), 225.0);
+        assert_eq!(heading_deg(Axes::new(0, 0, -500)), None);
+
+        let up = Axes::new(0, 0, 1_000);
+        assert_heading(tilt_compensated_heading_deg(Axes::new(0, 250, -430), up), 90.0);
+        // Même cap, capteur incliné de 30° autour de Y : sans compensation
+        // le cap serait faux
+        let tilted_up = Axes::new(-500, 0, 866);
+        let tilted_field = Axes::new(215, 250, -372);
+        assert_heading(tilt_compensated_heading_deg(tilted_field, tilted_up), 90.0);
+        let north_tilted = Axes::new(431, 0, -247);
+        assert_heading(tilt_compensated_heading_deg(north_tilted, tilted_up), 0.0);
+        assert_eq!(tilt_compensated_heading_deg(Axes::new(0, 0, -500), up), None);
+    }
+
+    #[test]
+    fn finds_the_hard_iron_offset() {
+        let mut calibration = HardIronCalibration::new();
+        let offset = Axes::new(120, -80, 0);
+        for (x, y) in [(250, 0), (0, 250), (-250, 0)] {
+            calibration.update(Axes::new(x + offset.x, y + offset.y, -430));
+        }
+        // Il manque le dernier quart de tour sur Y
+        assert_eq!(calibration.offset(), None);
+
+        calibration.update(Axes::new(offset.x, -250 + offset.y, -430));
+        assert_eq!(calibration.offset(), Some(offset));
+    }
+
+    #[test]
+    fn reads_the_corrected_field() {
+        let device = MockRegisters::with_increment_bit(0x80);
+        device.set(0, 0x0F, 0x3D);
+        let mut magnetometer = Lis3mdl::new(device.clone())
+            .with_rate(MagRate::Hz40, MagScale::Gauss8)
+            .with_hard_iron(Axes::new(100, 0, 0));
+        magnetometer.init().unwrap();
+
+        assert_eq!(device.get(0, 0x20), 0x78);
+        assert_eq!(device.get(0, 0x21), 0x20);
+        assert_eq!(device.get(0, 0x22), 0x00);
+
+        // 350 mG sur X à ±8 gauss, moins les 100 mG de l'aimant voisin
+        let x = 1_197i16.to_le_bytes();
+        device.load(0, 0x28, &[x[0], x[1], 0x00, 0x00, 0x00, 0x00]);
+        device.set(0, 0x27, 0x08);
+        assert_eq!(magnetometer.is_ready(), Ok(true));
+        assert_eq!(magnetometer.read_field(), Ok(Axes::new(249, 0, 0)));
+        assert_heading(magnetometer.read_heading_deg().unwrap(), 0.0);
+
+        device.set(0, 0x0F, 0x6A);
+        assert_eq!(magnetometer.init(), Err(SensorError::WrongDevice));
+    }
+}
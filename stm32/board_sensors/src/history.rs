@@ -0,0 +1,476 @@
+//! Append-only record log on a range of flash sectors, to keep the distance
+//! history and the faults across resets.
+//!
+//! The records are written one after the other in fixed-size slots. Once a
+//! sector is full the log starts erasing the next one in the background, so
+//! the next record does not wait for the erase; once the range is full the
+//! oldest sector is dropped. Each slot carries a sequence number and a CRC:
+//! on mount the log finds where it stopped, and a slot torn by a reset
+//! during its write is skipped.
+
+use ultrasonic_sensor::{Distance, SensorError};
+
+use crate::storage::{Flash, StorageError};
+
+const SLOT_SIZE: u32 = 16;
+/// First byte of an erased slot.
+const EMPTY: u8 = 0xFF;
+
+const KIND_BOOT: u8 = 0x01;
+const KIND_DISTANCE: u8 = 0x02;
+const KIND_FAULT: u8 = 0x03;
+
+const FAULT_MISALIGNED: u8 = 0x01;
+const FAULT_SENSOR: u8 = 0x02;
+
+/// Something worth remembering across resets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Record {
+    /// The firmware started.
+    Boot,
+    Distance(Distance),
+    Fault(Fault),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// The sensor tilted away from its mounting position.
+    Misaligned { tilt_deg: u8 },
+    /// A measurement failed.
+    Sensor(SensorError),
+}
+
+/// A record read back from the log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogEntry {
+    /// Increases by one with each record, resets included.
+    pub sequence: u32,
+    pub timestamp_ms: u32,
+    pub record: Record,
+}
+
+fn sensor_error_code(error: SensorError) -> (u8, u32) {
+    match error {
+        SensorError::NoEchoStart => (1, 0),
+        SensorError::EchoTooLong => (2, 0),
+        SensorError::OutOfRange(distance) => (3, distance.as_mm()),
+        SensorError::TooSoon => (4, 0),
+        SensorError::PinError => (5, 0),
+        SensorError::NoResponse => (6, 0),
+        SensorError::BadChecksum => (7, 0),
+        SensorError::BusError => (8, 0),
+        SensorError::WrongDevice => (9, 0),
+        SensorError::InvalidReading => (10, 0),
+        SensorError::InvalidConfig => (11, 0),
+        SensorError::TooClose(distance) => (12, distance.as_mm()),
+        // Erreur d'un pilote plus récent que ce format : relue comme un
+        // emplacement illisible
+        _ => (0, 0),
+    }
+}
+
+fn sensor_error_from_code(code: u8, mm: u32) -> Option<SensorError> {
+    Some(match code {
+        1 => SensorError::NoEchoStart,
+        2 => SensorError::EchoTooLong,
+        3 => SensorError::OutOfRange(Distance::from_mm(mm)),
+        4 => SensorError::TooSoon,
+        5 => SensorError::PinError,
+        6 => SensorError::NoResponse,
+        7 => SensorError::BadChecksum,
+        8 => SensorError::BusError,
+        9 => SensorError::WrongDevice,
+        10 => SensorError::InvalidReading,
+        11 => SensorError::InvalidConfig,
+        12 => SensorError::TooClose(Distance::from_mm(mm)),
+        _ => return None,
+    })
+}
+
+/// CRC-8, polynomial 0x07.
+fn crc8(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |crc, &byte| {
+        (0..8).fold(crc ^ byte, |crc, _| if crc & 0x80 != 0 { (crc << 1) ^ 0x07 } else { crc << 1 })
+    })
+}
+
+/// Slot layout: kind, sequence (LE), timestamp (LE), 6 payload bytes, CRC.
+fn encode(entry: &LogEntry) -> [u8; SLOT_SIZE as usize] {
+    let mut slot = [0; SLOT_SIZE as usize];
+    let (kind, payload) = match entry.record {
+        Record::Boot => (KIND_BOOT, [0; 6]),
+        Record::Distance(distance) => {
+            let mm = distance.as_mm().to_le_bytes();
+            (KIND_DISTANCE, [mm[0], mm[1], mm[2], mm[3], 0, 0])
+        }
+        Record::Fault(Fault::Misaligned { tilt_deg }) => (KIND_FAULT, [FAULT_MISALIGNED, tilt_deg, 0, 0, 0, 0]),
+        Record::Fault(Fault::Sensor(error)) => {
+            let (code, mm) = sensor_error_code(error);
+            let mm = mm.to_le_bytes();
+            (KIND_FAULT, [FAULT_SENSOR, code, mm[0], mm[1], mm[2], mm[3]])
+        }
+    };
+    slot[0] = kind;
+    slot[1..5].copy_from_slice(&entry.sequence.to_le_bytes());
+    slot[5..9].copy_from_slice(&entry.timestamp_ms.to_le_bytes());
+    slot[9..15].copy_from_slice(&payload);
+    slot[15] = crc8(&slot[..15]);
+    slot
+}
+
+/// `None` for an empty, torn or unknown slot.
+fn decode(slot: &[u8; SLOT_SIZE as usize]) -> Option<LogEntry> {
+    if slot[0] == EMPTY || crc8(&slot[..15]) != slot[15] {
+        return None;
+    }
+    let word = |at: usize| u32::from_le_bytes([slot[at], slot[at + 1], slot[at + 2], slot[at + 3]]);
+    let record = match (slot[0], slot[9]) {
+        (KIND_BOOT, _) => Record::Boot,
+        (KIND_DISTANCE, _) => Record::Distance(Distance::from_mm(word(9))),
+        (KIND_FAULT, FAULT_MISALIGNED) => Record::Fault(Fault::Misaligned { tilt_deg: slot[10] }),
+        (KIND_FAULT, FAULT_SENSOR) => Record::Fault(Fault::Sensor(sensor_error_from_code(slot[10], word(11))?)),
+        _ => return None,
+    };
+    Some(LogEntry {
+        sequence: word(1),
+        timestamp_ms: word(5),
+        record,
+    })
+}
+
+/// Append-only log of [`Record`]s on `sectors` flash sectors.
+pub struct RecordLog<F> {
+    flash: F,
+    start: u32,
+    sectors: u32,
+    /// Slot the next record goes to, counted from `start`.
+    next_slot: u32,
+    next_sequence: u32,
+    /// The sector of `next_slot` was erased, or is being erased, when
+    /// `next_slot` is the first slot of a sector.
+    erase_started: bool,
+}
+
+impl<F: Flash> RecordLog<F> {
+    const SLOTS_PER_SECTOR: u32 = F::SECTOR_SIZE / SLOT_SIZE;
+
+    /// Opens the log kept on the `sectors` sectors from `start`, and finds
+    /// where it stopped. A range that never held a log opens empty.
+    ///
+    /// At least 2 sectors are needed: entering a sector drops the oldest
+    /// one. If the log stopped at the end of a sector, the next one is
+    /// erased here.
+    pub fn mount(mut flash: F, start: u32, sectors: u32) -> Result<Self, StorageError> {
+        if sectors < 2 {
+            return Err(StorageError::TooFewSectors);
+        }
+        if !start.is_multiple_of(F::SECTOR_SIZE) {
+            return Err(StorageError::Unaligned);
+        }
+        match sectors.checked_mul(F::SECTOR_SIZE).and_then(|size| start.checked_add(size)) {
+            Some(end) if end <= flash.capacity() => {}
+            _ => return Err(StorageError::OutOfBounds),
+        }
+
+        // Le secteur le plus récent est celui dont le premier
+        // enregistrement a le plus grand numéro
+        let mut newest: Option<(u32, u32)> = None;
+        for sector in 0..sectors {
+            if let Some(entry) = read_slot(&mut flash, start, sector * Self::SLOTS_PER_SECTOR)? {
+                if newest.is_none_or(|(_, sequence)| entry.sequence > sequence) {
+                    newest = Some((sector, entry.sequence));
+                }
+            }
+        }
+
+        let mut log = Self {
+            flash,
+            start,
+            sectors,
+            next_slot: 0,
+            next_sequence: 0,
+            erase_started: false,
+        };
+        if let Some((sector, sequence)) = newest {
+            // Puis on cherche la fin dans ce secteur ; un enregistrement
+            // interrompu occupe tout de même son emplacement
+            let first = sector * Self::SLOTS_PER_SECTOR;
+            log.next_slot = (first + Self::SLOTS_PER_SECTOR) % log.slots();
+            log.next_sequence = sequence.wrapping_add(1);
+            for slot in first..first + Self::SLOTS_PER_SECTOR {
+                let mut bytes = [0; SLOT_SIZE as usize];
+                log.flash.read(log.slot_address(slot), &mut bytes)?;
+                if bytes[0] == EMPTY {
+                    log.next_slot = slot;
+                    break;
+                }
+                if let Some(entry) = decode(&bytes) {
+                    log.next_sequence = entry.sequence.wrapping_add(1);
+                }
+            }
+        }
+        if log.next_slot.is_multiple_of(Self::SLOTS_PER_SECTOR) {
+            log.flash.erase_sector(log.slot_address(log.next_slot))?;
+            log.erase_started = true;
+        }
+        Ok(log)
+    }
+
+    fn slots(&self) -> u32 {
+        self.sectors * Self::SLOTS_PER_SECTOR
+    }
+
+    fn slot_address(&self, slot: u32) -> u32 {
+        self.start + slot * SLOT_SIZE
+    }
+
+    /// Number of records the log keeps at least before dropping the oldest
+    /// ones.
+    pub fn capacity(&self) -> u32 {
+        (self.sectors - 1) * Self::SLOTS_PER_SECTOR
+    }
+
+    /// Sequence number the next record will get.
+    pub fn next_sequence(&self) -> u32 {
+        self.next_sequence
+    }
+
+    /// Writes `record` after the last one, dropping the oldest sector when
+    /// the log is full. Returns its sequence number.
+    ///
+    /// Fails with [`StorageError::Busy`] while the next sector is still
+    /// being erased, which lasts up to a few hundred milliseconds after a
+    /// sector fills up.
+    pub fn append(&mut self, timestamp_ms: u32, record: Record) -> Result<u32, StorageError> {
+        if self.next_slot.is_multiple_of(Self::SLOTS_PER_SECTOR) {
+            if !self.erase_started {
+                // L'effacement n'a pas pu être lancé à l'avance
+                self.flash.erase_sector(self.slot_address(self.next_slot))?;
+                self.erase_started = true;
+            } else if self.flash.is_busy()? {
+                return Err(StorageError::Busy);
+            }
+        }
+        let entry = LogEntry {
+            sequence: self.next_sequence,
+            timestamp_ms,
+            record,
+        };
+        self.flash.program(self.slot_address(self.next_slot), &encode(&entry))?;
+        self.next_slot = (self.next_slot + 1) % self.slots();
+        self.next_sequence = self.next_sequence.wrapping_add(1);
+        self.erase_started = false;
+        if self.next_slot.is_multiple_of(Self::SLOTS_PER_SECTOR) {
+            // En cas d'échec, le prochain enregistrement réessaie
+            self.erase_started = self.flash.start_erase_sector(self.slot_address(self.next_slot)).is_ok();
+        }
+        Ok(entry.sequence)
+    }
+
+    /// The records kept, oldest first. Torn records are skipped.
+    pub fn entries(&mut self) -> Entries<'_, F> {
+        // Le secteur suivant celui en cours contient les plus anciens
+        let current_sector = self.next_slot / Self::SLOTS_PER_SECTOR;
+        let first = (current_sector + 1) % self.sectors * Self::SLOTS_PER_SECTOR;
+        // Si le secteur en cours n'est pas encore entamé, il est effacé
+        // ou en cours d'effacement : on le saute
+        let remaining = self.capacity() + self.next_slot % Self::SLOTS_PER_SECTOR;
+        Entries {
+            log: self,
+            slot: first,
+            remaining,
+        }
+    }
+
+    /// Erases the whole log. Sequence numbers keep increasing.
+    pub fn clear(&mut self) -> Result<(), StorageError> {
+        for sector in 0..self.sectors {
+            self.flash.erase_sector(self.start + sector * F::SECTOR_SIZE)?;
+        }
+        self.next_slot = 0;
+        self.erase_started = true;
+        Ok(())
+    }
+
+    pub fn release(self) -> F {
+        self.flash
+    }
+}
+
+fn read_slot<F: Flash>(flash: &mut F, start: u32, slot: u32) -> Result<Option<LogEntry>, StorageError> {
+    let mut bytes = [0; SLOT_SIZE as usize];
+    flash.read(start + slot * SLOT_SIZE, &mut bytes)?;
+    Ok(decode(&bytes))
+}
+
+/// Iterator over the records of a [`RecordLog`], oldest first.
+pub struct Entries<'a, F> {
+    log: &'a mut RecordLog<F>,
+    slot: u32,
+    remaining: u32,
+}
+
+impl<F: Flash> Iterator for Entries<'_, F> {
+    type Item = Result<LogEntry, StorageError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.remaining > 0 {
+            let slot = self.slot;
+            self.slot = (self.slot + 1) % self.log.slots();
+            self.remaining -= 1;
+            match read_slot(&mut self.log.flash, self.log.start, slot) {
+                // Un secteur jamais écrit ne contient pas que des
+                // enregistrements du journal
+                Ok(Some(entry)) if entry.sequence < self.log.next_sequence => return Some(Ok(entry)),
+                Ok(_) => {}
+                Err(error) => {
+                    self.remaining = 0;
+                    return Some(Err(error));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockNorChip;
+    use crate::storage::{Mx25r6435f, RamFlash};
+    use ultrasonic_sensor::mock::{Bench, EchoScript};
+
+    const SLOTS: u32 = 4_096 / SLOT_SIZE;
+
+    fn records(log: &mut RecordLog<RamFlash<16_384>>) -> Vec<LogEntry> {
+        log.entries().collect::<Result<_, _>>().unwrap()
+    }
+
+    #[test]
+    fn round_trips_every_record() {
+        let mut log = RecordLog::mount(RamFlash::<16_384>::new(), 4_096, 2).unwrap();
+        let written = [
+            Record::Boot,
+            Record::Distance(Distance::from_mm(1_234)),
+            Record::Fault(Fault::Misaligned { tilt_deg: 12 }),
+            Record::Fault(Fault::Sensor(SensorError::OutOfRange(Distance::from_mm(4_500)))),
+            Record::Fault(Fault::Sensor(SensorError::WrongDevice)),
+            Record::Fault(Fault::Sensor(SensorError::InvalidReading)),
+            Record::Fault(Fault::Sensor(SensorError::TooClose(Distance::from_mm(12)))),
+        ];
+        for (timestamp_ms, record) in written.iter().enumerate() {
+            log.append(timestamp_ms as u32 * 1_000, *record).unwrap();
+        }
+
+        let read = records(&mut log);
+        assert_eq!(read.iter().map(|entry| entry.record).collect::<Vec<_>>(), written);
+        assert_eq!(read[3].sequence, 3);
+        assert_eq!(read[3].timestamp_ms, 3_000);
+        // Le secteur 0 n'appartient pas au journal
+        assert!(log.release().as_bytes()[..4_096].iter().all(|&byte| byte == 0xFF));
+    }
+
+    #[test]
+    fn survives_a_reset() {
+        let mut log = RecordLog::mount(RamFlash::<16_384>::new(), 0, 4).unwrap();
+        log.append(0, Record::Boot).unwrap();
+        log.append(10, Record::Distance(Distance::from_cm(42))).unwrap();
+
+        let mut log = RecordLog::mount(log.release(), 0, 4).unwrap();
+        assert_eq!(log.next_sequence(), 2);
+        assert_eq!(log.append(20, Record::Boot), Ok(2));
+        let read = records(&mut log);
+        assert_eq!(read.len(), 3);
+        assert_eq!(read[1].record, Record::Distance(Distance::from_cm(42)));
+    }
+
+    #[test]
+    fn drops_the_oldest_sector_when_full() {
+        let mut log = RecordLog::mount(RamFlash::<16_384>::new(), 0, 3).unwrap();
+        let total = 3 * SLOTS + 10;
+        for i in 0..total {
+            log.append(i, Record::Distance(Distance::from_mm(i))).unwrap();
+        }
+
+        // 10 enregistrements dans le secteur en cours, plus les 2 secteurs
+        // précédents
+        let check = |log: &mut RecordLog<RamFlash<16_384>>| {
+            let read = records(log);
+            assert_eq!(read.len() as u32, log.capacity() + 10);
+            assert_eq!(read[0].sequence, total - log.capacity() - 10);
+            assert!(read.windows(2).all(|pair| pair[1].sequence == pair[0].sequence + 1));
+            assert_eq!(read.last().unwrap().sequence, total - 1);
+        };
+        check(&mut log);
+        let mut log = RecordLog::mount(log.release(), 0, 3).unwrap();
+        check(&mut log);
+        assert_eq!(log.next_sequence(), total);
+    }
+
+    #[test]
+    fn mounts_on_a_sector_boundary() {
+        let mut log = RecordLog::mount(RamFlash::<16_384>::new(), 0, 2).unwrap();
+        for i in 0..SLOTS {
+            log.append(i, Record::Boot).unwrap();
+        }
+
+        // Le secteur 0 est plein : le suivant est effacé au montage
+        let mut log = RecordLog::mount(log.release(), 0, 2).unwrap();
+        assert_eq!(records(&mut log).len() as u32, SLOTS);
+        log.append(SLOTS, Record::Boot).unwrap();
+        let read = records(&mut log);
+        assert_eq!(read.len() as u32, SLOTS + 1);
+        assert_eq!(read.last().unwrap().sequence, SLOTS);
+    }
+
+    #[test]
+    fn skips_a_torn_record() {
+        let mut log = RecordLog::mount(RamFlash::<16_384>::new(), 0, 2).unwrap();
+        log.append(0, Record::Boot).unwrap();
+        log.append(5, Record::Distance(Distance::from_mm(800))).unwrap();
+        let mut flash = log.release();
+        // Coupure pendant l'écriture du 3e : seuls quelques bits sont écrits
+        flash.program(2 * SLOT_SIZE, &[KIND_DISTANCE, 0x02]).unwrap();
+
+        let mut log = RecordLog::mount(flash, 0, 2).unwrap();
+        log.append(10, Record::Boot).unwrap();
+        let read = records(&mut log);
+        assert_eq!(read.iter().map(|entry| entry.sequence).collect::<Vec<_>>(), [0, 1, 2]);
+        assert_eq!(read[2].timestamp_ms, 10);
+    }
+
+    #[test]
+    fn clears_and_checks_its_range() {
+        let mut log = RecordLog::mount(RamFlash::<16_384>::new(), 0, 2).unwrap();
+        log.append(0, Record::Boot).unwrap();
+        log.clear().unwrap();
+        assert!(records(&mut log).is_empty());
+        assert_eq!(log.append(1, Record::Boot), Ok(1));
+
+        assert_eq!(RecordLog::mount(RamFlash::<16_384>::new(), 100, 2).err(), Some(StorageError::Unaligned));
+        assert_eq!(RecordLog::mount(RamFlash::<16_384>::new(), 8_192, 3).err(), Some(StorageError::OutOfBounds));
+        assert_eq!(RecordLog::mount(RamFlash::<16_384>::new(), 0, 1).err(), Some(StorageError::TooFewSectors));
+    }
+
+    #[test]
+    fn erases_the_next_sector_ahead_of_time() {
+        let bench = Bench::new(EchoScript::Silent);
+        let chip = MockNorChip::default();
+        let flash = Mx25r6435f::new(chip.clone(), bench.delay());
+        let mut log = RecordLog::mount(flash, 0, 2).unwrap();
+        for i in 0..SLOTS {
+            log.append(i, Record::Boot).unwrap();
+        }
+        // L'effacement du secteur suivant part avec le dernier
+        // enregistrement du secteur plein
+        assert_eq!(chip.commands().last(), Some(&(0x20, Some(4_096))));
+
+        // Tant qu'il dure, l'enregistrement suivant est refusé sans attendre
+        chip.busy_for(1);
+        let before_us = bench.now_us();
+        assert_eq!(log.append(SLOTS, Record::Boot), Err(StorageError::Busy));
+        assert_eq!(bench.now_us(), before_us);
+        assert_eq!(log.append(SLOTS, Record::Boot), Ok(SLOTS));
+        assert_eq!(log.entries().count() as u32, SLOTS + 1);
+    }
+}
@@ -0,0 +1,487 @@
+use embedded_hal::i2c::I2c;
+use ultrasonic_sensor::SensorError;
+
+/// A reading on the three axes: mg for the accelerometer, mdps for the
+/// gyroscope, mG for the magnetometer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Axes {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl Axes {
+    pub const fn new(x: i32, y: i32, z: i32) -> Self {
+        Self { x, y, z }
+    }
+
+    /// Average of a batch of samples, e.g. a FIFO readout, `None` if empty.
+    pub fn mean(samples: &[Axes]) -> Option<Axes> {
+        if samples.is_empty() {
+            return None;
+        }
+        let count = samples.len() as i64;
+        let sum = |axis: fn(&Axes) -> i32| samples.iter().map(|sample| axis(sample) as i64).sum::<i64>() / count;
+        Some(Axes::new(sum(|s| s.x) as i32, sum(|s| s.y) as i32, sum(|s| s.z) as i32))
+    }
+
+    fn dot(&self, other: &Axes) -> f32 {
+        (self.x as i64 * other.x as i64 + self.y as i64 * other.y as i64 + self.z as i64 * other.z as i64) as f32
+    }
+}
+
+/// Angle in degrees between two accelerometer readings, i.e. how much the
+/// sensor has tilted from `reference` if both were taken at rest. `None`
+/// if one of them is null (free fall).
+pub fn tilt_deg(reference: Axes, current: Axes) -> Option<f32> {
+    let norms = libm::sqrtf(reference.dot(&reference)) * libm::sqrtf(current.dot(&current));
+    if norms == 0.0 {
+        return None;
+    }
+    let cosine = (reference.dot(&current) / norms).clamp(-1.0, 1.0);
+    Some(libm::acosf(cosine).to_degrees())
+}
+
+/// Output data rate of the LSM6DSL accelerometer, gyroscope and FIFO.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImuRate {
+    PowerDown = 0b0000,
+    Hz12_5 = 0b0001,
+    Hz26 = 0b0010,
+    Hz52 = 0b0011,
+    Hz104 = 0b0100,
+    Hz208 = 0b0101,
+    Hz416 = 0b0110,
+    Hz833 = 0b0111,
+    Hz1660 = 0b1000,
+    Hz3330 = 0b1001,
+    Hz6660 = 0b1010,
+}
+
+/// Accelerometer full scale, with its `CTRL1_XL` encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccelScale {
+    G2 = 0b00,
+    G4 = 0b10,
+    G8 = 0b11,
+    G16 = 0b01,
+}
+
+impl AccelScale {
+    const fn micro_g_per_lsb(self) -> i64 {
+        match self {
+            Self::G2 => 61,
+            Self::G4 => 122,
+            Self::G8 => 244,
+            Self::G16 => 488,
+        }
+    }
+}
+
+/// Gyroscope full scale, with its `CTRL2_G` encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GyroScale {
+    Dps125 = 0b0010,
+    Dps250 = 0b0000,
+    Dps500 = 0b0100,
+    Dps1000 = 0b1000,
+    Dps2000 = 0b1100,
+}
+
+impl GyroScale {
+    const fn micro_dps_per_lsb(self) -> i64 {
+        match self {
+            Self::Dps125 => 4_375,
+            Self::Dps250 => 8_750,
+            Self::Dps500 => 17_500,
+            Self::Dps1000 => 35_000,
+            Self::Dps2000 => 70_000,
+        }
+    }
+}
+
+/// ST LSM6DSL accelerometer and gyroscope.
+///
+/// The accelerometer samples can be queued in the FIFO
+/// ([`Self::with_fifo`]) and read in batches with [`Self::read_fifo`],
+/// which suits slow polling: averaged, they give a steady gravity vector
+/// for [`AlignmentMonitor`].
+pub struct Lsm6dsl<I> {
+    i2c: I,
+    address: u8,
+    accel_rate: ImuRate,
+    accel_scale: AccelScale,
+    gyro_rate: ImuRate,
+    gyro_scale: GyroScale,
+    fifo: bool,
+}
+
+impl<I> Lsm6dsl<I> {
+    /// SA0 low, as on the B-L475E-IOT01A.
+    pub const DEFAULT_ADDRESS: u8 = 0x6A;
+    const ID: u8 = 0x6A;
+    const FIFO_CTRL3: u8 = 0x08;
+    const FIFO_CTRL5: u8 = 0x0A;
+    const WHO_AM_I: u8 = 0x0F;
+    const CTRL1_XL: u8 = 0x10;
+    const CTRL2_G: u8 = 0x11;
+    const CTRL3_C: u8 = 0x12;
+    const STATUS: u8 = 0x1E;
+    const OUTX_L_G: u8 = 0x22;
+    const OUTX_L_XL: u8 = 0x28;
+    const FIFO_STATUS1: u8 = 0x3A;
+    const FIFO_DATA_OUT_L: u8 = 0x3E;
+
+    /// Accelerometer at 104 Hz and ±2 g, gyroscope off, FIFO bypassed.
+    pub fn new(i2c: I) -> Self {
+        Self {
+            i2c,
+            address: Self::DEFAULT_ADDRESS,
+            accel_rate: ImuRate::Hz104,
+            accel_scale: AccelScale::G2,
+            gyro_rate: ImuRate::PowerDown,
+            gyro_scale: GyroScale::Dps250,
+            fifo: false,
+        }
+    }
+
+    pub fn with_address(mut self, address: u8) -> Self {
+        self.address = address;
+        self
+    }
+
+    pub fn with_accelerometer(mut self, rate: ImuRate, scale: AccelScale) -> Self {
+        self.accel_rate = rate;
+        self.accel_scale = scale;
+        self
+    }
+
+    pub fn with_gyroscope(mut self, rate: ImuRate, scale: GyroScale) -> Self {
+        self.gyro_rate = rate;
+        self.gyro_scale = scale;
+        self
+    }
+
+    /// Queues the accelerometer samples in the FIFO, in continuous mode:
+    /// the oldest are dropped once it is full.
+    pub fn with_fifo(mut self) -> Self {
+        self.fifo = true;
+        self
+    }
+
+    pub fn release(self) -> I {
+        self.i2c
+    }
+
+    fn accel(&self, raw: [u8; 6]) -> Axes {
+        let axis = |offset: usize| {
+            let raw = i16::from_le_bytes([raw[offset], raw[offset + 1]]) as i64;
+            (raw * self.accel_scale.micro_g_per_lsb() / 1_000) as i32
+        };
+        Axes::new(axis(0), axis(2), axis(4))
+    }
+}
+
+impl<I: I2c> Lsm6dsl<I> {
+    /// Checks the device's identity and applies the configuration, with
+    /// block data update.
+    pub fn init(&mut self) -> Result<(), SensorError> {
+        if self.read(Self::WHO_AM_I)? != Self::ID {
+            return Err(SensorError::WrongDevice);
+        }
+
+        // BDU | IF_INC
+        self.write(Self::CTRL3_C, 0x40 | 0x04)?;
+        self.write(Self::CTRL1_XL, (self.accel_rate as u8) << 4 | (self.accel_scale as u8) << 2)?;
+        self.write(Self::CTRL2_G, (self.gyro_rate as u8) << 4 | self.gyro_scale as u8)?;
+
+        if self.fifo {
+            // Accéléromètre seul dans la FIFO, sans décimation
+            self.write(Self::FIFO_CTRL3, 0x01)?;
+            self.write(Self::FIFO_CTRL5, (self.accel_rate as u8) << 3 | 0b110)?;
+        } else {
+            self.write(Self::FIFO_CTRL5, 0x00)?;
+        }
+        Ok(())
+    }
+
+    /// Whether a new accelerometer sample is available.
+    pub fn is_accel_ready(&mut self) -> Result<bool, SensorError> {
+        Ok(self.read(Self::STATUS)? & 0x01 != 0)
+    }
+
+    /// Latest accelerometer sample, in mg.
+    pub fn read_accel(&mut self) -> Result<Axes, SensorError> {
+        let mut raw = [0; 6];
+        self.read_into(Self::OUTX_L_XL, &mut raw)?;
+        Ok(self.accel(raw))
+    }
+
+    /// Latest gyroscope sample, in mdps.
+    pub fn read_gyro(&mut self) -> Result<Axes, SensorError> {
+        let mut raw = [0; 6];
+        self.read_into(Self::OUTX_L_G, &mut raw)?;
+        let axis = |offset: usize| {
+            let raw = i16::from_le_bytes([raw[offset], raw[offset + 1]]) as i64;
+            (raw * self.gyro_scale.micro_dps_per_lsb() / 1_000) as i32
+        };
+        Ok(Axes::new(axis(0), axis(2), axis(4)))
+    }
+
+    /// Moves the accelerometer samples queued in the FIFO to `samples`,
+    /// oldest first, and returns how many were read. A sample whose first
+    /// axes were already read is dropped.
+    pub fn read_fifo(&mut self, samples: &mut [Axes]) -> Result<usize, SensorError> {
+        let mut status = [0; 4];
+        self.read_into(Self::FIFO_STATUS1, &mut status)?;
+        // Mots de 16 bits non lus, et axe du prochain mot (0 = X)
+        let mut words = u16::from_le_bytes([status[0], status[1] & 0x07]);
+        let mut pattern = u16::from_le_bytes([status[2], status[3] & 0x03]);
+
+        while !pattern.is_multiple_of(3) && words > 0 {
+            self.read_word()?;
+            pattern += 1;
+            words -= 1;
+        }
+
+        let mut count = 0;
+        while words >= 3 && count < samples.len() {
+            let mut raw = [0; 6];
+            for word in raw.chunks_exact_mut(2) {
+                word.copy_from_slice(&self.read_word()?);
+            }
+            samples[count] = self.accel(raw);
+            count += 1;
+            words -= 3;
+        }
+        Ok(count)
+    }
+
+    fn read_word(&mut self) -> Result<[u8; 2], SensorError> {
+        let mut word = [0; 2];
+        self.read_into(Self::FIFO_DATA_OUT_L, &mut word)?;
+        Ok(word)
+    }
+
+    fn write(&mut self, register: u8, value: u8) -> Result<(), SensorError> {
+        self.i2c.write(self.address, &[register, value]).map_err(|_| SensorError::BusError)
+    }
+
+    fn read(&mut self, register: u8) -> Result<u8, SensorError> {
+        let mut value = [0];
+        self.read_into(register, &mut value)?;
+        Ok(value[0])
+    }
+
+    fn read_into(&mut self, register: u8, buffer: &mut [u8]) -> Result<(), SensorError> {
+        self.i2c.write_read(self.address, &[register], buffer).map_err(|_| SensorError::BusError)
+    }
+}
+
+/// Whether the sensor still points where it was mounted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alignment {
+    Aligned,
+    Misaligned,
+}
+
+/// Watches the gravity vector measured on the sensor's mount, and reports
+/// when it has tilted from its mounting position by more than a threshold,
+/// e.g. after the bumper has been knocked.
+pub struct AlignmentMonitor {
+    max_tilt_deg: f32,
+    min_samples: u8,
+    reference: Option<Axes>,
+    alignment: Alignment,
+    tilt_deg: f32,
+    /// Consecutive samples disagreeing with the current alignment.
+    streak: u8,
+}
+
+impl AlignmentMonitor {
+    /// The first sample given to [`Self::update`] becomes the mounting
+    /// position, unless one is set with [`Self::with_reference`].
+    pub fn new(max_tilt_deg: f32) -> Self {
+        Self {
+            max_tilt_deg,
+            min_samples: 1,
+            reference: None,
+            alignment: Alignment::Aligned,
+            tilt_deg: 0.0,
+            streak: 0,
+        }
+    }
+
+    pub fn with_reference(mut self, reference: Axes) -> Self {
+        self.reference = Some(reference);
+        self
+    }
+
+    /// Number of consecutive samples needed to change the alignment, so
+    /// that a bump or a pothole does not raise the fault.
+    pub fn with_min_samples(mut self, samples: u8) -> Self {
+        self.min_samples = samples.max(1);
+        self
+    }
+
+    pub fn reference(&self) -> Option<Axes> {
+        self.reference
+    }
+
+    pub fn alignment(&self) -> Alignment {
+        self.alignment
+    }
+
+    /// Tilt of the last sample from the mounting position.
+    pub fn tilt_deg(&self) -> f32 {
+        self.tilt_deg
+    }
+
+    /// Feeds an accelerometer sample (ideally averaged). Returns the new
+    /// alignment when it changes.
+    pub fn update(&mut self, accel: Axes) -> Option<Alignment> {
+        let Some(reference) = self.reference else {
+            self.reference = Some(accel);
+            return None;
+        };
+        // En chute libre, la mesure ne dit rien de l'orientation
+        self.tilt_deg = tilt_deg(reference, accel)?;
+
+        let observed = if self.tilt_deg > self.max_tilt_deg {
+            Alignment::Misaligned
+        } else {
+            Alignment::Aligned
+        };
+        if observed == self.alignment {
+            self.streak = 0;
+            return None;
+        }
+
+        self.streak += 1;
+        if self.streak < self.min_samples {
+            return None;
+        }
+        self.streak = 0;
+        self.alignment = observed;
+        Some(observed)
+    }
+
+    /// Takes the current position as the new mounting position, e.g. once
+    /// the sensor has been realigned.
+    pub fn recalibrate(&mut self, accel: Axes) {
+        self.reference = Some(accel);
+        self.alignment = Alignment::Aligned;
+        self.tilt_deg = 0.0;
+        self.streak = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ultrasonic_sensor::mock::MockRegisters;
+
+    fn lsm6dsl() -> MockRegisters {
+        let device = MockRegisters::default();
+        device.set(0, 0x0F, 0x6A);
+        device
+    }
+
+    fn words(values: &[i16]) -> (Vec<u8>, Vec<u8>) {
+        values.iter().map(|value| (value.to_le_bytes()[0], value.to_le_bytes()[1])).unzip()
+    }
+
+    #[test]
+    fn computes_the_tilt() {
+        let level = Axes::new(0, 0, 1_000);
+
+        assert_eq!(tilt_deg(level, Axes::new(0, 0, 980)), Some(0.0));
+        assert!((tilt_deg(level, Axes::new(0, 500, 866)).unwrap() - 30.0).abs() < 0.1);
+        assert!((tilt_deg(level, Axes::new(1_000, 0, 0)).unwrap() - 90.0).abs() < 0.1);
+        assert_eq!(tilt_deg(level, Axes::default()), None);
+        assert_eq!(Axes::mean(&[Axes::new(0, 10, 990), Axes::new(2, 20, 1_010)]), Some(Axes::new(1, 15, 1_000)));
+    }
+
+    #[test]
+    fn configures_rates_and_scales() {
+        let device = lsm6dsl();
+        let mut imu = Lsm6dsl::new(device.clone())
+            .with_accelerometer(ImuRate::Hz52, AccelScale::G4)
+            .with_gyroscope(ImuRate::Hz208, GyroScale::Dps125)
+            .with_fifo();
+        imu.init().unwrap();
+
+        assert_eq!(device.get(0, 0x12), 0x44);
+        assert_eq!(device.get(0, 0x10), 0x38);
+        assert_eq!(device.get(0, 0x11), 0x52);
+        assert_eq!(device.get(0, 0x08), 0x01);
+        assert_eq!(device.get(0, 0x0A), 0x1E);
+
+        // 1 g sur Z à ±4 g, 100 dps sur X à ±125 dps
+        device.load(0, 0x28, &[0x00, 0x00, 0x00, 0x00, 0x08, 0x20]);
+        device.load(0, 0x22, &[0x49, 0x59, 0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(imu.read_accel(), Ok(Axes::new(0, 0, 1_000)));
+        assert_eq!(imu.read_gyro(), Ok(Axes::new(99_999, 0, 0)));
+
+        device.set(0, 0x0F, 0x69);
+        assert_eq!(imu.init(), Err(SensorError::WrongDevice));
+    }
+
+    #[test]
+    fn reads_whole_samples_from_the_fifo() {
+        let device = lsm6dsl();
+        let mut imu = Lsm6dsl::new(device.clone()).with_fifo();
+        imu.init().unwrap();
+
+        // 8 mots en attente, le prochain étant un Z : il est sauté, puis deux
+        // échantillons complets sont lus et le dernier X reste dans la FIFO
+        device.load(0, 0x3A, &[8, 0x00, 2, 0x00]);
+        let (low, high) = words(&[1_000, 0, 0, 16_394, -8_197, 0, 16_394, 0]);
+        device.stream(0, 0x3E, &low);
+        device.stream(0, 0x3F, &high);
+
+        let mut samples = [Axes::default(); 4];
+        assert_eq!(imu.read_fifo(&mut samples), Ok(2));
+        assert_eq!(samples[..2], [Axes::new(0, 0, 1_000), Axes::new(-500, 0, 1_000)]);
+    }
+
+    #[test]
+    fn stops_at_the_end_of_the_buffer() {
+        let device = lsm6dsl();
+        let mut imu = Lsm6dsl::new(device.clone()).with_fifo();
+        imu.init().unwrap();
+
+        device.load(0, 0x3A, &[6, 0x00, 0, 0x00]);
+        let mut samples = [Axes::default(); 1];
+        assert_eq!(imu.read_fifo(&mut samples), Ok(1));
+
+        device.break_bus();
+        assert_eq!(imu.read_fifo(&mut samples), Err(SensorError::BusError));
+    }
+
+    #[test]
+    fn raises_the_fault_once_tilted_for_long_enough() {
+        let mut monitor = AlignmentMonitor::new(10.0).with_min_samples(3);
+        let mounted = Axes::new(0, 0, 1_000);
+        // ~15° vers l'avant
+        let knocked = Axes::new(259, 0, 966);
+
+        assert_eq!(monitor.update(mounted), None);
+        assert_eq!(monitor.reference(), Some(mounted));
+
+        // Un choc isolé ne suffit pas
+        assert_eq!(monitor.update(knocked), None);
+        assert_eq!(monitor.update(mounted), None);
+        assert_eq!(monitor.update(Axes::default()), None);
+
+        assert_eq!(monitor.update(knocked), None);
+        assert_eq!(monitor.update(knocked), None);
+        assert_eq!(monitor.update(knocked), Some(Alignment::Misaligned));
+        assert!((monitor.tilt_deg() - 15.0).abs() < 0.1);
+        assert_eq!(monitor.update(knocked), None);
+
+        monitor.recalibrate(knocked);
+        assert_eq!(monitor.alignment(), Alignment::Aligned);
+        assert_eq!(monitor.update(knocked), None);
+    }
+}
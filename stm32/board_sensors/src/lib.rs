@@ -0,0 +1,24 @@
+//! Drivers for the on-board chips of the B-L475E-IOT01A Discovery kit, on
+//! embedded-hal 1.0: HTS221 and LPS22HB ambient sensors, LSM6DSL inertial
+//! module, LIS3MDL magnetometer and MX25R6435F NOR flash, with the record
+//! log kept on the latter.
+//!
+//! The ambient sensors feed the speed of sound of the ultrasonic sensors
+//! through [`EnvironmentSource`](ultrasonic_sensor::EnvironmentSource), and
+//! the drivers report the same [`SensorError`](ultrasonic_sensor::SensorError).
+
+#![cfg_attr(not(test), no_std)]
+
+mod climate;
+mod compass;
+mod history;
+mod imu;
+#[cfg(test)]
+mod mock;
+mod storage;
+
+pub use climate::{Hts221, Hts221Rate, Lps22hb, Lps22hbRate};
+pub use compass::{heading_deg, tilt_compensated_heading_deg, HardIronCalibration, Lis3mdl, MagRate, MagScale};
+pub use history::{Entries, Fault, LogEntry, Record, RecordLog};
+pub use imu::{tilt_deg, AccelScale, Alignment, AlignmentMonitor, Axes, GyroScale, ImuRate, Lsm6dsl};
+pub use storage::{Flash, Mx25r6435f, QspiBus, RamFlash, StorageError};
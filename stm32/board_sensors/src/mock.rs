@@ -0,0 +1,116 @@
+//! Host-side double of the MX25R6435F, for the tests of the flash driver and
+//! of the record log.
+
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use crate::storage::QspiBus;
+
+struct NorState {
+    memory: HashMap<u32, u8>,
+    commands: Vec<(u8, Option<u32>)>,
+    write_enabled: bool,
+    busy_polls: u32,
+    fault: bool,
+}
+
+/// Error of a [`MockNorChip`] whose bus was broken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BrokenBus;
+
+/// MX25R6435F behind a QSPI controller, with the commands its driver uses.
+/// Clones share the same chip.
+#[derive(Clone)]
+pub struct MockNorChip(Rc<RefCell<NorState>>);
+
+impl Default for MockNorChip {
+    fn default() -> Self {
+        Self(Rc::new(RefCell::new(NorState {
+            memory: HashMap::new(),
+            commands: Vec::new(),
+            write_enabled: false,
+            busy_polls: 0,
+            fault: false,
+        })))
+    }
+}
+
+impl MockNorChip {
+    const PAGE_SIZE: u32 = 256;
+    const SECTOR_SIZE: u32 = 4_096;
+
+    /// Every command so far but the status reads, with its address.
+    pub fn commands(&self) -> Vec<(u8, Option<u32>)> {
+        self.0.borrow().commands.clone()
+    }
+
+    /// The next `polls` status reads report a write in progress.
+    pub fn busy_for(&self, polls: u32) {
+        self.0.borrow_mut().busy_polls = polls;
+    }
+
+    /// Makes every command fail, as with a misconfigured controller.
+    pub fn break_bus(&self) {
+        self.0.borrow_mut().fault = true;
+    }
+}
+
+impl QspiBus for MockNorChip {
+    type Error = BrokenBus;
+
+    fn read(&mut self, instruction: u8, address: Option<u32>, _dummy_cycles: u8, buffer: &mut [u8]) -> Result<(), BrokenBus> {
+        let mut state = self.0.borrow_mut();
+        if state.fault {
+            return Err(BrokenBus);
+        }
+        if instruction != 0x05 {
+            state.commands.push((instruction, address));
+        }
+        match instruction {
+            0x9F => buffer.copy_from_slice(&[0xC2, 0x28, 0x17][..buffer.len()]),
+            0x05 => {
+                let busy = state.busy_polls > 0;
+                state.busy_polls = state.busy_polls.saturating_sub(1);
+                buffer.fill(u8::from(busy) | u8::from(state.write_enabled) << 1);
+            }
+            0x03 => {
+                let start = address.unwrap_or_default();
+                for (offset, byte) in buffer.iter_mut().enumerate() {
+                    *byte = state.memory.get(&(start + offset as u32)).copied().unwrap_or(0xFF);
+                }
+            }
+            _ => panic!("unexpected read command {instruction:#04x}"),
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, instruction: u8, address: Option<u32>, data: &[u8]) -> Result<(), BrokenBus> {
+        let mut state = self.0.borrow_mut();
+        if state.fault {
+            return Err(BrokenBus);
+        }
+        state.commands.push((instruction, address));
+        match instruction {
+            0x06 => state.write_enabled = true,
+            // Sans WREN, la puce ignore l'écriture
+            0x02 if state.write_enabled => {
+                let start = address.unwrap_or_default();
+                let page = start - start % Self::PAGE_SIZE;
+                for (offset, value) in data.iter().enumerate() {
+                    // Au-delà de la fin de page, l'adresse reboucle au début
+                    let byte_address = page + (start + offset as u32) % Self::PAGE_SIZE;
+                    let byte = state.memory.entry(byte_address).or_insert(0xFF);
+                    *byte &= value;
+                }
+                state.write_enabled = false;
+            }
+            0x20 if state.write_enabled => {
+                let sector = address.unwrap_or_default() / Self::SECTOR_SIZE;
+                state.memory.retain(|byte_address, _| byte_address / Self::SECTOR_SIZE != sector);
+                state.write_enabled = false;
+            }
+            0x02 | 0x20 => {}
+            _ => panic!("unexpected write command {instruction:#04x}"),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,371 @@
+use embedded_hal::delay::DelayNs;
+
+/// Reasons why a flash operation failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageError {
+    /// The operation goes past the end of the memory.
+    OutOfBounds,
+    /// An erase address is not at the start of a sector.
+    Unaligned,
+    /// The transfer with the memory failed.
+    BusError,
+    /// The memory stayed busy longer than its datasheet allows.
+    Timeout,
+    /// The memory did not identify as the expected part.
+    WrongDevice,
+    /// A sector erase started in the background is still running.
+    Busy,
+    /// A record log was given fewer than the 2 sectors it needs.
+    TooFewSectors,
+}
+
+/// NOR flash memory: erasing a sector sets all its bits to 1, programming
+/// can only clear bits.
+pub trait Flash {
+    /// Smallest erasable unit, in bytes.
+    const SECTOR_SIZE: u32;
+
+    /// Size of the memory, in bytes.
+    fn capacity(&self) -> u32;
+
+    fn read(&mut self, address: u32, buffer: &mut [u8]) -> Result<(), StorageError>;
+
+    /// Programs `data` at `address`, which should have been erased.
+    fn program(&mut self, address: u32, data: &[u8]) -> Result<(), StorageError>;
+
+    /// Erases the sector starting at `address`.
+    fn erase_sector(&mut self, address: u32) -> Result<(), StorageError>;
+
+    /// Starts erasing the sector at `address` and returns without waiting
+    /// for the erase to end, see [`Self::is_busy`]. The other operations
+    /// wait for it first. By default the sector is erased right away.
+    fn start_erase_sector(&mut self, address: u32) -> Result<(), StorageError> {
+        self.erase_sector(address)
+    }
+
+    /// Whether an erase started with [`Self::start_erase_sector`] is still
+    /// running.
+    fn is_busy(&mut self) -> Result<bool, StorageError> {
+        Ok(false)
+    }
+}
+
+fn check_bounds(capacity: u32, address: u32, len: usize) -> Result<(), StorageError> {
+    match address.checked_add(len as u32) {
+        Some(end) if end <= capacity => Ok(()),
+        _ => Err(StorageError::OutOfBounds),
+    }
+}
+
+/// [`Flash`] kept in RAM, with the same erase and program rules as a NOR
+/// flash: to test what is built on top of a flash on the host, or to run
+/// without one.
+pub struct RamFlash<const N: usize> {
+    memory: [u8; N],
+}
+
+impl<const N: usize> RamFlash<N> {
+    /// An erased memory. `N` must be a whole number of sectors.
+    pub const fn new() -> Self {
+        assert!((N as u32).is_multiple_of(Self::SECTOR_SIZE), "RAM flash size must be a whole number of sectors");
+        Self { memory: [0xFF; N] }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.memory
+    }
+}
+
+impl<const N: usize> Default for RamFlash<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Flash for RamFlash<N> {
+    const SECTOR_SIZE: u32 = 4_096;
+
+    fn capacity(&self) -> u32 {
+        N as u32
+    }
+
+    fn read(&mut self, address: u32, buffer: &mut [u8]) -> Result<(), StorageError> {
+        check_bounds(self.capacity(), address, buffer.len())?;
+        let start = address as usize;
+        buffer.copy_from_slice(&self.memory[start..start + buffer.len()]);
+        Ok(())
+    }
+
+    fn program(&mut self, address: u32, data: &[u8]) -> Result<(), StorageError> {
+        check_bounds(self.capacity(), address, data.len())?;
+        let start = address as usize;
+        for (byte, value) in self.memory[start..start + data.len()].iter_mut().zip(data) {
+            *byte &= value;
+        }
+        Ok(())
+    }
+
+    fn erase_sector(&mut self, address: u32) -> Result<(), StorageError> {
+        if !address.is_multiple_of(Self::SECTOR_SIZE) {
+            return Err(StorageError::Unaligned);
+        }
+        check_bounds(self.capacity(), address, Self::SECTOR_SIZE as usize)?;
+        let start = address as usize;
+        self.memory[start..start + Self::SECTOR_SIZE as usize].fill(0xFF);
+        Ok(())
+    }
+}
+
+/// A (Q)SPI controller able to run one flash command: an instruction byte,
+/// then a 24-bit address if any, then dummy cycles, then the data phase.
+/// Which lines each phase uses is up to the implementation.
+pub trait QspiBus {
+    type Error;
+
+    fn read(&mut self, instruction: u8, address: Option<u32>, dummy_cycles: u8, buffer: &mut [u8]) -> Result<(), Self::Error>;
+
+    fn write(&mut self, instruction: u8, address: Option<u32>, data: &[u8]) -> Result<(), Self::Error>;
+}
+
+/// Macronix MX25R6435F, the 64 Mbit NOR flash of the B-L475E-IOT01A, with
+/// its single-line commands.
+pub struct Mx25r6435f<Q, D> {
+    bus: Q,
+    delay: D,
+    /// A sector erase started in the background may still be running.
+    erasing: bool,
+}
+
+impl<Q, D> Mx25r6435f<Q, D> {
+    pub const CAPACITY: u32 = 8 * 1024 * 1024;
+    pub const PAGE_SIZE: u32 = 256;
+    const JEDEC_ID: [u8; 3] = [0xC2, 0x28, 0x17];
+
+    const READ: u8 = 0x03;
+    const PAGE_PROGRAM: u8 = 0x02;
+    const SECTOR_ERASE: u8 = 0x20;
+    const WRITE_ENABLE: u8 = 0x06;
+    const READ_STATUS: u8 = 0x05;
+    const READ_ID: u8 = 0x9F;
+    /// Write in progress.
+    const WIP: u8 = 0x01;
+
+    const POLL_US: u32 = 20;
+    /// Maximum page program and sector erase times (tPP, tSE).
+    const PROGRAM_TIMEOUT_US: u32 = 10_000;
+    const ERASE_TIMEOUT_US: u32 = 240_000;
+
+    pub fn new(bus: Q, delay: D) -> Self {
+        Self {
+            bus,
+            delay,
+            erasing: false,
+        }
+    }
+
+    pub fn release(self) -> (Q, D) {
+        (self.bus, self.delay)
+    }
+}
+
+impl<Q: QspiBus, D: DelayNs> Mx25r6435f<Q, D> {
+    /// Checks the JEDEC ID of the memory.
+    pub fn init(&mut self) -> Result<(), StorageError> {
+        let mut id = [0; 3];
+        self.bus.read(Self::READ_ID, None, 0, &mut id).map_err(|_| StorageError::BusError)?;
+        if id != Self::JEDEC_ID {
+            return Err(StorageError::WrongDevice);
+        }
+        Ok(())
+    }
+
+    fn write_enable(&mut self) -> Result<(), StorageError> {
+        self.bus.write(Self::WRITE_ENABLE, None, &[]).map_err(|_| StorageError::BusError)
+    }
+
+    fn write_in_progress(&mut self) -> Result<bool, StorageError> {
+        let mut status = [0];
+        self.bus.read(Self::READ_STATUS, None, 0, &mut status).map_err(|_| StorageError::BusError)?;
+        Ok(status[0] & Self::WIP != 0)
+    }
+
+    /// Polls the status register until the current program or erase is
+    /// over.
+    fn wait_ready(&mut self, timeout_us: u32) -> Result<(), StorageError> {
+        let mut waited_us = 0;
+        loop {
+            if !self.write_in_progress()? {
+                return Ok(());
+            }
+            if waited_us > timeout_us {
+                return Err(StorageError::Timeout);
+            }
+            self.delay.delay_us(Self::POLL_US);
+            waited_us += Self::POLL_US;
+        }
+    }
+
+    /// Waits for a background erase, if any: the chip ignores the other
+    /// commands until it is over.
+    fn finish_erase(&mut self) -> Result<(), StorageError> {
+        if self.erasing {
+            self.wait_ready(Self::ERASE_TIMEOUT_US)?;
+            self.erasing = false;
+        }
+        Ok(())
+    }
+}
+
+impl<Q: QspiBus, D: DelayNs> Flash for Mx25r6435f<Q, D> {
+    const SECTOR_SIZE: u32 = 4_096;
+
+    fn capacity(&self) -> u32 {
+        Self::CAPACITY
+    }
+
+    fn read(&mut self, address: u32, buffer: &mut [u8]) -> Result<(), StorageError> {
+        check_bounds(Self::CAPACITY, address, buffer.len())?;
+        self.finish_erase()?;
+        self.bus.read(Self::READ, Some(address), 0, buffer).map_err(|_| StorageError::BusError)
+    }
+
+    /// Programs page by page: a page program wraps around at the end of
+    /// its page.
+    fn program(&mut self, mut address: u32, mut data: &[u8]) -> Result<(), StorageError> {
+        check_bounds(Self::CAPACITY, address, data.len())?;
+        self.finish_erase()?;
+        while !data.is_empty() {
+            let room = (Self::PAGE_SIZE - address % Self::PAGE_SIZE) as usize;
+            let (chunk, rest) = data.split_at(room.min(data.len()));
+
+            self.write_enable()?;
+            self.bus.write(Self::PAGE_PROGRAM, Some(address), chunk).map_err(|_| StorageError::BusError)?;
+            self.wait_ready(Self::PROGRAM_TIMEOUT_US)?;
+
+            address += chunk.len() as u32;
+            data = rest;
+        }
+        Ok(())
+    }
+
+    fn erase_sector(&mut self, address: u32) -> Result<(), StorageError> {
+        self.start_erase_sector(address)?;
+        self.finish_erase()
+    }
+
+    /// A sector erase takes up to 240 ms.
+    fn start_erase_sector(&mut self, address: u32) -> Result<(), StorageError> {
+        if !address.is_multiple_of(Self::SECTOR_SIZE) {
+            return Err(StorageError::Unaligned);
+        }
+        check_bounds(Self::CAPACITY, address, Self::SECTOR_SIZE as usize)?;
+        self.finish_erase()?;
+
+        self.write_enable()?;
+        self.bus.write(Self::SECTOR_ERASE, Some(address), &[]).map_err(|_| StorageError::BusError)?;
+        self.erasing = true;
+        Ok(())
+    }
+
+    fn is_busy(&mut self) -> Result<bool, StorageError> {
+        if self.erasing {
+            self.erasing = self.write_in_progress()?;
+        }
+        Ok(self.erasing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockNorChip;
+    use ultrasonic_sensor::mock::{Bench, EchoScript};
+
+    #[test]
+    fn ram_flash_behaves_like_nor() {
+        let mut flash = RamFlash::<8_192>::new();
+        flash.program(10, &[0x0F, 0xAA]).unwrap();
+        flash.program(10, &[0xF3, 0xFF]).unwrap();
+
+        let mut read = [0; 3];
+        flash.read(10, &mut read).unwrap();
+        assert_eq!(read, [0x03, 0xAA, 0xFF]);
+
+        assert_eq!(flash.erase_sector(10), Err(StorageError::Unaligned));
+        flash.erase_sector(0).unwrap();
+        assert!(flash.as_bytes().iter().all(|&byte| byte == 0xFF));
+
+        assert_eq!(flash.read(8_190, &mut read), Err(StorageError::OutOfBounds));
+        assert_eq!(flash.erase_sector(8_192), Err(StorageError::OutOfBounds));
+    }
+
+    #[test]
+    fn programs_across_pages() {
+        let bench = Bench::new(EchoScript::Silent);
+        let chip = MockNorChip::default();
+        let mut flash = Mx25r6435f::new(chip.clone(), bench.delay());
+        flash.init().unwrap();
+
+        let data: Vec<u8> = (0..400).map(|i| i as u8).collect();
+        flash.program(200, &data).unwrap();
+
+        // Une écriture par page touchée, chacune précédée d'un WREN
+        assert_eq!(chip.commands(), [(0x9F, None), (0x06, None), (0x02, Some(200)), (0x06, None), (0x02, Some(256)), (0x06, None), (0x02, Some(512))]);
+        let mut read = vec![0; 400];
+        flash.read(200, &mut read).unwrap();
+        assert_eq!(read, data);
+    }
+
+    #[test]
+    fn erases_and_waits_for_the_chip() {
+        let bench = Bench::new(EchoScript::Silent);
+        let chip = MockNorChip::default();
+        let mut flash = Mx25r6435f::new(chip.clone(), bench.delay());
+        flash.program(4_096, &[0x00]).unwrap();
+
+        chip.busy_for(50);
+        flash.erase_sector(4_096).unwrap();
+        assert!(bench.now_us() >= 1_000);
+        let mut read = [0];
+        flash.read(4_096, &mut read).unwrap();
+        assert_eq!(read, [0xFF]);
+
+        assert_eq!(flash.erase_sector(100), Err(StorageError::Unaligned));
+        assert_eq!(flash.read(Mx25r6435f::<MockNorChip, ()>::CAPACITY, &mut read), Err(StorageError::OutOfBounds));
+
+        chip.busy_for(u32::MAX);
+        assert_eq!(flash.erase_sector(0), Err(StorageError::Timeout));
+    }
+
+    #[test]
+    fn erases_in_the_background() {
+        let bench = Bench::new(EchoScript::Silent);
+        let chip = MockNorChip::default();
+        let mut flash = Mx25r6435f::new(chip.clone(), bench.delay());
+        flash.program(4_096, &[0x00]).unwrap();
+
+        chip.busy_for(3);
+        flash.start_erase_sector(4_096).unwrap();
+        assert_eq!(bench.now_us(), 0);
+        assert_eq!(flash.is_busy(), Ok(true));
+        assert_eq!(flash.is_busy(), Ok(true));
+
+        // La lecture attend la fin de l'effacement
+        let mut read = [0];
+        flash.read(4_096, &mut read).unwrap();
+        assert_eq!(read, [0xFF]);
+        assert!(bench.now_us() > 0);
+        assert_eq!(flash.is_busy(), Ok(false));
+        assert_eq!(chip.commands().last(), Some(&(0x03, Some(4_096))));
+    }
+
+    #[test]
+    fn checks_the_jedec_id() {
+        let bench = Bench::new(EchoScript::Silent);
+        let chip = MockNorChip::default();
+        chip.break_bus();
+        let mut flash = Mx25r6435f::new(chip, bench.delay());
+
+        assert_eq!(flash.init(), Err(StorageError::BusError));
+    }
+}
@@ -6,43 +6,102 @@
 // Halt on panic
 use panic_halt as _;
 
-#[rtic::app(device = stm32f4xx_hal::pac, peripherals = true)] // peripherals = true makes sure that the device handle/field is available for use later in our code
+#[rtic::app(device = stm32f4xx_hal::pac, peripherals = true, dispatchers = [USART1])] // peripherals = true makes sure that the device handle/field is available for use later in our code
 mod app {
-    use rtt_target::{rtt_init_print, rprintln};
+    use core::fmt::Write;
+
+    use radar_telemetry::{ConfigAck, Fault, FaultCode, Heartbeat, Message, Sample, Setting, MAX_FRAME_LEN};
+    use rtt_target::{rprint, rprintln, rtt_init, set_print_channel, UpChannel};
     use stm32f4xx_hal::{
-        gpio::{self, Input, Output, PushPull},
-        pac::TIM2,
+        gpio::{self, Edge, ErasedPin, Input, Output, PushPull},
         pac::TIM1,
+        pac::TIM2,
+        pac::TIM3,
+        pac::TIM4,
+        pac::TIM5,
+        pac::TIM12,
+        pac::USART2,
         prelude::*,
-        timer::{self, Event},
+        serial,
+        timer::{self, Channel1, Event},
     };
+    use ultrasonic_sensor::{
+        parse_command, ApproachTracker, BeepPattern, Beeper, Command, CommandError, Distance, LineEditor, LineEvent,
+        OutputQueue, SensorError, SensorProfile, Servo, Sweep, SweepFrame, UltrasonicSensor, Zone, ZoneClassifier,
+        ZoneThresholds,
+    };
+
+    const BEEP_TICK_MS: u32 = 10;
+    /// Measurement period at boot, changed with the console's `period`.
+    const MEASUREMENT_PERIOD_MS: u32 = 100;
+    const HEARTBEAT_PERIOD_MS: u32 = 1_000;
+    /// 13 steps of 10° from -60° to +60°: one pass every 1.3 s.
+    const SWEEP_STEPS: usize = 13;
+    const CONSOLE_BAUD: u32 = 115_200;
+    const CONSOLE_LINE_LEN: usize = 32;
+    /// Room for the longest reply, `help`, and the echo typed meanwhile.
+    const CONSOLE_OUTPUT_LEN: usize = 512;
+
+    type Sensor = UltrasonicSensor<gpio::PC2<Output<PushPull>>, gpio::PC3<Input>, timer::DelayUs<TIM1>>;
 
     #[shared]
     struct Shared {
+        sensor: Sensor,                     // Capteur : trigger sur PC2, écho sur PC3
+        clock: timer::CounterUs<TIM5>,      // Base de temps libre en µs pour dater les fronts de l'écho
+        beeper: Beeper,                     // Rythme des bips selon la distance
+        telemetry: UpChannel,               // Trames binaires pour l'hôte, canal RTT 1
+        zones: ZoneClassifier,              // Seuils modifiables depuis la console
+        period_ms: u32,                     // Période de mesure courante
     }
 
     // Local resources go here
     #[local]
     struct Local {
-        trigger_pin: gpio::PC2<Output<PushPull>>,   // Pin pour déclencher l'ultrason
-        echo_pin: gpio::PC3<Input>,                 // Pin pour lire l'écho
-        delay: timer::DelayUs<TIM1>,
         timer: timer::CounterUs<TIM2>,
+        beep_timer: timer::CounterMs<TIM4>,
+        uptime_ms: u32,
+        heartbeat_in_ms: u32,                       // Décompte jusqu'au prochain heartbeat
+        buzzer: timer::PwmChannel<TIM3, 0>,        // Buzzer piézo sur PA6 (TIM3_CH1)
+        leds: [ErasedPin<Output<PushPull>>; 3],    // Barre de LED sur PB4, PB5, PB10, facultative
+        approach: ApproachTracker,
+        servo: Servo<timer::PwmChannel<TIM12, 0>>,  // Servo de balayage sur PB14 (TIM12_CH1)
+        sweep: Sweep<SWEEP_STEPS>,
+        scanning: bool,
+        console_tx: serial::Tx<USART2>,             // Console sur l'USART2 : TX sur PA2, RX sur PA3
+        console_rx: serial::Rx<USART2>,
+        console_out: OutputQueue<CONSOLE_OUTPUT_LEN>,  // Réponses en attente d'envoi, un octet par interruption TXE
+        line: LineEditor<CONSOLE_LINE_LEN>,
     }
 
     #[init]
     fn init(mut ctx: init::Context) -> (Shared, Local, init::Monotonics) {
 
-        rtt_init_print!();
+        // Canal 0 : texte pour rprintln!, canal 1 : télémétrie binaire
+        // (radar_telemetry), que l'hôte lit à part
+        let channels = rtt_init! {
+            up: {
+                0: { size: 1024, name: "Terminal" }
+                1: { size: 1024, name: "Telemetry" }
+            }
+        };
+        set_print_channel(channels.up.0);
+        let telemetry = channels.up.1;
 
         let mut dp = ctx.device;
 
         // Sépare le registre GPIOC en différentes broches (pins) pour pouvoir les manipuler individuellement.
+        let gpioa = dp.GPIOA.split();
+        let gpiob = dp.GPIOB.split();
         let gpioc = dp.GPIOC.split();
 
-        // let mut syscfg = dp.SYSCFG.constrain();
+        let mut syscfg = dp.SYSCFG.constrain();
         let trigger_pin = gpioc.pc2.into_push_pull_output();
-        let echo_pin = gpioc.pc3.into_pull_down_input();
+        let mut echo_pin = gpioc.pc3.into_pull_down_input();
+
+        // L'écho déclenche une interruption sur chacun de ses fronts
+        echo_pin.make_interrupt_source(&mut syscfg);
+        echo_pin.trigger_on_edge(&mut dp.EXTI, Edge::RisingFalling);
+        echo_pin.enable_interrupt(&mut dp.EXTI);
 
         // Set up the system clock. We want to run at 8MHz for this one.
         let rcc = dp.RCC.constrain();
@@ -52,25 +111,93 @@ mod app {
         .sysclk(168.MHz())
         .freeze();
 
-        let mut delay = dp.TIM1.delay_us(&clocks);
+        let delay = dp.TIM1.delay_us(&clocks);
         let mut timer = dp.TIM2.counter_us(&clocks);
 
+        // TIM5 is a 32-bit timer: left running over its whole range it gives
+        // µs timestamps that wrap around like a u32
+        let mut clock = dp.TIM5.counter_us(&clocks);
+        clock.start(u32::MAX.micros()).unwrap();
+
         // Kick off the timer with 100 milliseconds timeout first
-        timer.start(100.millis()).unwrap();
+        timer.start(MEASUREMENT_PERIOD_MS.millis()).unwrap();
 
         // Set up to generate interrupt when timer expires
         timer.listen(Event::Update);
 
+        // Buzzer driven at its resonant frequency, switched on and off by the
+        // beep task every 10 ms
+        let mut buzzer = dp.TIM3.pwm_hz(Channel1::new(gpioa.pa6), 2700.Hz(), &clocks).split();
+        buzzer.set_duty(buzzer.get_max_duty() / 2);
+
+        let mut beep_timer = dp.TIM4.counter_ms(&clocks);
+        beep_timer.start(BEEP_TICK_MS.millis()).unwrap();
+        beep_timer.listen(Event::Update);
+
+        // Nothing happens if the LED bar is not fitted
+        let leds = [
+            gpiob.pb4.into_push_pull_output().erase(),
+            gpiob.pb5.into_push_pull_output().erase(),
+            gpiob.pb10.into_push_pull_output().erase(),
+        ];
+
+        let sensor = UltrasonicSensor::new(trigger_pin, echo_pin, delay).with_profile(SensorProfile::HC_SR04);
+        let beeper = Beeper::new(ZoneThresholds::default());
+
+        // 5 cm of hysteresis (10 cm on the outer boundary) and two consecutive
+        // readings, i.e. 200 ms, before a zone change is taken into account
+        let zones = ZoneClassifier::new(ZoneThresholds::default())
+            .with_hysteresis(Distance::from_cm(10), Distance::from_cm(5), Distance::from_cm(5))
+            .with_min_dwell(2);
+
+        // Alarm when the obstacle would be hit within 1.5 s at the current speed
+        let approach = ApproachTracker::new(1_500);
+
+        // With the `scan` feature the sensor sits on a servo sweeping from
+        // -60° to +60°, otherwise the servo holds it straight ahead
+        let scanning = cfg!(feature = "scan");
+        let sweep = Sweep::between(-60, 60);
+        let mut servo_pwm = dp.TIM12.pwm_hz(Channel1::new(gpiob.pb14), 50.Hz(), &clocks).split();
+        servo_pwm.enable();
+        let mut servo = Servo::new(servo_pwm);
+        servo.set_angle(if scanning { sweep.angle_deg() } else { 0 });
+
+        // Line console on USART2, which a Nucleo board routes to the ST-LINK
+        // virtual COM port; an interrupt per byte received, and one per byte
+        // sent while replies are queued
+        let mut console = dp.USART2.serial((gpioa.pa2, gpioa.pa3), CONSOLE_BAUD.bps(), &clocks).unwrap();
+        console.listen(serial::Event::RxNotEmpty);
+        let (mut console_tx, console_rx) = console.split();
+        let mut console_out = OutputQueue::new();
+        write!(console_out, "Radar console, type `help` for the commands\r\n> ");
+        console_tx.listen();
+
         (
             Shared {
                // Initialization of shared resources go here
+               sensor,
+               clock,
+               beeper,
+               telemetry,
+               zones,
+               period_ms: MEASUREMENT_PERIOD_MS,
             },
             Local {
                 // Initialization of local resources go here
-                trigger_pin,                                    // Pin pour déclencher l'ultrason
-                echo_pin,                                       // Pin pour lire l'écho
-                delay,
                 timer,
+                beep_timer,
+                uptime_ms: 0,
+                heartbeat_in_ms: HEARTBEAT_PERIOD_MS,
+                buzzer,
+                leds,
+                approach,
+                servo,
+                sweep,
+                scanning,
+                console_tx,
+                console_rx,
+                console_out,
+                line: LineEditor::new(),
             },
             init::Monotonics()
         )
@@ -84,43 +211,394 @@ mod app {
         }
     }
 
-    #[task(binds = TIM2, local=[trigger_pin, echo_pin, delay, timer])]
-    fn read_sensor(mut ctx: read_sensor::Context) {
-
-        rprintln!("Task : Read sensor");
+    // Three tasks :
+    // start_measurement sends the trigger pulse every period, 100 ms at boot (TIM2), and returns right away
+    // echo_edge timestamps both edges of the echo (EXTI3), the CPU is free during the flight time
+    // report receives the timestamped results through its queue and updates the zone and closing speed feedback,
+    // and the telemetry, and in scan mode turns the servo to the next angle
+    // beep switches the buzzer on and off every 10 ms (TIM4), and sends the telemetry heartbeat every second
+    // console assembles the lines typed on USART2, runs the configuration commands and sends the queued replies
+    #[task(binds = TIM2, local = [timer], shared = [sensor, clock, period_ms])]
+    fn start_measurement(mut ctx: start_measurement::Context) {
 
-        let trigger_pin = ctx.local.trigger_pin;
-        let echo_pin = ctx.local.echo_pin;
-        let delay = ctx.local.delay;
         let timer = ctx.local.timer;
 
-        // Envoyer une impulsion de 10 µs sur le trigger pour démarrer la mesure
-        trigger_pin.set_high();
-        delay.delay_us(10_u32);  // Délai précis de 10 µs
-        trigger_pin.set_low();
+        (ctx.shared.sensor, ctx.shared.clock).lock(|sensor, clock| {
+            // Une mesure encore en attente de son écho est abandonnée
+            let now = clock.now().ticks();
+
+            if let Some(result) = sensor.check_timeout(now) {
+                report::spawn(result, now);
+            }
+
+            if let Err(error) = sensor.start_measurement(&mut || clock.now().ticks()) {
+                report::spawn(Err(error), now);
+            }
+        });
+
+        let period_ms = ctx.shared.period_ms.lock(|period_ms| *period_ms);
+        let _ = timer.start(period_ms.millis());
+    }
+
+    #[task(binds = EXTI3, priority = 2, shared = [sensor, clock])]
+    fn echo_edge(ctx: echo_edge::Context) {
+
+        (ctx.shared.sensor, ctx.shared.clock).lock(|sensor, clock| {
+            let now = clock.now().ticks();
+
+            sensor.echo_pin_mut().clear_interrupt_pending_bit();
+
+            if let Some(result) = sensor.on_echo_edge(now) {
+                report::spawn(result, now);
+            }
+        });
+    }
+
+    #[task(capacity = 4, local = [leds, approach, servo, sweep, scanning], shared = [beeper, telemetry, zones])]
+    fn report(mut ctx: report::Context, result: Result<Distance, SensorError>, timestamp_us: u32) {
+        let approach = ctx.local.approach;
+        let was_alarm = approach.is_alarm();
+
+        // Sans balayage, chaque mesure compte pour la vitesse d'approche
+        let mut centred = true;
+        let mut nearest = None;
+
+        // Le servo a jusqu'à la prochaine mesure pour tourner : au moins
+        // 60 ms, il lui en faut ~20 pour 10°
+        if *ctx.local.scanning {
+            let sweep = ctx.local.sweep;
+            // Deux angles ne voient pas le même obstacle : la vitesse
+            // d'approche ne suit que l'axe
+            centred = sweep.angle_deg() == 0;
+            if let Some(frame) = sweep.record(result.ok()) {
+                ctx.shared.telemetry.lock(|telemetry| send_sweep(telemetry, &frame));
+            }
+            nearest = Some(sweep.frame().nearest().map(|(_, distance)| distance).ok_or(SensorError::EchoTooLong));
+            ctx.local.servo.set_angle(sweep.angle_deg());
+        }
+
+        let (transition, zone, input) = ctx.shared.zones.lock(|zones| {
+            // En balayage, les zones suivent l'obstacle le plus proche sur
+            // tous les angles ; une panne ou la zone aveugle comptent telles
+            // quelles
+            let input = match (nearest, zones.thresholds().classify(result)) {
+                (Some(nearest), Some(zone)) if zone != Zone::Critical => nearest,
+                _ => result,
+            };
+            (zones.update(input), zones.zone(), input)
+        });
+        if let Some(transition) = transition {
+            rprintln!("Left {:?}, entered {:?}", transition.left, transition.entered);
+
+            // Une LED de plus par zone franchie
+            for (index, led) in ctx.local.leds.iter_mut().enumerate() {
+                led.set_state((index < transition.entered.bar_level() as usize).into());
+            }
+        }
+
+        if centred {
+            approach.update(timestamp_us, result.ok());
+        }
+        let alarm = approach.is_alarm();
+        if alarm && !was_alarm {
+            rprintln!("Closing fast: {:?}", approach.approach());
+        }
+
+        // L'alarme de temps avant collision passe en bip continu sans attendre la zone critique
+        ctx.shared.beeper.lock(|beeper| {
+            let pattern = if alarm {
+                BeepPattern::Continuous
+            } else {
+                beeper.pattern_for(zone, input)
+            };
+            beeper.set_pattern(pattern);
+        });
+
+        let message = match result {
+            Ok(distance) => Message::Sample(sample(timestamp_us, Some(distance))),
+            // Rien à portée : une mesure tout de même, sans distance
+            Err(SensorError::EchoTooLong | SensorError::OutOfRange(_)) => Message::Sample(sample(timestamp_us, None)),
+            // Zone aveugle : la distance mesurée, l'obstacle est au plus près
+            Err(SensorError::TooClose(distance)) => Message::Sample(sample(timestamp_us, Some(distance))),
+            Err(error) => Message::Fault(Fault {
+                sensor: 0,
+                timestamp_us,
+                code: fault_code(error),
+            }),
+        };
+        ctx.shared.telemetry.lock(|telemetry| send(telemetry, &message));
+
+        match result {
+            Ok(distance) => rprintln!("Distance : {}", distance),
+            Err(SensorError::NoEchoStart) => rprintln!("No echo: is the sensor connected?"),
+            Err(SensorError::EchoTooLong) => rprintln!("Nothing in range"),
+            Err(SensorError::OutOfRange(distance)) => rprintln!("Out of range: {}", distance),
+            Err(SensorError::TooClose(distance)) => rprintln!("Too close: {}", distance),
+            Err(SensorError::PinError) => rprintln!("Sensor pin error"),
+            Err(SensorError::TooSoon) => rprintln!("Measurement cycle too short"),
+            Err(SensorError::NoResponse) => rprintln!("Sensor not answering"),
+            Err(SensorError::BadChecksum) => rprintln!("Corrupted frame"),
+            Err(SensorError::BusError) => rprintln!("Sensor bus error"),
+            Err(SensorError::WrongDevice) => rprintln!("Unexpected device on the sensor bus"),
+            Err(SensorError::InvalidReading) => rprintln!("Unreliable measurement"),
+            Err(SensorError::InvalidConfig) => rprintln!("Sensor setting not supported"),
+            Err(error) => rprintln!("Sensor error: {:?}", error),
+        }
+    }
+
+    fn sample(timestamp_us: u32, distance: Option<Distance>) -> Sample {
+        Sample {
+            sensor: 0,
+            timestamp_us,
+            distance_mm: distance.map(|distance| distance.as_mm().min(u16::MAX as u32 - 1) as u16),
+        }
+    }
+
+    fn fault_code(error: SensorError) -> FaultCode {
+        match error {
+            SensorError::NoEchoStart => FaultCode::NoEchoStart,
+            SensorError::EchoTooLong => FaultCode::EchoTooLong,
+            SensorError::OutOfRange(_) | SensorError::TooClose(_) => FaultCode::OutOfRange,
+            SensorError::TooSoon => FaultCode::TooSoon,
+            SensorError::PinError => FaultCode::PinError,
+            SensorError::NoResponse => FaultCode::NoResponse,
+            SensorError::BadChecksum => FaultCode::BadChecksum,
+            SensorError::BusError => FaultCode::BusError,
+            SensorError::WrongDevice => FaultCode::WrongDevice,
+            SensorError::InvalidReading => FaultCode::InvalidReading,
+            SensorError::InvalidConfig => FaultCode::InvalidConfig,
+            // Erreur d'un pilote plus récent que le protocole
+            _ => FaultCode::Other(0),
+        }
+    }
+
+    /// Queues one frame on the telemetry channel. A frame that does not fit
+    /// is dropped whole, so the host never sees half a frame.
+    fn send(telemetry: &mut UpChannel, message: &Message) {
+        let mut frame = [0; MAX_FRAME_LEN];
+        if let Ok(len) = message.encode(&mut frame) {
+            telemetry.write(&frame[..len]);
+        }
+    }
+
+    /// One sweep frame to the host, and one line per pass: the distance in
+    /// cm at each angle, `-` where nothing was in range.
+    fn send_sweep(telemetry: &mut UpChannel, frame: &SweepFrame<SWEEP_STEPS>) {
+        let distances = frame.distances.iter().map(|distance| distance.map(|distance| distance.as_cm() as u16));
+        if let Some(sweep) = radar_telemetry::Sweep::new(frame.sweep, frame.first_deg, frame.step_deg, distances) {
+            send(telemetry, &Message::Sweep(sweep));
+        }
 
-        // Attendre que l'écho passe à HIGH
-        while echo_pin.is_low() {
+        rprint!("Sweep {}:", frame.sweep);
+        for (angle, distance) in frame.iter() {
+            match distance {
+                Some(distance) => rprint!(" {}°={}", angle, distance.as_cm()),
+                None => rprint!(" {}°=-", angle),
+            }
         }
+        rprintln!();
+    }
 
-        // // Démarrer le timer pour mesurer la durée de l'écho
-        let start_time = timer.now(); 
+    #[task(binds = TIM4, local = [beep_timer, uptime_ms, heartbeat_in_ms, buzzer], shared = [beeper, telemetry, period_ms])]
+    fn beep(mut ctx: beep::Context) {
+        let beep_timer = ctx.local.beep_timer;
+        let buzzer = ctx.local.buzzer;
 
-        // Attendre que l'écho passe à LOW (c'est la durée du signal)
-        while echo_pin.is_high() {
+        // Le même tick de 10 ms cadence le heartbeat de la télémétrie ; un
+        // décompte séparé, car le temps écoulé reboucle après ~49 jours
+        let uptime_ms = ctx.local.uptime_ms;
+        *uptime_ms = uptime_ms.wrapping_add(BEEP_TICK_MS);
+        let heartbeat_in_ms = ctx.local.heartbeat_in_ms;
+        *heartbeat_in_ms = heartbeat_in_ms.saturating_sub(BEEP_TICK_MS);
+        if *heartbeat_in_ms == 0 {
+            *heartbeat_in_ms = HEARTBEAT_PERIOD_MS;
+            let heartbeat = Message::Heartbeat(Heartbeat {
+                uptime_ms: *uptime_ms,
+                period_ms: ctx.shared.period_ms.lock(|period_ms| *period_ms).min(u16::MAX as u32) as u16,
+            });
+            ctx.shared.telemetry.lock(|telemetry| send(telemetry, &heartbeat));
         }
 
-        // Lire la durée du signal d'écho en µs
-        let echo_time = (timer.now() - start_time).to_micros();
-        let _ = timer.cancel();
+        if ctx.shared.beeper.lock(|beeper| beeper.tick(BEEP_TICK_MS)) {
+            buzzer.enable();
+        } else {
+            buzzer.disable();
+        }
+
+        // L'interruption est déjà levée : `wait` ne fait qu'acquitter le drapeau
+        let _ = beep_timer.wait();
+    }
 
-        // Calculer la distance
-        rprintln!("Duration time : {}",echo_time);               
-        let distance_cm = (echo_time as f64)*17.0/100.0;
-        rprintln!("Distance : {}cm", distance_cm);
+    // Les réponses passent par une file vidée à chaque interruption TXE :
+    // aucune attente sur la ligne, le traitement d'un octet reçu ne prend
+    // que quelques µs, à la même priorité que les mesures et non au-dessus
+    // de l'écho
+    #[task(
+        binds = USART2,
+        local = [console_tx, console_rx, console_out, line],
+        shared = [sensor, beeper, telemetry, zones, period_ms]
+    )]
+    fn console(mut ctx: console::Context) {
+        let tx = ctx.local.console_tx;
+        let out = ctx.local.console_out;
 
-        let _ = timer.start(100.millis());
+        // Lire acquitte aussi les erreurs de réception (débordement, bruit)
+        loop {
+            let byte = match ctx.local.console_rx.read() {
+                Ok(byte) => byte,
+                Err(nb::Error::WouldBlock) => break,
+                Err(nb::Error::Other(_)) => continue,
+            };
+
+            let command = match ctx.local.line.push(byte) {
+                Some(LineEvent::Echo(byte)) => {
+                    write!(out, "{}", byte as char);
+                    continue;
+                }
+                Some(LineEvent::Erase) => {
+                    write!(out, "\x08 \x08");
+                    continue;
+                }
+                Some(LineEvent::TooLong) => {
+                    write!(out, "\r\nerror: line too long\r\n> ");
+                    continue;
+                }
+                Some(LineEvent::Line(line)) => parse_command(line),
+                None => continue,
+            };
+
+            write!(out, "\r\n");
+            match command {
+                Ok(Command::Period { ms }) => {
+                    // Pas de nouveau déclenchement avant la fin du cycle du capteur
+                    let min_ms = ctx.shared.sensor.lock(|sensor| sensor.profile().min_cycle_us.div_ceil(1_000));
+                    let accepted = ms >= min_ms;
+                    let period_ms = ctx.shared.period_ms.lock(|period_ms| {
+                        if accepted {
+                            *period_ms = ms;
+                        }
+                        *period_ms
+                    });
+                    if accepted {
+                        write!(out, "period {} ms\r\n", period_ms);
+                    } else {
+                        write!(out, "error: the sensor needs at least {} ms\r\n", min_ms);
+                    }
+                    ctx.shared.telemetry.lock(|telemetry| {
+                        send(telemetry, &config_ack(Setting::PeriodMs, period_ms as i32, accepted))
+                    });
+                }
+                Ok(Command::Zones(thresholds)) => {
+                    // Le capteur ne distingue rien sous sa zone aveugle
+                    let blind_zone = ctx.shared.sensor.lock(|sensor| sensor.profile().blind_zone);
+                    let accepted = thresholds.critical() >= blind_zone;
+                    let thresholds = ctx.shared.zones.lock(|zones| {
+                        if accepted {
+                            zones.set_thresholds(thresholds);
+                        }
+                        *zones.thresholds()
+                    });
+                    if accepted {
+                        ctx.shared.beeper.lock(|beeper| beeper.set_thresholds(thresholds));
+                        write_zones(out, &thresholds);
+                    } else {
+                        write!(out, "error: the critical zone must cover the {} cm blind zone\r\n", blind_zone.as_cm());
+                    }
+                    ctx.shared.telemetry.lock(|telemetry| {
+                        send(telemetry, &config_ack(Setting::Zones, thresholds.far().as_cm() as i32, accepted))
+                    });
+                }
+                Ok(Command::Temperature { dc }) => {
+                    let speed_mm_s = ctx.shared.sensor.lock(|sensor| {
+                        let mut environment = sensor.environment();
+                        environment.temperature_dc = dc;
+                        sensor.set_environment(environment);
+                        environment.speed_of_sound_mm_s()
+                    });
+                    write_temperature(out, dc, speed_mm_s);
+                    ctx.shared.telemetry.lock(|telemetry| {
+                        send(telemetry, &config_ack(Setting::TemperatureDc, dc as i32, true))
+                    });
+                }
+                Ok(Command::Status) => {
+                    let period_ms = ctx.shared.period_ms.lock(|period_ms| *period_ms);
+                    let (thresholds, zone) = ctx.shared.zones.lock(|zones| (*zones.thresholds(), zones.zone()));
+                    let environment = ctx.shared.sensor.lock(|sensor| sensor.environment());
+                    write!(out, "period {} ms\r\n", period_ms);
+                    write_zones(out, &thresholds);
+                    write_temperature(out, environment.temperature_dc, environment.speed_of_sound_mm_s());
+                    write!(out, "current zone {:?}\r\n", zone);
+                }
+                Ok(Command::Reset) => {
+                    ctx.shared.telemetry.lock(|telemetry| send(telemetry, &config_ack(Setting::Reset, 0, true)));
+                    write!(out, "resetting\r\n");
+                    // Laisser partir la file et le dernier octet avant de redémarrer
+                    while let Some(byte) = out.pop() {
+                        let _ = nb::block!(tx.write(byte));
+                    }
+                    let _ = nb::block!(tx.flush());
+                    cortex_m::peripheral::SCB::sys_reset();
+                }
+                Ok(Command::Help) => {
+                    write!(
+                        out,
+                        "period <ms>                    time between two measurements\r\n\
+                         zones <far> <near> <critical>  zone thresholds in cm\r\n\
+                         temp <C>                       air temperature, e.g. 21.5\r\n\
+                         status                         current settings\r\n\
+                         reset                          restart the radar\r\n"
+                    );
+                }
+                Err(CommandError::Empty) => {}
+                Err(error) => {
+                    write!(out, "error: {}\r\n", error);
+                }
+            }
+            write!(out, "> ");
+        }
+
+        // Autant d'octets que l'émetteur en accepte, le reste à la
+        // prochaine interruption TXE
+        while let Some(byte) = out.peek() {
+            if tx.write(byte).is_err() {
+                break;
+            }
+            out.pop();
+        }
+        if out.is_empty() {
+            tx.unlisten();
+        } else {
+            tx.listen();
+        }
+    }
+
+    fn config_ack(setting: Setting, value: i32, accepted: bool) -> Message {
+        Message::ConfigAck(ConfigAck { setting, value, accepted })
+    }
+
+    fn write_zones(out: &mut OutputQueue<CONSOLE_OUTPUT_LEN>, thresholds: &ZoneThresholds) {
+        write!(
+            out,
+            "zones far {} cm, near {} cm, critical {} cm\r\n",
+            thresholds.far().as_cm(),
+            thresholds.near().as_cm(),
+            thresholds.critical().as_cm()
+        );
+    }
 
+    /// Temperature in tenths of °C without floating point formatting.
+    fn write_temperature(out: &mut OutputQueue<CONSOLE_OUTPUT_LEN>, dc: i16, speed_mm_s: u32) {
+        let sign = if dc < 0 { "-" } else { "" };
+        let dc = dc.unsigned_abs();
+        write!(
+            out,
+            "temperature {}{}.{} C, speed of sound {}.{:03} m/s\r\n",
+            sign,
+            dc / 10,
+            dc % 10,
+            speed_mm_s / 1_000,
+            speed_mm_s % 1_000
+        );
     }
 
 }
@@ -0,0 +1,128 @@
+// #![deny(unsafe_code)]
+#![no_main]
+#![no_std]
+#![allow(unused_must_use)]
+
+use embedded_hal::digital::{ErrorType, InputPin, OutputPin};
+use panic_halt as _;
+use rtt_target::{rtt_init_print, rprintln};
+use stm32f4xx_hal::{
+    gpio::{DynamicPin, PinState},
+    pac::TIM2,
+    pac::TIM1,
+    prelude::*,
+    timer::{self, Event},
+};
+use ultrasonic_sensor::{IoPin, SensorError, SinglePinSensor};
+
+/// Signal pin of the PING))), switched between push-pull output for the
+/// trigger and floating input for the echo.
+pub struct SignalPin(DynamicPin<'C', 2>);
+
+impl ErrorType for SignalPin {
+    type Error = <DynamicPin<'C', 2> as ErrorType>::Error;
+}
+
+impl OutputPin for SignalPin {
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.0.set_high()
+    }
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.0.set_low()
+    }
+}
+
+impl InputPin for SignalPin {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        self.0.is_high()
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        self.0.is_low()
+    }
+}
+
+impl IoPin for SignalPin {
+    fn set_as_output(&mut self) -> Result<(), Self::Error> {
+        self.0.make_push_pull_output_in_state(PinState::Low);
+        Ok(())
+    }
+
+    fn set_as_input(&mut self) -> Result<(), Self::Error> {
+        self.0.make_floating_input();
+        Ok(())
+    }
+}
+
+#[rtic::app(device = stm32f4xx_hal::pac, peripherals = true)]
+mod app {
+    use super::*;
+
+    #[shared]
+    struct Shared {}
+
+    #[local]
+    struct Local {
+        sensor: SinglePinSensor<SignalPin, timer::DelayUs<TIM1>>,
+        timer: timer::CounterUs<TIM2>,
+    }
+
+    #[init]
+    fn init(ctx: init::Context) -> (Shared, Local, init::Monotonics) {
+        rtt_init_print!();
+
+        let dp = ctx.device;
+
+        // Une seule broche pour le trigger et l'écho : SIG du PING))) sur PC2
+        let gpioc = dp.GPIOC.split();
+        let signal_pin = SignalPin(gpioc.pc2.into_dynamic());
+
+        let rcc = dp.RCC.constrain();
+        let clocks = rcc
+            .cfgr
+            .use_hse(8.MHz())
+            .sysclk(168.MHz())
+            .freeze();
+
+        let delay = dp.TIM1.delay_us(&clocks);
+        let mut timer = dp.TIM2.counter_us(&clocks);
+        timer.start(100.millis()).unwrap();
+        timer.listen(Event::Update);
+
+        let sensor = SinglePinSensor::new(signal_pin, delay);
+
+        (
+            Shared {},
+            Local {
+                sensor,
+                timer,
+            },
+            init::Monotonics(),
+        )
+    }
+
+    #[task(binds = TIM2, local=[sensor, timer])]
+    fn read_sensor(ctx: read_sensor::Context) {
+        let timer = ctx.local.timer;
+
+        match ctx.local.sensor.measure_distance(&mut || timer.now().ticks()) {
+            Ok(distance) => rprintln!("Measured distance: {}", distance),
+            Err(SensorError::NoEchoStart) => rprintln!("No echo: is the sensor connected?"),
+            Err(SensorError::EchoTooLong) => rprintln!("Nothing in range"),
+            Err(SensorError::OutOfRange(distance)) => rprintln!("Out of range: {}", distance),
+            Err(SensorError::TooClose(distance)) => rprintln!("Too close: {}", distance),
+            Err(SensorError::PinError) => rprintln!("Sensor pin error"),
+            Err(SensorError::TooSoon) => rprintln!("Measurement cycle too short"),
+            Err(SensorError::NoResponse) => rprintln!("Sensor not answering"),
+            Err(SensorError::BadChecksum) => rprintln!("Corrupted frame"),
+            Err(SensorError::BusError) => rprintln!("Sensor bus error"),
+            Err(SensorError::WrongDevice) => rprintln!("Unexpected device on the sensor bus"),
+            Err(SensorError::InvalidReading) => rprintln!("Unreliable measurement"),
+            Err(SensorError::InvalidConfig) => rprintln!("Sensor setting not supported"),
+            Err(error) => rprintln!("Sensor error: {:?}", error),
+        }
+
+        timer.start(100.millis()).unwrap();
+    }
+}
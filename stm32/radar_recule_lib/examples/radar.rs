@@ -0,0 +1,93 @@
+// #![deny(unsafe_code)]
+#![no_main]
+#![no_std]
+#![allow(unused_must_use)]
+
+use panic_halt as _;
+use rtt_target::{rtt_init_print, rprintln};
+use stm32f4xx_hal::{
+    gpio::{self, Input, Output, PushPull},
+    pac::TIM2,
+    pac::TIM1,
+    prelude::*,
+    timer::{self, Event},
+};
+use ultrasonic_sensor::{DistanceSensor, SensorError, UltrasonicSensor};  // Importation de ton module
+
+// Any `DistanceSensor` fits here, e.g. `A02yyuw<Serial<USART2>>` or
+// `Rcwl1601<I2c<I2C1>, DelayUs<TIM1>>`: only `init` has to change
+type Sensor = UltrasonicSensor<gpio::PC2<Output<PushPull>>, gpio::PC3<Input>, timer::DelayUs<TIM1>>;
+
+#[rtic::app(device = stm32f4xx_hal::pac, peripherals = true)]
+mod app {
+    use super::*;
+
+    #[shared]
+    struct Shared {}
+
+    #[local]
+    struct Local {
+        sensor: Sensor,
+        timer: timer::CounterUs<TIM2>,
+    }
+
+    #[init]
+    fn init(mut ctx: init::Context) -> (Shared, Local, init::Monotonics) {
+        rtt_init_print!();
+
+        let mut dp = ctx.device;
+
+        let gpioc = dp.GPIOC.split();
+        let trigger_pin = gpioc.pc2.into_push_pull_output();
+        let echo_pin = gpioc.pc3.into_pull_down_input();
+
+        let rcc = dp.RCC.constrain();
+        let clocks = rcc
+            .cfgr
+            .use_hse(8.MHz())
+            .sysclk(168.MHz())
+            .freeze();
+
+        let delay = dp.TIM1.delay_us(&clocks);
+        let mut timer = dp.TIM2.counter_us(&clocks);
+        timer.start(100.millis()).unwrap();
+        timer.listen(Event::Update);
+
+        let sensor = UltrasonicSensor::new(trigger_pin, echo_pin, delay);
+
+        (
+            Shared {},
+            Local {
+                sensor,
+                timer,
+            },
+            init::Monotonics(),
+        )
+    }
+
+    #[task(binds = TIM2, local=[sensor, timer])]
+    fn read_sensor(mut ctx: read_sensor::Context) {
+        rprintln!("Task : Read sensor");
+
+        let timer = ctx.local.timer;
+
+        match DistanceSensor::measure_distance(ctx.local.sensor, &mut || timer.now().ticks()) {
+            Ok(distance) => rprintln!("Measured distance: {}", distance),
+            Err(SensorError::NoEchoStart) => rprintln!("No echo: is the sensor connected?"),
+            Err(SensorError::EchoTooLong) => rprintln!("Nothing in range"),
+            Err(SensorError::OutOfRange(distance)) => rprintln!("Out of range: {}", distance),
+            Err(SensorError::TooClose(distance)) => rprintln!("Too close: {}", distance),
+            Err(SensorError::PinError) => rprintln!("Sensor pin error"),
+            Err(SensorError::TooSoon) => rprintln!("Measurement cycle too short"),
+            Err(SensorError::NoResponse) => rprintln!("Sensor not answering"),
+            Err(SensorError::BadChecksum) => rprintln!("Corrupted frame"),
+            Err(SensorError::BusError) => rprintln!("Sensor bus error"),
+            Err(SensorError::WrongDevice) => rprintln!("Unexpected device on the sensor bus"),
+            Err(SensorError::InvalidReading) => rprintln!("Unreliable measurement"),
+            Err(SensorError::InvalidConfig) => rprintln!("Sensor setting not supported"),
+            Err(error) => rprintln!("Sensor error: {:?}", error),
+        }
+
+        timer.start(100.millis()).unwrap();
+    }
+}
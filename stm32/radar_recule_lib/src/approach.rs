@@ -0,0 +1,196 @@
+use crate::Distance;
+
+/// Speed at which the obstacle gets closer, and when it would be hit at that
+/// speed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Approach {
+    /// Positive when the obstacle gets closer, negative when it moves away.
+    pub closing_speed_mm_s: i32,
+    /// `None` when the obstacle is not getting closer fast enough for a
+    /// collision to be expected.
+    pub time_to_collision_ms: Option<u32>,
+    /// The time to collision is below the alarm threshold.
+    pub alarm: bool,
+}
+
+/// Derives the closing speed and the time to collision from successive
+/// distance readings.
+///
+/// Differentiating readings taken 100 ms apart turns 1 cm of noise into
+/// 10 cm/s of speed, so the speed goes through an exponential moving average
+/// and speeds below the minimum closing speed do not give a time to collision.
+pub struct ApproachTracker {
+    alarm_ttc_ms: u32,
+    alpha_pct: u8,
+    min_closing_speed_mm_s: u32,
+    last: Option<(u32, Distance)>,
+    /// Closing speed in 1/256 mm/s.
+    speed: Option<i32>,
+    approach: Option<Approach>,
+}
+
+impl ApproachTracker {
+    /// Raises the alarm when the time to collision drops below
+    /// `alarm_ttc_ms`. Speed smoothed with alpha = 50 %, speeds below 10 cm/s
+    /// ignored.
+    pub fn new(alarm_ttc_ms: u32) -> Self {
+        Self {
+            alarm_ttc_ms,
+            alpha_pct: 50,
+            min_closing_speed_mm_s: 100,
+            last: None,
+            speed: None,
+            approach: None,
+        }
+    }
+
+    /// `alpha_pct` in `1..=100`, 100 disables smoothing.
+    pub fn with_smoothing(mut self, alpha_pct: u8) -> Self {
+        assert!((1..=100).contains(&alpha_pct), "alpha must be in 1..=100 %");
+        self.alpha_pct = alpha_pct;
+        self
+    }
+
+    pub fn with_min_closing_speed(mut self, mm_s: u32) -> Self {
+        self.min_closing_speed_mm_s = mm_s;
+        self
+    }
+
+    /// Feeds a reading taken at `now_us` (wrapping µs timestamp). A failed
+    /// measurement (`None`) loses track of the obstacle. Returns `None` until
+    /// two consecutive readings are available.
+    pub fn update(&mut self, now_us: u32, distance: Option<Distance>) -> Option<Approach> {
+        let Some(distance) = distance else {
+            self.reset();
+            return None;
+        };
+
+        let (last_us, last_distance) = self.last.replace((now_us, distance))?;
+
+        let elapsed_us = now_us.wrapping_sub(last_us);
+        if elapsed_us == 0 {
+            return self.approach;
+        }
+
+        // Vitesse instantanée en 1/256 mm/s, positive si l'obstacle se rapproche
+        let closer_mm = last_distance.as_mm() as i64 - distance.as_mm() as i64;
+        // Deux mesures à quelques µs d'écart dépassent la plage : saturée
+        let reading = i32::try_from(closer_mm * 256 * 1_000_000 / elapsed_us as i64)
+            .unwrap_or(if closer_mm > 0 { i32::MAX } else { i32::MIN });
+        let speed = match self.speed {
+            // Entre l'ancienne et la nouvelle valeur : tient dans un i32
+            Some(speed) => (speed as i64 + (reading as i64 - speed as i64) * self.alpha_pct as i64 / 100) as i32,
+            None => reading,
+        };
+        self.speed = Some(speed);
+
+        let closing_speed_mm_s = speed / 256;
+        let time_to_collision_ms = match u32::try_from(closing_speed_mm_s) {
+            Ok(mm_s) if mm_s >= self.min_closing_speed_mm_s.max(1) => {
+                Some((distance.as_mm() as u64 * 1_000 / mm_s as u64) as u32)
+            }
+            _ => None,
+        };
+
+        self.approach = Some(Approach {
+            closing_speed_mm_s,
+            time_to_collision_ms,
+            alarm: time_to_collision_ms.is_some_and(|ttc| ttc < self.alarm_ttc_ms),
+        });
+        self.approach
+    }
+
+    /// Last estimate, `None` while no obstacle is being tracked.
+    pub fn approach(&self) -> Option<Approach> {
+        self.approach
+    }
+
+    pub fn is_alarm(&self) -> bool {
+        self.approach.is_some_and(|approach| approach.alarm)
+    }
+
+    pub fn reset(&mut self) {
+        self.last = None;
+        self.speed = None;
+        self.approach = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PERIOD_US: u32 = 100_000;
+
+    /// Feeds one reading every 100 ms.
+    fn track(tracker: &mut ApproachTracker, start_us: u32, mm: &[u32]) -> Vec<Option<Approach>> {
+        mm.iter()
+            .enumerate()
+            .map(|(i, &mm)| tracker.update(start_us.wrapping_add(i as u32 * PERIOD_US), Some(Distance::from_mm(mm))))
+            .collect()
+    }
+
+    #[test]
+    fn estimates_closing_speed_and_time_to_collision() {
+        let mut tracker = ApproachTracker::new(1_000).with_smoothing(100);
+        // Recul à 50 cm/s
+        let approach = track(&mut tracker, 0, &[2_000, 1_950, 1_900]);
+
+        assert_eq!(approach[0], None);
+        assert_eq!(
+            approach[2],
+            Some(Approach { closing_speed_mm_s: 500, time_to_collision_ms: Some(3_800), alarm: false })
+        );
+    }
+
+    #[test]
+    fn raises_the_alarm_when_reversing_fast() {
+        let mut tracker = ApproachTracker::new(1_500);
+        // Recul rapide à 1 m/s depuis 3 m
+        let readings: Vec<_> = (0..25).map(|i| 3_000 - i * 100).collect();
+        let approach = track(&mut tracker, 0, &readings);
+
+        let first_alarm = approach.iter().position(|a| a.is_some_and(|a| a.alarm)).unwrap();
+        assert_eq!(readings[first_alarm], 1_400);
+        assert!(tracker.is_alarm());
+        assert_eq!(tracker.approach().unwrap().closing_speed_mm_s, 1_000);
+    }
+
+    #[test]
+    fn ignores_noise_and_receding_obstacles() {
+        let mut tracker = ApproachTracker::new(1_500);
+
+        // Obstacle immobile, mesure bruitée de ±1 cm
+        let still = track(&mut tracker, 0, &[800, 810, 800, 790, 800, 810, 800]);
+        assert!(still[1..].iter().all(|a| a.unwrap().time_to_collision_ms.is_none()), "{still:?}");
+
+        tracker.reset();
+        let receding = track(&mut tracker, 0, &[500, 600, 700]);
+        assert_eq!(receding[2].unwrap().time_to_collision_ms, None);
+        assert!(receding[2].unwrap().closing_speed_mm_s < 0);
+    }
+
+    #[test]
+    fn handles_clock_wrap_and_lost_echo() {
+        let mut tracker = ApproachTracker::new(1_000).with_smoothing(100);
+        let approach = track(&mut tracker, u32::MAX - 50_000, &[1_000, 900]);
+        assert_eq!(approach[1].unwrap().closing_speed_mm_s, 1_000);
+
+        assert_eq!(tracker.update(200_000, None), None);
+        assert_eq!(tracker.approach(), None);
+        assert_eq!(tracker.update(300_000, Some(Distance::from_mm(800))), None);
+    }
+
+    #[test]
+    fn saturates_on_readings_a_few_us_apart() {
+        let mut tracker = ApproachTracker::new(1_000).with_smoothing(100);
+        tracker.update(0, Some(Distance::from_mm(3_000)));
+
+        let approach = tracker.update(1, Some(Distance::from_mm(100))).unwrap();
+        assert_eq!(approach.closing_speed_mm_s, i32::MAX / 256);
+        assert!(approach.alarm);
+
+        tracker.update(2, Some(Distance::from_mm(3_000)));
+        assert_eq!(tracker.approach().unwrap().closing_speed_mm_s, i32::MIN / 256);
+    }
+}
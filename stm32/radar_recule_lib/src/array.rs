@@ -0,0 +1,206 @@
+use crate::{Clock, Distance, DistanceSensor, SensorError};
+
+/// Several sensors covering the same area, e.g. the four to six sensors of a
+/// bumper.
+///
+/// Sensors are fired one at a time, round-robin, and a guard time is kept
+/// between the end of a measurement and the next trigger so that late
+/// echoes of one sensor are not picked up by its neighbour.
+pub struct SensorArray<S, const N: usize> {
+    sensors: [S; N],
+    latest: [Option<Result<Distance, SensorError>>; N],
+    next: usize,
+    guard_time_us: u32,
+    last_end_us: Option<u32>,
+}
+
+impl<S, const N: usize> SensorArray<S, N> {
+    pub fn new(sensors: [S; N], guard_time_us: u32) -> Self {
+        const { assert!(N > 0, "a sensor array needs at least one sensor") };
+        Self {
+            sensors,
+            latest: [None; N],
+            next: 0,
+            guard_time_us,
+            last_end_us: None,
+        }
+    }
+
+    pub fn sensor_mut(&mut self, channel: usize) -> &mut S {
+        &mut self.sensors[channel]
+    }
+
+    /// All the sensors, e.g. to update their ambient conditions.
+    pub fn sensors_mut(&mut self) -> &mut [S; N] {
+        &mut self.sensors
+    }
+
+    /// Channel fired by the next measurement.
+    pub fn next_channel(&self) -> usize {
+        self.next
+    }
+
+    /// Result of the last measurement of `channel`, `None` before its first
+    /// measurement.
+    pub fn latest(&self, channel: usize) -> Option<Result<Distance, SensorError>> {
+        self.latest[channel]
+    }
+
+    /// Last distance measured by `channel`, `None` if its last measurement
+    /// failed.
+    pub fn distance(&self, channel: usize) -> Option<Distance> {
+        self.latest[channel].and_then(Result::ok)
+    }
+
+    /// Channel currently seeing the closest obstacle, with its distance. An
+    /// obstacle in the blind zone of a sensor is as close as it gets, at
+    /// [`Distance::ZERO`].
+    pub fn nearest(&self) -> Option<(usize, Distance)> {
+        (0..N)
+            .filter_map(|channel| match self.latest[channel]? {
+                Ok(distance) => Some((channel, distance)),
+                Err(SensorError::TooClose(_)) => Some((channel, Distance::ZERO)),
+                Err(_) => None,
+            })
+            .min_by_key(|&(_, distance)| distance)
+    }
+
+    pub fn release(self) -> [S; N] {
+        self.sensors
+    }
+
+    fn guard_elapsed(&self, now_us: u32) -> bool {
+        match self.last_end_us {
+            Some(end) => now_us.wrapping_sub(end) >= self.guard_time_us,
+            None => true,
+        }
+    }
+
+    fn record<C: Clock>(
+        &mut self,
+        result: Result<Distance, SensorError>,
+        clock: &mut C,
+    ) -> (usize, Result<Distance, SensorError>) {
+        let channel = self.next;
+        self.latest[channel] = Some(result);
+        self.next = (channel + 1) % N;
+        self.last_end_us = Some(clock.now_us());
+        (channel, result)
+    }
+}
+
+impl<S: DistanceSensor, const N: usize> SensorArray<S, N> {
+    /// Measures with the next sensor if the guard time has elapsed, returns
+    /// `None` without blocking otherwise. Meant to be called periodically.
+    pub fn poll<C: Clock>(&mut self, clock: &mut C) -> Option<(usize, Result<Distance, SensorError>)> {
+        if !self.guard_elapsed(clock.now_us()) {
+            return None;
+        }
+
+        let result = self.sensors[self.next].measure_distance(clock);
+        Some(self.record(result, clock))
+    }
+
+    /// Waits for the guard time to elapse, then measures with the next
+    /// sensor.
+    pub fn measure_next<C: Clock>(&mut self, clock: &mut C) -> (usize, Result<Distance, SensorError>) {
+        while !self.guard_elapsed(clock.now_us()) {}
+
+        let result = self.sensors[self.next].measure_distance(clock);
+        self.record(result, clock)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{Bench, EchoScript, MockDelay, MockEcho, MockTrigger};
+    use crate::UltrasonicSensor;
+
+    const GUARD_US: u32 = 10_000;
+
+    fn pulse(distance_mm: u32) -> EchoScript {
+        // 344,04 m/s par défaut, aller-retour
+        EchoScript::Pulse { delay_us: 450, width_us: distance_mm * 2_000_000 / 344_040 }
+    }
+
+    /// One bench per sensor, kept in step by a shared clock.
+    fn array(
+        scripts: [EchoScript; 3],
+    ) -> ([Bench; 3], SensorArray<UltrasonicSensor<MockTrigger, MockEcho, MockDelay>, 3>) {
+        let benches = scripts.map(Bench::new);
+        let sensors = benches
+            .each_ref()
+            .map(|bench| UltrasonicSensor::new(bench.trigger(), bench.echo(), bench.delay()));
+        (benches, SensorArray::new(sensors, GUARD_US))
+    }
+
+    fn clock(benches: &[Bench; 3]) -> impl FnMut() -> u32 + '_ {
+        || {
+            benches.iter().for_each(|bench| bench.advance(1));
+            benches[0].now_us()
+        }
+    }
+
+    #[test]
+    fn fires_sensors_round_robin() {
+        let (benches, mut array) = array([pulse(1_200), EchoScript::Silent, pulse(450)]);
+        let mut clock = clock(&benches);
+
+        let channels: Vec<_> = (0..4).map(|_| array.measure_next(&mut clock).0).collect();
+
+        assert_eq!(channels, [0, 1, 2, 0]);
+        assert_eq!(benches.each_ref().map(|bench| bench.trigger_pulses().len()), [2, 1, 1]);
+        assert_eq!(array.latest(1), Some(Err(SensorError::NoEchoStart)));
+        assert_eq!(array.distance(1), None);
+        assert!(array.distance(0).unwrap().abs_diff(Distance::from_mm(1_200)) <= Distance::from_mm(1));
+    }
+
+    #[test]
+    fn waits_the_guard_time_between_sensors() {
+        let (benches, mut array) = array([pulse(1_200), pulse(800), pulse(450)]);
+        let mut clock = clock(&benches);
+
+        assert_eq!(array.poll(&mut clock).map(|(channel, _)| channel), Some(0));
+        assert_eq!(array.poll(&mut clock), None);
+        assert!(benches[1].trigger_pulses().is_empty());
+
+        benches.iter().for_each(|bench| bench.advance(GUARD_US));
+        assert_eq!(array.poll(&mut clock).map(|(channel, _)| channel), Some(1));
+
+        // `measure_next` attend la fin du temps de garde au lieu d'abandonner
+        let end = benches[0].now_us();
+        assert_eq!(array.measure_next(&mut clock).0, 2);
+        assert!(benches[0].now_us() - end >= GUARD_US);
+    }
+
+    #[test]
+    fn reports_the_nearest_obstacle() {
+        let (benches, mut array) = array([pulse(1_200), EchoScript::Silent, pulse(450)]);
+        let mut clock = clock(&benches);
+
+        assert_eq!(array.nearest(), None);
+
+        for _ in 0..3 {
+            let _ = array.measure_next(&mut clock);
+        }
+
+        let (channel, distance) = array.nearest().unwrap();
+        assert_eq!(channel, 2);
+        assert!(distance.abs_diff(Distance::from_mm(450)) <= Distance::from_mm(1));
+    }
+
+    #[test]
+    fn counts_the_blind_zone_as_nearest() {
+        // 1 cm : dans la zone aveugle de 2 cm du capteur du milieu
+        let (benches, mut array) = array([pulse(1_200), pulse(10), pulse(450)]);
+        let mut clock = clock(&benches);
+        for _ in 0..3 {
+            let _ = array.measure_next(&mut clock);
+        }
+
+        assert!(matches!(array.latest(1), Some(Err(SensorError::TooClose(_)))));
+        assert_eq!(array.distance(1), None);
+        assert_eq!(array.nearest(), Some((1, Distance::ZERO)));
+    }
+}
@@ -0,0 +1,81 @@
+use embassy_futures::select::{select, Either};
+use embedded_hal_async::{delay::DelayNs, digital::Wait};
+
+use crate::{Clock, Distance, EchoPins, EchoSensor, SensorError, ECHO_START_TIMEOUT_US};
+
+impl<P, D> EchoSensor<P, D>
+where
+    P: EchoPins,
+    P::Echo: Wait,
+    D: DelayNs,
+{
+    /// Same measurement as [`EchoSensor::measure_distance`], but the
+    /// echo edges are awaited instead of polled, leaving the executor free
+    /// to run other tasks during the flight time. `clock` only timestamps
+    /// the two edges, the timeouts are awaited on the delay.
+    pub async fn measure<C: Clock>(&mut self, clock: &mut C) -> Result<Distance, SensorError> {
+        let remaining = self.cycle_remaining_us(clock.now_us());
+        if remaining > 0 {
+            self.delay.delay_us(remaining).await;
+        }
+
+        // Envoyer une impulsion sur le trigger pour démarrer la mesure
+        self.pins.set_trigger(true)?;
+        self.delay.delay_us(self.profile.trigger_pulse_us).await;
+        self.pins.set_trigger(false)?;
+        self.pins.listen()?;
+        self.last_trigger_us = Some(clock.now_us());
+
+        // Attendre que l'écho passe à HIGH, sans dépasser le timeout
+        match select(self.pins.echo().wait_for_high(), self.delay.delay_us(ECHO_START_TIMEOUT_US)).await {
+            Either::First(result) => result.map_err(|_| SensorError::PinError)?,
+            Either::Second(()) => return Err(SensorError::NoEchoStart),
+        }
+
+        let start_time = clock.now_us();
+
+        // Attendre que l'écho passe à LOW, sans dépasser la portée maximale
+        let max_echo_us = self.max_echo_us();
+        match select(self.pins.echo().wait_for_low(), self.delay.delay_us(max_echo_us)).await {
+            Either::First(result) => result.map_err(|_| SensorError::PinError)?,
+            Either::Second(()) => return Err(SensorError::EchoTooLong),
+        }
+
+        let echo_time = clock.now_us().wrapping_sub(start_time);
+
+        self.distance_from_echo(echo_time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mock::{Bench, EchoScript};
+    use crate::{Distance, SensorError, UltrasonicSensor, ECHO_START_TIMEOUT_US};
+
+    #[test]
+    fn awaits_the_echo() {
+        let bench = Bench::new(EchoScript::Pulse { delay_us: 450, width_us: 1_000 });
+        let mut sensor = UltrasonicSensor::new(bench.trigger(), bench.echo(), bench.delay());
+
+        let distance = bench.block_on(sensor.measure(&mut bench.clock())).unwrap();
+
+        assert!(distance.abs_diff(Distance::from_mm(172)) <= Distance::from_mm(1), "{distance}");
+        assert_eq!(bench.trigger_pulses(), [10]);
+    }
+
+    #[test]
+    fn times_out_while_awaiting() {
+        let bench = Bench::new(EchoScript::Silent);
+        let mut sensor = UltrasonicSensor::new(bench.trigger(), bench.echo(), bench.delay());
+
+        let result = bench.block_on(sensor.measure(&mut bench.clock()));
+
+        assert_eq!(result, Err(SensorError::NoEchoStart));
+        assert!(bench.now_us() <= ECHO_START_TIMEOUT_US + 20);
+
+        bench.script(EchoScript::Pulse { delay_us: 450, width_us: 38_000 });
+        let result = bench.block_on(sensor.measure(&mut bench.clock()));
+
+        assert_eq!(result, Err(SensorError::EchoTooLong));
+    }
+}
@@ -0,0 +1,409 @@
+use core::{fmt, str};
+
+use crate::{Distance, Environment, ZoneThresholds};
+
+const MIN_PERIOD_MS: u32 = 10;
+const MAX_PERIOD_MS: u32 = 60_000;
+const MAX_ZONE_CM: u32 = 1_000;
+
+/// A configuration command typed on the radar console.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    /// `period <ms>`: time between two measurements, 10 ms to 60 s.
+    Period { ms: u32 },
+    /// `zones <far> <near> <critical>`: zone thresholds in cm, decreasing,
+    /// up to 10 m.
+    Zones(ZoneThresholds),
+    /// `temp <°C>`: air temperature, e.g. `21.5` or `-3`, stored in tenths
+    /// of °C, -40 °C to +85 °C.
+    Temperature { dc: i16 },
+    /// `status`: prints the current settings.
+    Status,
+    /// `reset`: restarts the radar.
+    Reset,
+    /// `help`: lists the commands.
+    Help,
+}
+
+/// Why a console line is not a valid command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandError {
+    /// Nothing but blanks on the line.
+    Empty,
+    UnknownCommand,
+    MissingArgument,
+    TooManyArguments,
+    /// An argument is not a number, or has more than one decimal.
+    InvalidNumber,
+    /// A number outside the range of its setting.
+    OutOfRange,
+    /// The zone thresholds do not decrease from far to critical.
+    UnorderedZones,
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Empty => "empty line",
+            Self::UnknownCommand => "unknown command, try `help`",
+            Self::MissingArgument => "missing argument",
+            Self::TooManyArguments => "too many arguments",
+            Self::InvalidNumber => "invalid number",
+            Self::OutOfRange => "value out of range",
+            Self::UnorderedZones => "thresholds must decrease from far to critical",
+        })
+    }
+}
+
+/// Parses one console line. Command names are case-insensitive, words are
+/// separated by any number of blanks.
+pub fn parse(line: &str) -> Result<Command, CommandError> {
+    let mut words = line.split_ascii_whitespace();
+    let name = words.next().ok_or(CommandError::Empty)?;
+    let is = |command: &str| name.eq_ignore_ascii_case(command);
+
+    let command = if is("period") {
+        let ms = number(words.next())?;
+        if !(MIN_PERIOD_MS..=MAX_PERIOD_MS).contains(&ms) {
+            return Err(CommandError::OutOfRange);
+        }
+        Command::Period { ms }
+    } else if is("zones") {
+        let mut thresholds = [0; 3];
+        for threshold in &mut thresholds {
+            *threshold = number(words.next())?;
+            if *threshold == 0 || *threshold > MAX_ZONE_CM {
+                return Err(CommandError::OutOfRange);
+            }
+        }
+        let [far, near, critical] = thresholds;
+        if !(far > near && near > critical) {
+            return Err(CommandError::UnorderedZones);
+        }
+        Command::Zones(ZoneThresholds::new(
+            Distance::from_cm(far),
+            Distance::from_cm(near),
+            Distance::from_cm(critical),
+        ))
+    } else if is("temp") {
+        let dc = tenths(words.next().ok_or(CommandError::MissingArgument)?)?;
+        let range = Environment::MIN_TEMPERATURE_DC as i32..=Environment::MAX_TEMPERATURE_DC as i32;
+        if !range.contains(&dc) {
+            return Err(CommandError::OutOfRange);
+        }
+        Command::Temperature { dc: dc as i16 }
+    } else if is("status") {
+        Command::Status
+    } else if is("reset") {
+        Command::Reset
+    } else if is("help") {
+        Command::Help
+    } else {
+        return Err(CommandError::UnknownCommand);
+    };
+
+    if words.next().is_some() {
+        return Err(CommandError::TooManyArguments);
+    }
+    Ok(command)
+}
+
+/// A whole number of plain digits: no sign, no decimal.
+fn number(word: Option<&str>) -> Result<u32, CommandError> {
+    let word = word.ok_or(CommandError::MissingArgument)?;
+    if !is_digits(word) {
+        return Err(CommandError::InvalidNumber);
+    }
+    // Que des chiffres : l'analyse n'échoue que sur un débordement
+    word.parse().map_err(|_| CommandError::OutOfRange)
+}
+
+/// A signed decimal with at most one decimal, in tenths.
+fn tenths(word: &str) -> Result<i32, CommandError> {
+    let (negative, unsigned) = match word.strip_prefix('-') {
+        Some(unsigned) => (true, unsigned),
+        None => (false, word.strip_prefix('+').unwrap_or(word)),
+    };
+    let (whole, decimal) = match unsigned.split_once('.') {
+        Some((whole, decimal)) if decimal.len() == 1 => (whole, decimal),
+        Some(_) => return Err(CommandError::InvalidNumber),
+        None => (unsigned, "0"),
+    };
+    if !is_digits(whole) || !is_digits(decimal) {
+        return Err(CommandError::InvalidNumber);
+    }
+
+    let whole: i32 = whole.parse().map_err(|_| CommandError::OutOfRange)?;
+    let tenths = whole
+        .checked_mul(10)
+        .and_then(|tenths| tenths.checked_add((decimal.as_bytes()[0] - b'0') as i32))
+        .ok_or(CommandError::OutOfRange)?;
+    Ok(if negative { -tenths } else { tenths })
+}
+
+fn is_digits(word: &str) -> bool {
+    !word.is_empty() && word.bytes().all(|byte| byte.is_ascii_digit())
+}
+
+/// What a byte received on the console did to the line being typed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEvent<'a> {
+    /// The character was added to the line and should be echoed.
+    Echo(u8),
+    /// The last character was erased, the terminal should erase it too.
+    Erase,
+    /// Enter was pressed: the line typed, terminator excluded.
+    Line(&'a str),
+    /// Enter was pressed on a line longer than the buffer: it was dropped.
+    TooLong,
+}
+
+/// Assembles the bytes received on a serial console into lines of up to
+/// `N` characters, with backspace.
+///
+/// A line ends at CR, LF or CR LF, whatever the terminal sends. Only
+/// printable ASCII is kept, so a line is always valid UTF-8.
+pub struct LineEditor<const N: usize> {
+    buffer: [u8; N],
+    len: usize,
+    overflow: bool,
+    after_cr: bool,
+}
+
+impl<const N: usize> LineEditor<N> {
+    pub const fn new() -> Self {
+        Self {
+            buffer: [0; N],
+            len: 0,
+            overflow: false,
+            after_cr: false,
+        }
+    }
+
+    /// Feeds one byte received. `None` when it changes nothing visible.
+    pub fn push(&mut self, byte: u8) -> Option<LineEvent<'_>> {
+        let after_cr = core::mem::replace(&mut self.after_cr, byte == b'\r');
+        match byte {
+            // Le LF d'un CR LF : la ligne est déjà terminée
+            b'\n' if after_cr => None,
+            b'\r' | b'\n' => {
+                let (len, overflow) = (self.len, self.overflow);
+                self.len = 0;
+                self.overflow = false;
+                if overflow {
+                    return Some(LineEvent::TooLong);
+                }
+                Some(LineEvent::Line(str::from_utf8(&self.buffer[..len]).unwrap_or_default()))
+            }
+            // Backspace ou DEL selon le terminal
+            0x08 | 0x7F if self.len > 0 && !self.overflow => {
+                self.len -= 1;
+                Some(LineEvent::Erase)
+            }
+            0x20..=0x7E => match self.buffer.get_mut(self.len) {
+                Some(slot) if !self.overflow => {
+                    *slot = byte;
+                    self.len += 1;
+                    Some(LineEvent::Echo(byte))
+                }
+                _ => {
+                    self.overflow = true;
+                    None
+                }
+            },
+            _ => None,
+        }
+    }
+
+    /// Drops the line being typed.
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.overflow = false;
+    }
+}
+
+impl<const N: usize> Default for LineEditor<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Ring buffer of up to `N` bytes waiting to go out on a serial console, so
+/// that an interrupt handler can queue a whole reply and send it one byte at
+/// each transmit interrupt instead of waiting for the line.
+///
+/// Writing more than fits drops the end of the text and returns an error.
+pub struct OutputQueue<const N: usize> {
+    buffer: [u8; N],
+    head: usize,
+    len: usize,
+}
+
+impl<const N: usize> OutputQueue<N> {
+    pub const fn new() -> Self {
+        Self {
+            buffer: [0; N],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Queues one byte, `false` if the queue is full.
+    pub fn push(&mut self, byte: u8) -> bool {
+        if self.len == N {
+            return false;
+        }
+        self.buffer[(self.head + self.len) % N] = byte;
+        self.len += 1;
+        true
+    }
+
+    /// Oldest byte queued.
+    pub fn peek(&self) -> Option<u8> {
+        (self.len > 0).then(|| self.buffer[self.head])
+    }
+
+    /// Takes the oldest byte queued.
+    pub fn pop(&mut self) -> Option<u8> {
+        let byte = self.peek()?;
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+        Some(byte)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Drops everything queued.
+    pub fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+    }
+}
+
+impl<const N: usize> Default for OutputQueue<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> fmt::Write for OutputQueue<N> {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        for &byte in text.as_bytes() {
+            if !self.push(byte) {
+                return Err(fmt::Error);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_commands() {
+        assert_eq!(parse("period 250"), Ok(Command::Period { ms: 250 }));
+        assert_eq!(
+            parse("  ZONES 200\t120  40 "),
+            Ok(Command::Zones(ZoneThresholds::new(
+                Distance::from_cm(200),
+                Distance::from_cm(120),
+                Distance::from_cm(40),
+            )))
+        );
+        assert_eq!(parse("temp 21.5"), Ok(Command::Temperature { dc: 215 }));
+        assert_eq!(parse("temp -0.5"), Ok(Command::Temperature { dc: -5 }));
+        assert_eq!(parse("temp +4"), Ok(Command::Temperature { dc: 40 }));
+        assert_eq!(parse("Status"), Ok(Command::Status));
+        assert_eq!(parse("reset"), Ok(Command::Reset));
+        assert_eq!(parse("help"), Ok(Command::Help));
+    }
+
+    #[test]
+    fn rejects_invalid_commands() {
+        assert_eq!(parse(" \t"), Err(CommandError::Empty));
+        assert_eq!(parse("distance"), Err(CommandError::UnknownCommand));
+        assert_eq!(parse("period"), Err(CommandError::MissingArgument));
+        assert_eq!(parse("period 100 ms"), Err(CommandError::TooManyArguments));
+        assert_eq!(parse("period -100"), Err(CommandError::InvalidNumber));
+        assert_eq!(parse("period 5"), Err(CommandError::OutOfRange));
+        assert_eq!(parse("period 99999999999"), Err(CommandError::OutOfRange));
+        assert_eq!(parse("zones 150 80"), Err(CommandError::MissingArgument));
+        assert_eq!(parse("zones 80 150 30"), Err(CommandError::UnorderedZones));
+        assert_eq!(parse("zones 150 80 0"), Err(CommandError::OutOfRange));
+        assert_eq!(parse("temp 21.25"), Err(CommandError::InvalidNumber));
+        assert_eq!(parse("temp 2a"), Err(CommandError::InvalidNumber));
+        assert_eq!(parse("temp -."), Err(CommandError::InvalidNumber));
+        assert_eq!(parse("temp 90"), Err(CommandError::OutOfRange));
+        assert_eq!(parse("status now"), Err(CommandError::TooManyArguments));
+    }
+
+    fn type_in<const N: usize>(editor: &mut LineEditor<N>, bytes: &[u8]) -> Vec<String> {
+        let mut events = Vec::new();
+        for &byte in bytes {
+            events.extend(editor.push(byte).map(|event| match event {
+                LineEvent::Echo(byte) => (byte as char).to_string(),
+                LineEvent::Erase => "<erase>".into(),
+                LineEvent::Line(line) => format!("<line {line}>"),
+                LineEvent::TooLong => "<too long>".into(),
+            }));
+        }
+        events
+    }
+
+    #[test]
+    fn edits_lines() {
+        let mut editor = LineEditor::<8>::new();
+
+        // Saisie corrigée au retour arrière, terminée par CR LF
+        assert_eq!(
+            type_in(&mut editor, b"temq\x7Fp 5\r\n"),
+            ["t", "e", "m", "q", "<erase>", "p", " ", "5", "<line temp 5>"]
+        );
+        // LF seul, ligne vide, caractères de contrôle ignorés
+        assert_eq!(type_in(&mut editor, b"\x08\x1B\n\r"), ["<line >", "<line >"]);
+
+        // Ligne trop longue : l'écho s'arrête et la ligne est jetée
+        let events = type_in(&mut editor, b"period 1000\r");
+        assert_eq!(events.len(), 9);
+        assert_eq!(events[8], "<too long>");
+        assert_eq!(type_in(&mut editor, b"reset\r"), ["r", "e", "s", "e", "t", "<line reset>"]);
+
+        type_in(&mut editor, b"stat");
+        editor.clear();
+        assert_eq!(type_in(&mut editor, b"\r"), ["<line >"]);
+    }
+
+    #[test]
+    fn queues_the_output() {
+        use core::fmt::Write;
+
+        let mut queue = OutputQueue::<8>::new();
+        assert_eq!(queue.pop(), None);
+        write!(queue, "> {}", 42).unwrap();
+        assert_eq!(queue.len(), 4);
+        assert_eq!(queue.peek(), Some(b'>'));
+        assert_eq!(queue.pop(), Some(b'>'));
+        assert_eq!(queue.pop(), Some(b' '));
+
+        // Le tampon reboucle ; ce qui dépasse est perdu
+        assert!(write!(queue, "abcdefg").is_err());
+        let mut sent = Vec::new();
+        while let Some(byte) = queue.pop() {
+            sent.push(byte);
+        }
+        assert_eq!(sent, b"42abcdef");
+        assert!(queue.is_empty());
+
+        queue.push(b'x');
+        queue.clear();
+        assert_eq!(queue.pop(), None);
+    }
+}
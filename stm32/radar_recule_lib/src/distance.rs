@@ -0,0 +1,73 @@
+use core::fmt;
+
+/// A distance, stored as an integer number of millimetres so that no
+/// floating point is needed on FPU-less cores.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Distance(u32);
+
+impl Distance {
+    pub const ZERO: Self = Self(0);
+
+    pub const fn from_mm(mm: u32) -> Self {
+        Self(mm)
+    }
+
+    pub const fn from_cm(cm: u32) -> Self {
+        Self(cm * 10)
+    }
+
+    /// Distance travelled by sound during half of `echo_us` (the echo is a
+    /// round trip) at `speed_mm_s`, rounded to the nearest millimetre.
+    pub const fn from_echo(echo_us: u32, speed_mm_s: u32) -> Self {
+        let mm = (echo_us as u64 * speed_mm_s as u64 + 1_000_000) / 2_000_000;
+        Self(mm as u32)
+    }
+
+    pub const fn as_mm(self) -> u32 {
+        self.0
+    }
+
+    /// Whole centimetres, rounded down.
+    pub const fn as_cm(self) -> u32 {
+        self.0 / 10
+    }
+
+    pub fn as_m_f32(self) -> f32 {
+        self.0 as f32 / 1_000.0
+    }
+
+    pub const fn abs_diff(self, other: Self) -> Self {
+        Self(self.0.abs_diff(other.0))
+    }
+}
+
+impl fmt::Display for Distance {
+    /// Centimetres with one decimal, e.g. `123.4cm`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}cm", self.0 / 10, self.0 % 10)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_units() {
+        let distance = Distance::from_mm(1_234);
+
+        assert_eq!(distance.as_mm(), 1_234);
+        assert_eq!(distance.as_cm(), 123);
+        assert_eq!(distance.as_m_f32(), 1.234);
+        assert_eq!(Distance::from_cm(42), Distance::from_mm(420));
+        assert_eq!(distance.to_string(), "123.4cm");
+    }
+
+    #[test]
+    fn converts_echo_time() {
+        assert_eq!(Distance::from_echo(1_000, 340_000), Distance::from_mm(170));
+        assert_eq!(Distance::from_echo(11_627, 344_040), Distance::from_mm(2_000));
+        // Pas de débordement sur une durée d'écho maximale
+        assert_eq!(Distance::from_echo(u32::MAX, 400_000).as_mm(), 858_993_459);
+    }
+}
@@ -0,0 +1,131 @@
+/// Ambient conditions the speed of sound depends on, in integer units so
+/// that the per-reading computation needs no floating point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Environment {
+    /// Air temperature in tenths of °C.
+    pub temperature_dc: i16,
+    /// Relative humidity in %.
+    pub humidity_pct: u8,
+}
+
+impl Environment {
+    /// Coldest temperature the speed of sound is computed for, -40 °C.
+    pub const MIN_TEMPERATURE_DC: i16 = -400;
+    /// Hottest temperature the speed of sound is computed for, +85 °C.
+    pub const MAX_TEMPERATURE_DC: i16 = 850;
+
+    /// Converts readings in °C and % (rounded to 0.1 °C and 1 %).
+    pub fn from_celsius(celsius: f32, humidity_pct: f32) -> Self {
+        let mut environment = Self::default();
+        environment.set_temperature_c(celsius);
+        environment.set_humidity_pct(humidity_pct);
+        environment
+    }
+
+    pub fn set_temperature_c(&mut self, celsius: f32) {
+        self.temperature_dc = round(celsius * 10.0) as i16;
+    }
+
+    pub fn set_humidity_pct(&mut self, percent: f32) {
+        self.humidity_pct = round(percent.clamp(0.0, 100.0)) as u8;
+    }
+
+    pub fn temperature_c(&self) -> f32 {
+        self.temperature_dc as f32 / 10.0
+    }
+
+    /// Speed of sound in air in mm/s.
+    ///
+    /// Linear approximation of `331.3 * sqrt(1 + T / 273.15)` m/s, which
+    /// stays within 0.2 % between -20 °C and +50 °C, plus the small increase
+    /// due to water vapour (12.4 mm/s per % of relative humidity).
+    ///
+    /// The temperature is clamped to [`Self::MIN_TEMPERATURE_DC`] and
+    /// [`Self::MAX_TEMPERATURE_DC`], the humidity to 100 %.
+    pub const fn speed_of_sound_mm_s(&self) -> u32 {
+        let temperature_dc = if self.temperature_dc < Self::MIN_TEMPERATURE_DC {
+            Self::MIN_TEMPERATURE_DC
+        } else if self.temperature_dc > Self::MAX_TEMPERATURE_DC {
+            Self::MAX_TEMPERATURE_DC
+        } else {
+            self.temperature_dc
+        };
+        let humidity_pct = if self.humidity_pct > 100 { 100 } else { self.humidity_pct };
+
+        let tenths = 3_313_000 + 606 * temperature_dc as i32 + 124 * humidity_pct as i32;
+        (tenths / 10) as u32
+    }
+
+    /// Speed of sound in air in m/s.
+    pub fn speed_of_sound(&self) -> f32 {
+        self.speed_of_sound_mm_s() as f32 / 1_000.0
+    }
+}
+
+impl Default for Environment {
+    /// 20 °C and 50 % relative humidity.
+    fn default() -> Self {
+        Self {
+            temperature_dc: 200,
+            humidity_pct: 50,
+        }
+    }
+}
+
+/// Rounds half away from zero, `f32::round` is not available in `core`.
+fn round(value: f32) -> f32 {
+    if value < 0.0 {
+        (value - 0.5) as i32 as f32
+    } else {
+        (value + 0.5) as i32 as f32
+    }
+}
+
+/// Something able to report the ambient conditions, such as a temperature
+/// and humidity sensor mounted next to the ultrasonic one.
+pub trait EnvironmentSource {
+    /// Air temperature in °C, `None` if it could not be read.
+    fn temperature_c(&mut self) -> Option<f32>;
+
+    /// Relative humidity in %, `None` if unknown.
+    fn humidity_pct(&mut self) -> Option<f32> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn speed_of_sound_follows_temperature() {
+        let dry = |temperature_dc| Environment { temperature_dc, humidity_pct: 0 };
+
+        assert_eq!(dry(0).speed_of_sound_mm_s(), 331_300);
+        assert_eq!(dry(200).speed_of_sound_mm_s(), 343_420);
+        assert_eq!(dry(-200).speed_of_sound_mm_s(), 319_180);
+        assert_eq!(dry(350).speed_of_sound_mm_s(), 352_510);
+        assert!((dry(200).speed_of_sound() - 343.4).abs() < 0.1);
+
+        // Hors de la plage de la formule : bornée à -40 °C et +85 °C
+        assert_eq!(dry(i16::MIN).speed_of_sound_mm_s(), dry(-400).speed_of_sound_mm_s());
+        assert_eq!(dry(i16::MAX).speed_of_sound_mm_s(), dry(850).speed_of_sound_mm_s());
+        let saturated = Environment { temperature_dc: 200, humidity_pct: u8::MAX };
+        assert_eq!(saturated.speed_of_sound_mm_s(), 344_660);
+    }
+
+    #[test]
+    fn humidity_speeds_sound_up() {
+        let dry = Environment { temperature_dc: 200, humidity_pct: 0 };
+        let wet = Environment { temperature_dc: 200, humidity_pct: 100 };
+
+        assert_eq!(wet.speed_of_sound_mm_s() - dry.speed_of_sound_mm_s(), 1_240);
+    }
+
+    #[test]
+    fn converts_from_celsius() {
+        assert_eq!(Environment::from_celsius(-5.46, 42.6), Environment { temperature_dc: -55, humidity_pct: 43 });
+        assert_eq!(Environment::from_celsius(21.04, 120.0), Environment { temperature_dc: 210, humidity_pct: 100 });
+        assert_eq!(Environment::from_celsius(21.04, 120.0).temperature_c(), 21.0);
+    }
+}
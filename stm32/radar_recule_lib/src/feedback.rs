@@ -0,0 +1,221 @@
+use crate::{Distance, SensorError, Zone, ZoneThresholds};
+
+/// Beep length and period of the fault pattern.
+const FAULT_ON_MS: u32 = 500;
+const FAULT_PERIOD_MS: u32 = 2_000;
+
+/// What the buzzer should be doing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BeepPattern {
+    Silent,
+    /// Beeps of `on_ms` repeated every `period_ms`.
+    Intermittent { on_ms: u32, period_ms: u32 },
+    Continuous,
+    /// The sensor is not working: a long beep every two seconds, so that a
+    /// dead sensor is not mistaken for a clear path.
+    Fault,
+}
+
+/// Parking-sensor style beeper: silent when clear, beeping faster and faster
+/// as the obstacle gets closer, continuous tone in the critical zone, a slow
+/// beep of its own when the sensor fails.
+///
+/// `tick` is called at a fixed rate and tells whether the buzzer should
+/// sound, so the beeper knows nothing about the PWM driving it.
+pub struct Beeper {
+    thresholds: ZoneThresholds,
+    on_ms: u32,
+    slowest_period_ms: u32,
+    fastest_period_ms: u32,
+    zone: Zone,
+    pattern: BeepPattern,
+    phase_ms: u32,
+}
+
+impl Beeper {
+    /// 60 ms beeps, every second at the far threshold down to every 150 ms at
+    /// the critical one.
+    pub fn new(thresholds: ZoneThresholds) -> Self {
+        Self {
+            thresholds,
+            on_ms: 60,
+            slowest_period_ms: 1_000,
+            fastest_period_ms: 150,
+            zone: Zone::Clear,
+            pattern: BeepPattern::Silent,
+            phase_ms: 0,
+        }
+    }
+
+    pub fn with_timing(mut self, on_ms: u32, slowest_period_ms: u32, fastest_period_ms: u32) -> Self {
+        assert!(
+            on_ms < fastest_period_ms && fastest_period_ms <= slowest_period_ms,
+            "beeps must be shorter than the fastest period"
+        );
+        self.on_ms = on_ms;
+        self.slowest_period_ms = slowest_period_ms;
+        self.fastest_period_ms = fastest_period_ms;
+        self
+    }
+
+    pub fn thresholds(&self) -> &ZoneThresholds {
+        &self.thresholds
+    }
+
+    /// Changes the distances the beep period is interpolated between.
+    pub fn set_thresholds(&mut self, thresholds: ZoneThresholds) {
+        self.thresholds = thresholds;
+    }
+
+    pub fn pattern(&self) -> BeepPattern {
+        self.pattern
+    }
+
+    /// Pattern for `reading` with the obstacle in `zone`. Between the far and
+    /// critical thresholds the period shrinks linearly with the distance.
+    ///
+    /// The critical zone stays continuous whatever the reading. Otherwise a
+    /// sensor fault gets the fault pattern, and a reading without distance
+    /// keeps the current pattern.
+    pub fn pattern_for(&self, zone: Zone, reading: Result<Distance, SensorError>) -> BeepPattern {
+        let distance = match (zone, reading) {
+            (Zone::Critical, _) => return BeepPattern::Continuous,
+            (_, Err(error)) if error.is_fault() => return BeepPattern::Fault,
+            (Zone::Clear, _) => return BeepPattern::Silent,
+            (_, Ok(distance)) => distance,
+            (_, Err(_)) => return self.pattern,
+        };
+
+        let (far, critical) = (self.thresholds.far().as_mm(), self.thresholds.critical().as_mm());
+        let position = distance.as_mm().min(far).saturating_sub(critical);
+        let span = self.slowest_period_ms - self.fastest_period_ms;
+
+        BeepPattern::Intermittent {
+            on_ms: self.on_ms,
+            // Zones confondues : le plus rapide dès la zone lointaine
+            period_ms: self.fastest_period_ms + (span * position).checked_div(far.saturating_sub(critical)).unwrap_or(0),
+        }
+    }
+
+    /// Classifies `reading` and switches to the matching pattern. Returns
+    /// the zone so that the caller can also update a LED bar; a reading that
+    /// tells nothing about the obstacle keeps the previous zone.
+    pub fn update(&mut self, reading: Result<Distance, SensorError>) -> Zone {
+        if let Some(zone) = self.thresholds.classify(reading) {
+            self.zone = zone;
+        }
+        self.set_pattern(self.pattern_for(self.zone, reading));
+        self.zone
+    }
+
+    /// Switches pattern. Coming out of silence the first beep starts right
+    /// away, otherwise the current beep goes on with the new period.
+    pub fn set_pattern(&mut self, pattern: BeepPattern) {
+        if self.pattern == BeepPattern::Silent {
+            self.phase_ms = 0;
+        }
+        self.pattern = pattern;
+    }
+
+    /// Whether the buzzer should sound for the next `elapsed_ms`.
+    pub fn tick(&mut self, elapsed_ms: u32) -> bool {
+        match self.pattern {
+            BeepPattern::Silent => false,
+            BeepPattern::Continuous => true,
+            BeepPattern::Intermittent { on_ms, period_ms } => self.cycle(elapsed_ms, on_ms, period_ms),
+            BeepPattern::Fault => self.cycle(elapsed_ms, FAULT_ON_MS, FAULT_PERIOD_MS),
+        }
+    }
+
+    fn cycle(&mut self, elapsed_ms: u32, on_ms: u32, period_ms: u32) -> bool {
+        let phase = self.phase_ms % period_ms;
+        self.phase_ms = (phase + elapsed_ms) % period_ms;
+        phase < on_ms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TICK_MS: u32 = 10;
+
+    fn period(beeper: &Beeper, cm: u32) -> u32 {
+        let reading = Ok(Distance::from_cm(cm));
+        match beeper.pattern_for(beeper.thresholds().classify(reading).unwrap(), reading) {
+            BeepPattern::Intermittent { period_ms, .. } => period_ms,
+            pattern => panic!("{pattern:?}"),
+        }
+    }
+
+    /// Buzzer state over `ms`, one character per tick.
+    fn trace(beeper: &mut Beeper, ms: u32) -> String {
+        (0..ms / TICK_MS).map(|_| if beeper.tick(TICK_MS) { '#' } else { '.' }).collect()
+    }
+
+    #[test]
+    fn beeps_faster_as_the_obstacle_approaches() {
+        let beeper = Beeper::new(ZoneThresholds::default());
+
+        assert_eq!(period(&beeper, 150), 1_000);
+        assert_eq!(period(&beeper, 90), 575);
+        assert_eq!(period(&beeper, 31), 157);
+
+        let periods: Vec<_> = (31..=150).rev().map(|cm| period(&beeper, cm)).collect();
+        assert!(periods.windows(2).all(|pair| pair[1] <= pair[0]));
+    }
+
+    #[test]
+    fn silent_when_clear_continuous_when_critical() {
+        let mut beeper = Beeper::new(ZoneThresholds::default());
+
+        assert_eq!(beeper.update(Err(SensorError::EchoTooLong)), Zone::Clear);
+        assert_eq!(trace(&mut beeper, 100), "..........");
+
+        assert_eq!(beeper.update(Ok(Distance::from_cm(200))), Zone::Clear);
+        assert_eq!(beeper.pattern(), BeepPattern::Silent);
+
+        assert_eq!(beeper.update(Ok(Distance::from_cm(20))), Zone::Critical);
+        assert_eq!(trace(&mut beeper, 100), "##########");
+    }
+
+    #[test]
+    fn failed_readings_do_not_silence_the_beeper() {
+        let mut beeper = Beeper::new(ZoneThresholds::default());
+
+        // Capteur débranché, rien devant : bip de défaut, pas de silence
+        assert_eq!(beeper.update(Err(SensorError::NoEchoStart)), Zone::Clear);
+        assert_eq!(beeper.pattern(), BeepPattern::Fault);
+        assert_eq!(trace(&mut beeper, 2_000).matches('#').count(), 50);
+
+        // En zone critique, un défaut ne coupe pas le son continu
+        beeper.update(Ok(Distance::from_cm(20)));
+        assert_eq!(beeper.update(Err(SensorError::BusError)), Zone::Critical);
+        assert_eq!(beeper.pattern(), BeepPattern::Continuous);
+        assert_eq!(beeper.pattern_for(Zone::Critical, Err(SensorError::EchoTooLong)), BeepPattern::Continuous);
+
+        // Zone aveugle : critique aussi
+        beeper.update(Ok(Distance::from_cm(100)));
+        assert_eq!(beeper.update(Err(SensorError::TooClose(Distance::from_cm(1)))), Zone::Critical);
+
+        // Une mesure trop tôt ou douteuse ne change rien
+        let pattern = beeper.pattern_for(Zone::Near, Ok(Distance::from_cm(60)));
+        beeper.set_pattern(pattern);
+        for error in [SensorError::TooSoon, SensorError::InvalidReading] {
+            assert_eq!(beeper.pattern_for(Zone::Near, Err(error)), pattern);
+        }
+    }
+
+    #[test]
+    fn plays_the_intermittent_pattern() {
+        let mut beeper = Beeper::new(ZoneThresholds::default()).with_timing(30, 1_000, 100);
+
+        beeper.set_pattern(BeepPattern::Intermittent { on_ms: 30, period_ms: 100 });
+        assert_eq!(trace(&mut beeper, 200), "###.......###.......");
+
+        // Changer de période ne relance pas le bip en cours
+        beeper.tick(TICK_MS);
+        beeper.set_pattern(BeepPattern::Intermittent { on_ms: 30, period_ms: 50 });
+        assert_eq!(trace(&mut beeper, 100), "##..###..#");
+    }
+}
@@ -0,0 +1,348 @@
+use crate::{Clock, Distance, DistanceSensor, SensorError};
+
+/// Median of the last `N` readings, removes isolated spikes.
+pub struct MedianFilter<const N: usize> {
+    window: [Distance; N],
+    len: usize,
+    next: usize,
+}
+
+impl<const N: usize> MedianFilter<N> {
+    pub const fn new() -> Self {
+        assert!(N > 0, "the median window cannot be empty");
+        Self {
+            window: [Distance::ZERO; N],
+            len: 0,
+            next: 0,
+        }
+    }
+
+    /// Adds a reading and returns the median of the window. Until the window
+    /// is full, the median of the readings received so far.
+    pub fn update(&mut self, distance: Distance) -> Distance {
+        self.window[self.next] = distance;
+        self.next = (self.next + 1) % N;
+        self.len = (self.len + 1).min(N);
+
+        let mut sorted = self.window;
+        let sorted = &mut sorted[..self.len];
+        sorted.sort_unstable();
+
+        if self.len % 2 == 1 {
+            sorted[self.len / 2]
+        } else {
+            let (low, high) = (sorted[self.len / 2 - 1].as_mm(), sorted[self.len / 2].as_mm());
+            Distance::from_mm(low + (high - low) / 2)
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.len = 0;
+        self.next = 0;
+    }
+}
+
+impl<const N: usize> Default for MedianFilter<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Exponential moving average: `output += alpha * (reading - output)`.
+pub struct MovingAverage {
+    alpha_pct: u8,
+    /// Output in 1/256 mm, so that small corrections are not lost to
+    /// integer rounding.
+    value: Option<u32>,
+}
+
+impl MovingAverage {
+    /// `alpha_pct` in `1..=100`, the smaller the smoother. 100 disables
+    /// smoothing.
+    pub fn new(alpha_pct: u8) -> Self {
+        assert!((1..=100).contains(&alpha_pct), "alpha must be in 1..=100 %");
+        Self { alpha_pct, value: None }
+    }
+
+    pub fn update(&mut self, distance: Distance) -> Distance {
+        let reading = distance.as_mm() << 8;
+        let value = match self.value {
+            Some(value) => {
+                let step = (reading as i64 - value as i64) * self.alpha_pct as i64 / 100;
+                (value as i64 + step) as u32
+            }
+            None => reading,
+        };
+        self.value = Some(value);
+        Distance::from_mm((value + 128) >> 8)
+    }
+
+    pub fn reset(&mut self) {
+        self.value = None;
+    }
+}
+
+/// Drops readings that jump more than `max_jump` away from the last accepted
+/// one.
+///
+/// A real change of scene (an obstacle entering the beam) also looks like a
+/// jump, so after `max_rejections` consecutive rejections the new level is
+/// accepted.
+pub struct OutlierRejector {
+    max_jump: Distance,
+    max_rejections: u8,
+    rejections: u8,
+    last: Option<Distance>,
+}
+
+impl OutlierRejector {
+    pub fn new(max_jump: Distance, max_rejections: u8) -> Self {
+        Self {
+            max_jump,
+            max_rejections,
+            rejections: 0,
+            last: None,
+        }
+    }
+
+    /// `Some(distance)` if the reading is accepted, `None` if rejected.
+    pub fn update(&mut self, distance: Distance) -> Option<Distance> {
+        if let Some(last) = self.last {
+            if distance.abs_diff(last) > self.max_jump && self.rejections < self.max_rejections {
+                self.rejections += 1;
+                return None;
+            }
+        }
+
+        self.rejections = 0;
+        self.last = Some(distance);
+        Some(distance)
+    }
+
+    pub fn reset(&mut self) {
+        self.rejections = 0;
+        self.last = None;
+    }
+}
+
+/// Outlier rejection, then median of `N`, then moving average. Each stage
+/// is optional: `N = 1` disables the median.
+pub struct DistanceFilter<const N: usize> {
+    outliers: Option<OutlierRejector>,
+    median: MedianFilter<N>,
+    average: Option<MovingAverage>,
+    output: Option<Distance>,
+}
+
+impl<const N: usize> DistanceFilter<N> {
+    pub fn new() -> Self {
+        Self {
+            outliers: None,
+            median: MedianFilter::new(),
+            average: None,
+            output: None,
+        }
+    }
+
+    pub fn with_outlier_rejection(mut self, max_jump: Distance, max_rejections: u8) -> Self {
+        self.outliers = Some(OutlierRejector::new(max_jump, max_rejections));
+        self
+    }
+
+    pub fn with_moving_average(mut self, alpha_pct: u8) -> Self {
+        self.average = Some(MovingAverage::new(alpha_pct));
+        self
+    }
+
+    /// Feeds a raw reading and returns the filtered distance. A rejected
+    /// outlier leaves the output unchanged, `None` until a first reading has
+    /// been accepted.
+    pub fn update(&mut self, distance: Distance) -> Option<Distance> {
+        let distance = match &mut self.outliers {
+            Some(outliers) => match outliers.update(distance) {
+                Some(distance) => distance,
+                None => return self.output,
+            },
+            None => distance,
+        };
+
+        let mut output = self.median.update(distance);
+        if let Some(average) = &mut self.average {
+            output = average.update(output);
+        }
+
+        self.output = Some(output);
+        self.output
+    }
+
+    /// Last filtered distance.
+    pub fn output(&self) -> Option<Distance> {
+        self.output
+    }
+
+    pub fn reset(&mut self) {
+        if let Some(outliers) = &mut self.outliers {
+            outliers.reset();
+        }
+        self.median.reset();
+        if let Some(average) = &mut self.average {
+            average.reset();
+        }
+        self.output = None;
+    }
+}
+
+impl<const N: usize> Default for DistanceFilter<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A [`DistanceSensor`] whose readings go through a [`DistanceFilter`]. It
+/// is itself a [`DistanceSensor`], so it can be used wherever a raw sensor
+/// is.
+pub struct FilteredSensor<S, const N: usize> {
+    sensor: S,
+    filter: DistanceFilter<N>,
+}
+
+impl<S, const N: usize> FilteredSensor<S, N> {
+    pub fn new(sensor: S, filter: DistanceFilter<N>) -> Self {
+        Self { sensor, filter }
+    }
+
+    pub fn sensor_mut(&mut self) -> &mut S {
+        &mut self.sensor
+    }
+
+    pub fn filter_mut(&mut self) -> &mut DistanceFilter<N> {
+        &mut self.filter
+    }
+
+    pub fn release(self) -> S {
+        self.sensor
+    }
+}
+
+impl<S: DistanceSensor, const N: usize> DistanceSensor for FilteredSensor<S, N> {
+    /// Takes a reading and returns the smoothed distance. Measurement errors
+    /// are passed through and do not disturb the filter; a rejected outlier
+    /// returns the previous smoothed distance.
+    fn measure_distance<C: Clock>(&mut self, clock: &mut C) -> Result<Distance, SensorError> {
+        let distance = self.sensor.measure_distance(clock)?;
+
+        // Le premier relevé est toujours accepté, `update` renvoie donc une valeur
+        Ok(self.filter.update(distance).unwrap_or(distance))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{Bench, EchoScript};
+    use crate::UltrasonicSensor;
+
+    /// Car backing towards a wall at ~1.5 m, measured every 100 ms against a
+    /// soft surface: two spurious long echoes and one short multipath echo.
+    const APPROACH_MM: [u32; 16] = [
+        1521, 1508, 1499, 1482, 3124, 1460, 1453, 1439,
+        1426, 213, 1402, 1390, 1377, 3418, 1351, 1339,
+    ];
+
+    /// Obstacle suddenly entering the beam at ~60 cm.
+    const STEP_MM: [u32; 10] = [
+        2302, 2298, 2305, 612, 608, 615, 609, 611, 607, 610,
+    ];
+
+    fn samples(mm: &[u32]) -> Vec<Distance> {
+        mm.iter().map(|&mm| Distance::from_mm(mm)).collect()
+    }
+
+    fn max_step(values: &[Distance]) -> u32 {
+        values.windows(2).map(|pair| pair[1].abs_diff(pair[0]).as_mm()).max().unwrap()
+    }
+
+    #[test]
+    fn median_removes_spikes() {
+        let approach = samples(&APPROACH_MM);
+        let mut median = MedianFilter::<3>::new();
+        let output: Vec<_> = approach.iter().map(|&d| median.update(d)).collect();
+
+        assert!(max_step(&approach) > 1_000);
+        assert!(max_step(&output[2..]) < 50, "{output:?}");
+    }
+
+    #[test]
+    fn median_of_partial_window() {
+        let mut median = MedianFilter::<5>::new();
+
+        assert_eq!(median.update(Distance::from_mm(100)), Distance::from_mm(100));
+        assert_eq!(median.update(Distance::from_mm(200)), Distance::from_mm(150));
+        assert_eq!(median.update(Distance::from_mm(900)), Distance::from_mm(200));
+    }
+
+    #[test]
+    fn moving_average_smooths() {
+        let mut average = MovingAverage::new(50);
+
+        assert_eq!(average.update(Distance::from_mm(1_000)), Distance::from_mm(1_000));
+        assert_eq!(average.update(Distance::from_mm(500)), Distance::from_mm(750));
+        assert_eq!(average.update(Distance::from_mm(500)), Distance::from_mm(625));
+    }
+
+    #[test]
+    fn moving_average_converges_without_rounding_stall() {
+        let mut average = MovingAverage::new(10);
+        average.update(Distance::from_mm(1_000));
+
+        let mut output = Distance::ZERO;
+        for _ in 0..100 {
+            output = average.update(Distance::from_mm(1_004));
+        }
+
+        assert_eq!(output, Distance::from_mm(1_004));
+    }
+
+    #[test]
+    fn rejector_drops_jumps_then_follows_a_new_level() {
+        let step = samples(&STEP_MM);
+        let mut outliers = OutlierRejector::new(Distance::from_cm(20), 2);
+        let accepted: Vec<_> = step.iter().map(|&d| outliers.update(d)).collect();
+
+        assert_eq!(accepted[..3], step[..3].iter().map(|&d| Some(d)).collect::<Vec<_>>()[..]);
+        assert_eq!(accepted[3..5], [None, None]);
+        assert_eq!(accepted[5..], step[5..].iter().map(|&d| Some(d)).collect::<Vec<_>>()[..]);
+    }
+
+    #[test]
+    fn pipeline_tracks_the_approach() {
+        let mut filter = DistanceFilter::<3>::new()
+            .with_outlier_rejection(Distance::from_cm(30), 2)
+            .with_moving_average(60);
+        let output: Vec<_> = samples(&APPROACH_MM).iter().map(|&d| filter.update(d).unwrap()).collect();
+
+        assert!(max_step(&output) < 50, "{output:?}");
+        assert!(output[15].abs_diff(Distance::from_mm(1_345)) < Distance::from_mm(30), "{output:?}");
+    }
+
+    #[test]
+    fn filtered_sensor_smooths_readings() {
+        let bench = Bench::new(EchoScript::Pulse { delay_us: 450, width_us: 5_814 });
+        let sensor = UltrasonicSensor::new(bench.trigger(), bench.echo(), bench.delay());
+        let mut sensor = FilteredSensor::new(sensor, DistanceFilter::<3>::new());
+        let one_meter = Distance::from_cm(100);
+
+        let first = sensor.measure_distance(&mut bench.clock()).unwrap();
+        assert!(first.abs_diff(one_meter) <= Distance::from_mm(1), "{first}");
+
+        // Un écho parasite isolé est absorbé par la médiane
+        bench.script(EchoScript::Pulse { delay_us: 450, width_us: 1_163 });
+        sensor.measure_distance(&mut bench.clock()).unwrap();
+        bench.script(EchoScript::Pulse { delay_us: 450, width_us: 5_814 });
+        let filtered = sensor.measure_distance(&mut bench.clock()).unwrap();
+        assert!(filtered.abs_diff(one_meter) <= Distance::from_mm(1), "{filtered}");
+
+        bench.script(EchoScript::Silent);
+        assert_eq!(sensor.measure_distance(&mut bench.clock()), Err(SensorError::NoEchoStart));
+    }
+}
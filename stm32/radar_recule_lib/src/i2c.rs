@@ -0,0 +1,103 @@
+use embedded_hal::{delay::DelayNs, i2c::I2c};
+
+use crate::{Clock, Distance, DistanceSensor, SensorError};
+
+/// RCWL-1601 (and RCWL-9600 based boards) in I2C mode: writing 0x01 starts
+/// a measurement, whose result is read back ~100 ms later as a 24-bit
+/// distance in µm.
+pub struct Rcwl1601<I, D> {
+    i2c: I,
+    delay: D,
+    address: u8,
+}
+
+impl<I, D> Rcwl1601<I, D> {
+    pub const DEFAULT_ADDRESS: u8 = 0x57;
+    const MEASURE: u8 = 0x01;
+    /// Time the module needs before its result can be read.
+    pub const CONVERSION_MS: u32 = 120;
+    const MIN_MM: u32 = 20;
+    const MAX_MM: u32 = 4_500;
+
+    pub fn new(i2c: I, delay: D) -> Self {
+        Self {
+            i2c,
+            delay,
+            address: Self::DEFAULT_ADDRESS,
+        }
+    }
+
+    pub fn with_address(mut self, address: u8) -> Self {
+        self.address = address;
+        self
+    }
+
+    pub fn release(self) -> (I, D) {
+        (self.i2c, self.delay)
+    }
+}
+
+impl<I: I2c, D> Rcwl1601<I, D> {
+    /// Starts a measurement and returns right away. The result can be read
+    /// with [`Self::read_distance`] [`Self::CONVERSION_MS`] later, e.g. from
+    /// the next tick of a periodic task.
+    pub fn start_measurement(&mut self) -> Result<(), SensorError> {
+        self.i2c.write(self.address, &[Self::MEASURE]).map_err(|_| SensorError::BusError)
+    }
+
+    pub fn read_distance(&mut self) -> Result<Distance, SensorError> {
+        let mut raw = [0; 3];
+        self.i2c.read(self.address, &mut raw).map_err(|_| SensorError::BusError)?;
+
+        let um = u32::from_be_bytes([0, raw[0], raw[1], raw[2]]);
+        let distance = Distance::from_mm((um + 500) / 1_000);
+        if distance.as_mm() < Self::MIN_MM {
+            return Err(SensorError::TooClose(distance));
+        }
+        if distance.as_mm() > Self::MAX_MM {
+            return Err(SensorError::OutOfRange(distance));
+        }
+        Ok(distance)
+    }
+}
+
+impl<I: I2c, D: DelayNs> DistanceSensor for Rcwl1601<I, D> {
+    fn measure_distance<C: Clock>(&mut self, _clock: &mut C) -> Result<Distance, SensorError> {
+        self.start_measurement()?;
+        self.delay.delay_ms(Self::CONVERSION_MS);
+        self.read_distance()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{Bench, EchoScript, MockI2c};
+
+    #[test]
+    fn reads_the_distance_register() {
+        let bench = Bench::new(EchoScript::Silent);
+        let i2c = MockI2c::default();
+        // 1 234 567 µm
+        i2c.answer(&[0x12, 0xD6, 0x87]);
+        let mut sensor = Rcwl1601::new(i2c.clone(), bench.delay());
+
+        assert_eq!(sensor.measure_distance(&mut bench.clock()), Ok(Distance::from_mm(1_235)));
+        assert_eq!(i2c.writes(), [(0x57, vec![0x01])]);
+        assert!(bench.now_us() >= Rcwl1601::<MockI2c, ()>::CONVERSION_MS * 1_000);
+    }
+
+    #[test]
+    fn reports_bus_and_range_errors() {
+        let bench = Bench::new(EchoScript::Silent);
+        let i2c = MockI2c::default();
+        i2c.answer(&[0, 0, 0]);
+        let mut sensor = Rcwl1601::new(i2c.clone(), bench.delay()).with_address(0x58);
+
+        assert_eq!(sensor.measure_distance(&mut bench.clock()), Err(SensorError::TooClose(Distance::ZERO)));
+        assert_eq!(i2c.writes()[0].0, 0x58);
+
+        i2c.break_bus();
+        assert_eq!(sensor.measure_distance(&mut bench.clock()), Err(SensorError::BusError));
+    }
+}
@@ -1,61 +1,646 @@
-#![no_std]
+#![cfg_attr(not(any(test, feature = "mock")), no_std)]
 
-use stm32f4xx_hal::{
-    gpio::{Output, PushPull, Input},
-    timer::{DelayUs, CounterUs},
-};
-use rtt_target::rprintln;
+mod approach;
+mod array;
+#[cfg(feature = "async")]
+mod asynch;
+mod console;
+mod distance;
+mod environment;
+mod feedback;
+mod filter;
+mod i2c;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+mod pins;
+mod polar;
+mod profile;
+mod scan;
+mod tof;
+mod uart;
+mod zone;
 
-pub struct UltrasonicSensor<T, E, D> {
-    trigger_pin: T,
-    echo_pin: E,
+pub use approach::{Approach, ApproachTracker};
+pub use array::SensorArray;
+pub use console::{parse as parse_command, Command, CommandError, LineEditor, LineEvent, OutputQueue};
+pub use distance::Distance;
+pub use environment::{Environment, EnvironmentSource};
+pub use feedback::{BeepPattern, Beeper};
+pub use filter::{DistanceFilter, FilteredSensor, MedianFilter, MovingAverage, OutlierRejector};
+pub use i2c::Rcwl1601;
+pub use pins::{EchoPins, IoPin, OpenDrain, SinglePin, TwoPins};
+pub use polar::PolarMap;
+pub use profile::SensorProfile;
+pub use scan::{Servo, Sweep, SweepFrame};
+pub use tof::Vl53l0x;
+pub use uart::{A02yyuw, Us100};
+pub use zone::{Zone, ZoneClassifier, ZoneThresholds, ZoneTransition};
+
+use embedded_hal::{delay::DelayNs, digital::InputPin};
+
+/// Maximum time to wait for the echo line to go high after the trigger pulse.
+const ECHO_START_TIMEOUT_US: u32 = 5_000;
+
+/// Reasons why a measurement did not produce a distance.
+///
+/// New drivers may add variants: matches outside this crate need a wildcard
+/// arm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SensorError {
+    /// The echo line never went high after the trigger pulse: the sensor is
+    /// most likely disconnected or not powered.
+    NoEchoStart,
+    /// The echo line stayed high longer than the maximum range allows:
+    /// nothing in range reflected the burst. An HC-SR04 keeps it high for
+    /// ~38 ms in that case.
+    EchoTooLong,
+    /// The echo was received but the computed distance is beyond the
+    /// sensor's maximum range.
+    OutOfRange(Distance),
+    /// The computed distance is within the sensor's blind zone: the
+    /// obstacle is closer than the sensor can measure.
+    TooClose(Distance),
+    /// A non-blocking measurement was started before the sensor's minimum
+    /// cycle time had elapsed since the previous trigger.
+    TooSoon,
+    /// A trigger or echo pin operation failed.
+    PinError,
+    /// A UART or I2C sensor did not answer in time.
+    NoResponse,
+    /// A frame was received but its checksum does not match.
+    BadChecksum,
+    /// The UART or I2C transfer with the sensor failed.
+    BusError,
+    /// The device on the bus did not identify as the expected sensor.
+    WrongDevice,
+    /// The sensor flagged its own measurement as unreliable, e.g. a hardware
+    /// fault or a target closer than its minimum range.
+    InvalidReading,
+    /// A setting is outside what the sensor supports, e.g. a VL53L0X timing
+    /// budget shorter than its ranging sequence.
+    InvalidConfig,
+}
+
+impl SensorError {
+    /// Whether the sensor or its wiring is at fault, rather than the scene
+    /// in front of it or a single measurement.
+    pub const fn is_fault(self) -> bool {
+        !matches!(
+            self,
+            Self::EchoTooLong | Self::OutOfRange(_) | Self::TooClose(_) | Self::TooSoon | Self::InvalidReading
+        )
+    }
+}
+
+/// Free-running microsecond time base used to time the echo pulse.
+///
+/// Any `FnMut() -> u32` is a clock, so a HAL counter can be passed as
+/// `&mut || timer.now().ticks()`.
+pub trait Clock {
+    /// Current time in microseconds. The value may wrap around, elapsed
+    /// times are computed with wrapping arithmetic.
+    fn now_us(&mut self) -> u32;
+}
+
+impl<F: FnMut() -> u32> Clock for F {
+    fn now_us(&mut self) -> u32 {
+        self()
+    }
+}
+
+/// Anything able to measure a distance, whatever the way it talks to the
+/// microcontroller: echo pulse, UART frames or I2C registers. Code written
+/// against this trait works with any of the supported modules.
+///
+/// Only the blocking measurement is abstracted. The non-blocking calls
+/// differ too much between an echo timed from interrupts and a frame or a
+/// register polled on a bus, so they stay on each driver, e.g.
+/// [`EchoSensor::start_measurement`] or [`Vl53l0x::read_range`].
+pub trait DistanceSensor {
+    /// Takes one measurement, blocking until its result is known.
+    fn measure_distance<C: Clock>(&mut self, clock: &mut C) -> Result<Distance, SensorError>;
+}
+
+/// Progress of a non-blocking measurement.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Capture {
+    Idle,
+    /// Trigger pulse sent at the given time, echo not started yet.
+    WaitingRise(u32),
+    /// Echo started at the given time.
+    WaitingFall(u32),
+}
+
+/// Ultrasonic sensor timed from its echo pulse, whatever its wiring: see
+/// [`UltrasonicSensor`] and [`SinglePinSensor`].
+pub struct EchoSensor<P, D> {
+    pins: P,
     delay: D,
+    environment: Environment,
+    profile: SensorProfile,
+    capture: Capture,
+    last_trigger_us: Option<u32>,
 }
 
-impl<T, E, D> UltrasonicSensor<T, E, D>
-where
-    T: Output<PushPull>,
-    E: Input,
-    D: DelayUs<u32>,
-{
+/// Sensor with separate trigger and echo pins, such as the HC-SR04.
+pub type UltrasonicSensor<T, E, D> = EchoSensor<TwoPins<T, E>, D>;
+
+/// 3-wire sensor whose single signal pin carries both the trigger and the
+/// echo, such as the Parallax PING))).
+pub type SinglePinSensor<P, D> = EchoSensor<SinglePin<P>, D>;
+
+impl<T, E, D> EchoSensor<TwoPins<T, E>, D> {
+    /// A sensor with the [`SensorProfile::HC_SR04`] timings.
     pub fn new(trigger_pin: T, echo_pin: E, delay: D) -> Self {
+        Self::from_pins(TwoPins::new(trigger_pin, echo_pin), delay)
+    }
+}
+
+impl<P, D> EchoSensor<SinglePin<P>, D> {
+    /// A sensor with the [`SensorProfile::PING`] timings. `pin` must be a
+    /// pin whose direction can be switched, or an [`OpenDrain`] pin with a
+    /// pull-up.
+    pub fn new(pin: P, delay: D) -> Self {
+        Self::from_pins(SinglePin::new(pin), delay).with_profile(SensorProfile::PING)
+    }
+}
+
+impl<P, D> EchoSensor<P, D> {
+    fn from_pins(pins: P, delay: D) -> Self {
         Self {
-            trigger_pin,
-            echo_pin,
+            pins,
             delay,
+            environment: Environment::default(),
+            profile: SensorProfile::HC_SR04,
+            capture: Capture::Idle,
+            last_trigger_us: None,
+        }
+    }
+
+    /// Uses the blind zone, range and timings of another module.
+    pub fn with_profile(mut self, profile: SensorProfile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    pub fn profile(&self) -> &SensorProfile {
+        &self.profile
+    }
+
+    /// Sets the air temperature used for the following readings.
+    pub fn set_temperature(&mut self, celsius: f32) {
+        self.environment.set_temperature_c(celsius);
+    }
+
+    /// Sets the relative humidity used for the following readings.
+    pub fn set_humidity(&mut self, percent: f32) {
+        self.environment.set_humidity_pct(percent);
+    }
+
+    /// Sets both ambient conditions at once, without floating point.
+    pub fn set_environment(&mut self, environment: Environment) {
+        self.environment = environment;
+    }
+
+    pub fn environment(&self) -> Environment {
+        self.environment
+    }
+
+    /// Refreshes the ambient conditions from `source`. Values the source
+    /// cannot provide keep their previous setting.
+    pub fn update_environment<S: EnvironmentSource>(&mut self, source: &mut S) {
+        if let Some(celsius) = source.temperature_c() {
+            self.environment.set_temperature_c(celsius);
+        }
+        if let Some(percent) = source.humidity_pct() {
+            self.environment.set_humidity_pct(percent);
+        }
+    }
+
+    /// `true` while a measurement started by [`Self::start_measurement`] is
+    /// still waiting for its echo.
+    pub fn is_measuring(&self) -> bool {
+        self.capture != Capture::Idle
+    }
+
+    /// Longest echo accepted with the current profile and speed of sound.
+    fn max_echo_us(&self) -> u32 {
+        self.profile.max_echo_us(self.environment.speed_of_sound_mm_s())
+    }
+
+    /// Time left before the minimum cycle time allows a new trigger.
+    fn cycle_remaining_us(&self, now_us: u32) -> u32 {
+        match self.last_trigger_us {
+            Some(last) => self.profile.min_cycle_us.saturating_sub(now_us.wrapping_sub(last)),
+            None => 0,
         }
     }
 
-    pub fn measure_distance(
-        &mut self,
-        timer: &mut CounterUs,
-    ) -> Option<f64> {
-        rprintln!("Measuring distance...");
+    fn distance_from_echo(&self, echo_time: u32) -> Result<Distance, SensorError> {
+        // Calculer la distance : aller-retour à la vitesse du son du moment, en entiers
+        let distance = Distance::from_echo(echo_time, self.environment.speed_of_sound_mm_s());
+
+        if distance < self.profile.blind_zone {
+            return Err(SensorError::TooClose(distance));
+        }
+        if distance > self.profile.max_range {
+            return Err(SensorError::OutOfRange(distance));
+        }
+
+        Ok(distance)
+    }
+}
 
-        // Envoyer une impulsion de 10 µs sur le trigger pour démarrer la mesure
-        self.trigger_pin.set_high();
-        self.delay.delay_us(10_u32);
-        self.trigger_pin.set_low();
+impl<P, D> EchoSensor<P, D>
+where
+    P: EchoPins,
+    D: DelayNs,
+{
+    /// Access to the echo pin, e.g. to clear its interrupt pending bit.
+    pub fn echo_pin_mut(&mut self) -> &mut P::Echo {
+        self.pins.echo()
+    }
 
-        // Attendre que l'écho passe à HIGH
-        while self.echo_pin.is_low() {
+    /// Measures the distance, blocking until the echo has ended. If the
+    /// previous trigger is more recent than the profile's minimum cycle
+    /// time, waits for the remaining time first.
+    pub fn measure_distance<C: Clock>(&mut self, clock: &mut C) -> Result<Distance, SensorError> {
+        let remaining = self.cycle_remaining_us(clock.now_us());
+        if remaining > 0 {
+            self.delay.delay_us(remaining);
         }
 
-        // Démarrer le timer pour mesurer la durée de l'écho
-        let start_time = timer.now();
+        let trigger_time = self.trigger(clock)?;
 
-        // Attendre que l'écho passe à LOW
-        while self.echo_pin.is_high() {
+        // Attendre que l'écho passe à HIGH, sans dépasser le timeout
+        while self.pins.echo().is_low().map_err(|_| SensorError::PinError)? {
+            if clock.now_us().wrapping_sub(trigger_time) > ECHO_START_TIMEOUT_US {
+                return Err(SensorError::NoEchoStart);
+            }
+        }
+
+        // Démarrer le chronomètre pour mesurer la durée de l'écho
+        let start_time = clock.now_us();
+
+        // Attendre que l'écho passe à LOW, sans dépasser la portée maximale
+        let max_echo_us = self.max_echo_us();
+        while self.pins.echo().is_high().map_err(|_| SensorError::PinError)? {
+            if clock.now_us().wrapping_sub(start_time) > max_echo_us {
+                return Err(SensorError::EchoTooLong);
+            }
         }
 
         // Lire la durée du signal d'écho en µs
-        let echo_time = (timer.now() - start_time).to_micros();
-        rprintln!("Duration time : {}µs", echo_time);
+        let echo_time = clock.now_us().wrapping_sub(start_time);
+
+        self.distance_from_echo(echo_time)
+    }
+
+    /// Sends the trigger pulse and returns without waiting for the echo.
+    ///
+    /// The echo edges must then be reported with [`Self::on_echo_edge`],
+    /// typically from the echo pin's EXTI interrupt, and
+    /// [`Self::check_timeout`] must be called periodically to detect a
+    /// missing or endless echo.
+    ///
+    /// Fails with [`SensorError::TooSoon`] instead of waiting if the
+    /// profile's minimum cycle time has not elapsed since the previous
+    /// trigger.
+    pub fn start_measurement<C: Clock>(&mut self, clock: &mut C) -> Result<(), SensorError> {
+        // Refusée, la mesure en cours continue
+        if self.cycle_remaining_us(clock.now_us()) > 0 {
+            return Err(SensorError::TooSoon);
+        }
+
+        self.capture = Capture::Idle;
+        let trigger_time = self.trigger(clock)?;
+        self.capture = Capture::WaitingRise(trigger_time);
+        Ok(())
+    }
+
+    /// Sends the trigger pulse, returns the time of its falling edge.
+    fn trigger<C: Clock>(&mut self, clock: &mut C) -> Result<u32, SensorError> {
+        // Envoyer une impulsion sur le trigger pour démarrer la mesure
+        self.pins.set_trigger(true)?;
+        self.delay.delay_us(self.profile.trigger_pulse_us);
+        self.pins.set_trigger(false)?;
+        self.pins.listen()?;
+
+        let now = clock.now_us();
+        self.last_trigger_us = Some(now);
+        Ok(now)
+    }
+
+    /// Handles an edge on the echo line seen at `now_us`. Returns the result
+    /// of the measurement once the echo has ended.
+    pub fn on_echo_edge(&mut self, now_us: u32) -> Option<Result<Distance, SensorError>> {
+        let high = match self.pins.echo().is_high() {
+            Ok(high) => high,
+            Err(_) => {
+                self.capture = Capture::Idle;
+                return Some(Err(SensorError::PinError));
+            }
+        };
+
+        match (self.capture, high) {
+            (Capture::WaitingRise(_), true) => {
+                self.capture = Capture::WaitingFall(now_us);
+                None
+            }
+            (Capture::WaitingFall(start_time), false) => {
+                self.capture = Capture::Idle;
+                let echo_time = now_us.wrapping_sub(start_time);
+                if echo_time > self.max_echo_us() {
+                    return Some(Err(SensorError::EchoTooLong));
+                }
+                Some(self.distance_from_echo(echo_time))
+            }
+            // Front parasite ou aucune mesure en cours
+            _ => None,
+        }
+    }
+
+    /// Gives up on the pending measurement if its echo is overdue at
+    /// `now_us`, returning the corresponding error.
+    pub fn check_timeout(&mut self, now_us: u32) -> Option<Result<Distance, SensorError>> {
+        let error = match self.capture {
+            Capture::WaitingRise(trigger_time)
+                if now_us.wrapping_sub(trigger_time) > ECHO_START_TIMEOUT_US =>
+            {
+                SensorError::NoEchoStart
+            }
+            Capture::WaitingFall(start_time)
+                if now_us.wrapping_sub(start_time) > self.max_echo_us() =>
+            {
+                SensorError::EchoTooLong
+            }
+            _ => return None,
+        };
+
+        self.capture = Capture::Idle;
+        Some(Err(error))
+    }
+}
+
+impl<P, D> DistanceSensor for EchoSensor<P, D>
+where
+    P: EchoPins,
+    D: DelayNs,
+{
+    fn measure_distance<C: Clock>(&mut self, clock: &mut C) -> Result<Distance, SensorError> {
+        EchoSensor::measure_distance(self, clock)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{Bench, EchoScript};
+
+    fn sensor(bench: &Bench) -> UltrasonicSensor<mock::MockTrigger, mock::MockEcho, mock::MockDelay> {
+        UltrasonicSensor::new(bench.trigger(), bench.echo(), bench.delay())
+    }
+
+    fn assert_close(distance: Distance, expected_mm: u32) {
+        let expected = Distance::from_mm(expected_mm);
+        assert!(distance.abs_diff(expected) <= Distance::from_mm(1), "{distance} != {expected}");
+    }
+
+    #[test]
+    fn measures_echo_width() {
+        let bench = Bench::new(EchoScript::Pulse { delay_us: 450, width_us: 1_000 });
+        let mut sensor = sensor(&bench);
+
+        assert_close(sensor.measure_distance(&mut bench.clock()).unwrap(), 172);
+
+        bench.script(EchoScript::Pulse { delay_us: 450, width_us: 11_626 });
+        assert_close(sensor.measure_distance(&mut bench.clock()).unwrap(), 2_000);
+    }
+
+    #[test]
+    fn compensates_for_temperature() {
+        // Obstacle à 2 m : l'écho dure 12 299 µs à -10 °C et 11 347 µs à 35 °C
+        let bench = Bench::new(EchoScript::Pulse { delay_us: 450, width_us: 12_299 });
+        let mut sensor = sensor(&bench);
+        sensor.set_humidity(0.0);
+
+        sensor.set_temperature(-10.0);
+        assert_close(sensor.measure_distance(&mut bench.clock()).unwrap(), 2_000);
+
+        bench.script(EchoScript::Pulse { delay_us: 450, width_us: 11_347 });
+        sensor.set_temperature(35.0);
+        assert_close(sensor.measure_distance(&mut bench.clock()).unwrap(), 2_000);
+    }
+
+    #[test]
+    fn reads_the_environment_source() {
+        struct Hygrometer;
+
+        impl EnvironmentSource for Hygrometer {
+            fn temperature_c(&mut self) -> Option<f32> {
+                Some(-5.5)
+            }
+        }
+
+        let bench = Bench::new(EchoScript::Silent);
+        let mut sensor = sensor(&bench);
+        sensor.update_environment(&mut Hygrometer);
+
+        assert_eq!(sensor.environment(), Environment { temperature_dc: -55, humidity_pct: 50 });
+    }
+
+    #[test]
+    fn sends_a_10_us_trigger_pulse() {
+        let bench = Bench::new(EchoScript::Pulse { delay_us: 450, width_us: 1_000 });
+        let mut sensor = sensor(&bench);
+
+        sensor.measure_distance(&mut bench.clock()).unwrap();
+        sensor.measure_distance(&mut bench.clock()).unwrap();
+
+        assert_eq!(bench.trigger_pulses(), [10, 10]);
+    }
+
+    #[test]
+    fn reports_a_silent_sensor() {
+        let bench = Bench::new(EchoScript::Silent);
+        let mut sensor = sensor(&bench);
+
+        assert_eq!(sensor.measure_distance(&mut bench.clock()), Err(SensorError::NoEchoStart));
+        assert!(bench.now_us() <= ECHO_START_TIMEOUT_US + 20);
+    }
+
+    #[test]
+    fn reports_a_late_echo_start() {
+        let bench = Bench::new(EchoScript::Pulse { delay_us: ECHO_START_TIMEOUT_US + 100, width_us: 1_000 });
+        let mut sensor = sensor(&bench);
+
+        assert_eq!(sensor.measure_distance(&mut bench.clock()), Err(SensorError::NoEchoStart));
+    }
+
+    #[test]
+    fn reports_an_echo_longer_than_the_range() {
+        let bench = Bench::new(EchoScript::Pulse { delay_us: 450, width_us: 38_000 });
+        let mut sensor = sensor(&bench);
+
+        assert_eq!(sensor.measure_distance(&mut bench.clock()), Err(SensorError::EchoTooLong));
+        assert!(bench.now_us() < 450 + sensor.max_echo_us() + 20);
+    }
+
+    #[test]
+    fn clips_readings_outside_the_range() {
+        let bench = Bench::new(EchoScript::Pulse { delay_us: 450, width_us: 50 });
+        let mut sensor = sensor(&bench);
+
+        match sensor.measure_distance(&mut bench.clock()) {
+            Err(SensorError::TooClose(distance)) => assert!(distance < sensor.profile().blind_zone),
+            other => panic!("unexpected {other:?}"),
+        }
+
+        bench.script(EchoScript::Pulse { delay_us: 450, width_us: 24_000 });
+        match sensor.measure_distance(&mut bench.clock()) {
+            Err(SensorError::OutOfRange(distance)) => assert!(distance > sensor.profile().max_range),
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+
+    #[test]
+    fn survives_clock_wrap_around() {
+        let bench = Bench::starting_at(u32::MAX - 700, EchoScript::Pulse { delay_us: 450, width_us: 1_000 });
+        let mut sensor = sensor(&bench);
+
+        assert_close(sensor.measure_distance(&mut bench.clock()).unwrap(), 172);
+    }
+
+    #[test]
+    fn reports_pin_failures() {
+        let bench = Bench::new(EchoScript::Pulse { delay_us: 450, width_us: 1_000 });
+        let mut sensor = sensor(&bench);
+        bench.break_pins();
+
+        assert_eq!(sensor.measure_distance(&mut bench.clock()), Err(SensorError::PinError));
+    }
+
+    #[test]
+    fn measures_from_echo_edges() {
+        let bench = Bench::new(EchoScript::Pulse { delay_us: 450, width_us: 1_000 });
+        let mut sensor = sensor(&bench);
+
+        sensor.start_measurement(&mut bench.clock()).unwrap();
+        assert!(sensor.is_measuring());
+        assert_eq!(bench.trigger_pulses(), [10]);
+
+        bench.advance(450);
+        assert_eq!(sensor.on_echo_edge(bench.now_us()), None);
+        assert_eq!(sensor.check_timeout(bench.now_us()), None);
+
+        bench.advance(1_000);
+        assert_close(sensor.on_echo_edge(bench.now_us()).unwrap().unwrap(), 172);
+        assert!(!sensor.is_measuring());
+    }
+
+    #[test]
+    fn ignores_edges_outside_a_measurement() {
+        let bench = Bench::new(EchoScript::Pulse { delay_us: 450, width_us: 1_000 });
+        let mut sensor = sensor(&bench);
+
+        assert_eq!(sensor.on_echo_edge(bench.now_us()), None);
+        assert_eq!(sensor.check_timeout(bench.now_us()), None);
+    }
+
+    #[test]
+    fn times_out_without_echo_edges() {
+        let bench = Bench::new(EchoScript::Silent);
+        let mut sensor = sensor(&bench);
+
+        sensor.start_measurement(&mut bench.clock()).unwrap();
+        bench.advance(ECHO_START_TIMEOUT_US / 2);
+        assert_eq!(sensor.check_timeout(bench.now_us()), None);
+        bench.advance(ECHO_START_TIMEOUT_US);
+        assert_eq!(sensor.check_timeout(bench.now_us()), Some(Err(SensorError::NoEchoStart)));
+        assert!(!sensor.is_measuring());
+
+        bench.script(EchoScript::Pulse { delay_us: 450, width_us: 38_000 });
+        bench.advance(sensor.profile().min_cycle_us);
+        sensor.start_measurement(&mut bench.clock()).unwrap();
+        bench.advance(450);
+        assert_eq!(sensor.on_echo_edge(bench.now_us()), None);
+        bench.advance(sensor.max_echo_us() + 1);
+        assert_eq!(sensor.check_timeout(bench.now_us()), Some(Err(SensorError::EchoTooLong)));
+    }
+
+    #[test]
+    fn applies_the_sensor_profile() {
+        // Obstacle à 15 cm : mesurable par un HC-SR04, dans la zone aveugle d'un JSN-SR04T
+        let bench = Bench::new(EchoScript::Pulse { delay_us: 450, width_us: 872 });
+        let mut sensor = sensor(&bench);
+        assert_close(sensor.measure_distance(&mut bench.clock()).unwrap(), 150);
+
+        let mut sensor = sensor.with_profile(SensorProfile::JSN_SR04T);
+        match sensor.measure_distance(&mut bench.clock()) {
+            Err(SensorError::TooClose(distance)) => assert!(distance < sensor.profile().blind_zone),
+            other => panic!("unexpected {other:?}"),
+        }
+        assert_eq!(bench.trigger_pulses(), [10, 20]);
+
+        // 5 m : hors de portée d'un HC-SR04 mais pas d'un JSN-SR04T
+        bench.script(EchoScript::Pulse { delay_us: 450, width_us: 29_071 });
+        assert_close(sensor.measure_distance(&mut bench.clock()).unwrap(), 5_000);
+    }
+
+    #[test]
+    fn enforces_the_minimum_cycle_time() {
+        let bench = Bench::new(EchoScript::Pulse { delay_us: 450, width_us: 1_000 });
+        let mut sensor = sensor(&bench);
+        let min_cycle_us = sensor.profile().min_cycle_us;
+
+        sensor.measure_distance(&mut bench.clock()).unwrap();
+        let first_end = bench.now_us();
+        sensor.measure_distance(&mut bench.clock()).unwrap();
+        // Le second déclenchement a attendu la fin du cycle
+        assert!(bench.now_us() - first_end >= min_cycle_us - 1_500);
+        assert!(bench.now_us() - first_end < min_cycle_us + 1_500);
+
+        // En mode non bloquant, la mesure est refusée au lieu d'attendre
+        assert_eq!(sensor.start_measurement(&mut bench.clock()), Err(SensorError::TooSoon));
+        assert!(!sensor.is_measuring());
+        bench.advance(min_cycle_us);
+        assert_eq!(sensor.start_measurement(&mut bench.clock()), Ok(()));
+
+        // Un déclenchement refusé n'abandonne pas la mesure en cours
+        assert_eq!(sensor.start_measurement(&mut bench.clock()), Err(SensorError::TooSoon));
+        assert!(sensor.is_measuring());
+    }
+
+    #[test]
+    fn drives_a_single_pin_sensor() {
+        // PING))) : l'écho démarre 750 µs après l'impulsion, obstacle à 50 cm
+        let bench = Bench::new(EchoScript::Pulse { delay_us: 750, width_us: 2_907 });
+        let mut sensor = SinglePinSensor::new(bench.io_pin(), bench.delay());
+
+        assert_close(sensor.measure_distance(&mut bench.clock()).unwrap(), 500);
+        assert_close(sensor.measure_distance(&mut bench.clock()).unwrap(), 500);
+        assert_eq!(bench.trigger_pulses(), [5, 5]);
+
+        bench.script(EchoScript::Silent);
+        assert_eq!(sensor.measure_distance(&mut bench.clock()), Err(SensorError::NoEchoStart));
+
+        bench.break_pins();
+        assert_eq!(sensor.measure_distance(&mut bench.clock()), Err(SensorError::PinError));
+    }
+
+    #[test]
+    fn single_pin_sensor_measures_from_echo_edges() {
+        let bench = Bench::new(EchoScript::Pulse { delay_us: 750, width_us: 1_000 });
+        let mut sensor = SinglePinSensor::new(bench.io_pin(), bench.delay());
 
-        // Calculer la distance en cm
-        let distance_cm = (echo_time as f64)*17.0/100.0;
-        rprintln!("Distance: {}cm", distance_cm);
+        sensor.start_measurement(&mut bench.clock()).unwrap();
+        // La broche est repassée en entrée : le niveau lu est celui de l'écho
+        assert_eq!(sensor.echo_pin_mut().is_high(), Ok(false));
 
-        Some(distance_cm)
+        bench.advance(750);
+        assert_eq!(sensor.on_echo_edge(bench.now_us()), None);
+        bench.advance(1_000);
+        assert_close(sensor.on_echo_edge(bench.now_us()).unwrap().unwrap(), 172);
     }
 }
@@ -0,0 +1,631 @@
+//! Host-side doubles for the embedded-hal traits used by the drivers, also
+//! available to other crates through the `mock` feature.
+//!
+//! All the doubles share one simulated bench: the delay and the clock move
+//! the simulated time forward, the trigger pin records its edges and the
+//! echo pin answers according to a scripted pulse. The single pin of a
+//! 3-wire sensor does both, depending on its direction.
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    convert::Infallible,
+    rc::Rc,
+};
+#[cfg(feature = "async")]
+use std::{
+    future::Future,
+    pin::pin,
+    task::{Context, Poll, Waker},
+};
+
+use embedded_hal::{
+    delay::DelayNs,
+    digital::{ErrorKind, ErrorType, InputPin, OutputPin},
+    i2c::{self, I2c, Operation, SevenBitAddress},
+    pwm::{self, SetDutyCycle},
+};
+use embedded_hal_nb::{nb, serial};
+
+use crate::{Clock, IoPin};
+
+/// What the simulated sensor does after the trigger pulse.
+#[derive(Debug, Clone, Copy)]
+pub enum EchoScript {
+    /// The echo line goes high `delay_us` after the end of the trigger pulse
+    /// and stays high for `width_us`.
+    Pulse { delay_us: u32, width_us: u32 },
+    /// The echo line never moves, as with an unplugged sensor.
+    Silent,
+}
+
+struct State {
+    now_us: u32,
+    trigger_high: bool,
+    trigger_edges: Vec<(u32, bool)>,
+    trigger_fall_us: Option<u32>,
+    echo: EchoScript,
+    pin_fault: bool,
+    io_output: bool,
+    io_level: bool,
+}
+
+impl State {
+    fn echo_is_high(&self) -> bool {
+        match (self.echo, self.trigger_fall_us) {
+            (EchoScript::Pulse { delay_us, width_us }, Some(fall)) => {
+                let elapsed = self.now_us.wrapping_sub(fall);
+                elapsed >= delay_us && elapsed < delay_us + width_us
+            }
+            _ => false,
+        }
+    }
+
+    fn set_trigger(&mut self, high: bool) {
+        if self.trigger_high != high {
+            self.trigger_edges.push((self.now_us, high));
+            if !high {
+                self.trigger_fall_us = Some(self.now_us);
+            }
+        }
+        self.trigger_high = high;
+    }
+}
+
+/// The simulated bench the doubles are plugged into.
+#[derive(Clone)]
+pub struct Bench(Rc<RefCell<State>>);
+
+impl Bench {
+    pub fn new(echo: EchoScript) -> Self {
+        Self::starting_at(0, echo)
+    }
+
+    /// Starts the simulated time at `now_us`, to exercise clock wrap-around.
+    pub fn starting_at(now_us: u32, echo: EchoScript) -> Self {
+        Self(Rc::new(RefCell::new(State {
+            now_us,
+            trigger_high: false,
+            trigger_edges: Vec::new(),
+            trigger_fall_us: None,
+            echo,
+            pin_fault: false,
+            io_output: false,
+            io_level: false,
+        })))
+    }
+
+    pub fn trigger(&self) -> MockTrigger {
+        MockTrigger(self.clone())
+    }
+
+    pub fn echo(&self) -> MockEcho {
+        MockEcho(self.clone())
+    }
+
+    /// Signal pin of a 3-wire sensor, starting as an input.
+    pub fn io_pin(&self) -> MockIoPin {
+        MockIoPin(self.clone())
+    }
+
+    pub fn delay(&self) -> MockDelay {
+        MockDelay(self.clone())
+    }
+
+    pub fn clock(&self) -> MockClock {
+        MockClock(self.clone())
+    }
+
+    /// Changes the echo answered to the next trigger pulses.
+    pub fn script(&self, echo: EchoScript) {
+        self.0.borrow_mut().echo = echo;
+    }
+
+    /// Makes every pin operation fail from now on.
+    pub fn break_pins(&self) {
+        self.0.borrow_mut().pin_fault = true;
+    }
+
+    pub fn now_us(&self) -> u32 {
+        self.0.borrow().now_us
+    }
+
+    /// Lets `us` microseconds pass.
+    pub fn advance(&self, us: u32) {
+        let mut state = self.0.borrow_mut();
+        state.now_us = state.now_us.wrapping_add(us);
+    }
+
+    #[cfg(feature = "async")]
+    /// Runs `future` to completion, letting 1 µs pass each time it is
+    /// pending so that awaited edges and delays eventually complete.
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut context = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+                return output;
+            }
+            self.advance(1);
+        }
+    }
+
+    /// Widths in µs of every high pulse sent on the trigger pin.
+    pub fn trigger_pulses(&self) -> Vec<u32> {
+        self.0
+            .borrow()
+            .trigger_edges
+            .chunks(2)
+            .filter_map(|edges| match edges {
+                [(rise, true), (fall, false)] => Some(fall.wrapping_sub(*rise)),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+pub struct MockTrigger(Bench);
+
+impl ErrorType for MockTrigger {
+    type Error = ErrorKind;
+}
+
+impl OutputPin for MockTrigger {
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        let mut state = (self.0).0.borrow_mut();
+        if state.pin_fault {
+            return Err(ErrorKind::Other);
+        }
+        state.set_trigger(true);
+        Ok(())
+    }
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        let mut state = (self.0).0.borrow_mut();
+        if state.pin_fault {
+            return Err(ErrorKind::Other);
+        }
+        state.set_trigger(false);
+        Ok(())
+    }
+}
+
+pub struct MockEcho(Bench);
+
+impl ErrorType for MockEcho {
+    type Error = ErrorKind;
+}
+
+impl InputPin for MockEcho {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        let state = (self.0).0.borrow();
+        if state.pin_fault {
+            return Err(ErrorKind::Other);
+        }
+        Ok(state.echo_is_high())
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        self.is_high().map(|high| !high)
+    }
+}
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::digital::Wait for MockEcho {
+    async fn wait_for_high(&mut self) -> Result<(), Self::Error> {
+        std::future::poll_fn(|_| match self.is_high() {
+            Ok(false) => Poll::Pending,
+            Ok(true) => Poll::Ready(Ok(())),
+            Err(error) => Poll::Ready(Err(error)),
+        })
+        .await
+    }
+
+    async fn wait_for_low(&mut self) -> Result<(), Self::Error> {
+        std::future::poll_fn(|_| match self.is_low() {
+            Ok(false) => Poll::Pending,
+            Ok(true) => Poll::Ready(Ok(())),
+            Err(error) => Poll::Ready(Err(error)),
+        })
+        .await
+    }
+
+    async fn wait_for_rising_edge(&mut self) -> Result<(), Self::Error> {
+        self.wait_for_low().await?;
+        self.wait_for_high().await
+    }
+
+    async fn wait_for_falling_edge(&mut self) -> Result<(), Self::Error> {
+        self.wait_for_high().await?;
+        self.wait_for_low().await
+    }
+
+    async fn wait_for_any_edge(&mut self) -> Result<(), Self::Error> {
+        if self.is_high()? {
+            self.wait_for_low().await
+        } else {
+            self.wait_for_high().await
+        }
+    }
+}
+
+/// Only drives the line while switched to output; writing to it as an input
+/// fails, as with a HAL dynamic pin.
+pub struct MockIoPin(Bench);
+
+impl MockIoPin {
+    fn write(&mut self, high: bool) -> Result<(), ErrorKind> {
+        let mut state = (self.0).0.borrow_mut();
+        if state.pin_fault || !state.io_output {
+            return Err(ErrorKind::Other);
+        }
+        state.io_level = high;
+        state.set_trigger(high);
+        Ok(())
+    }
+}
+
+impl ErrorType for MockIoPin {
+    type Error = ErrorKind;
+}
+
+impl OutputPin for MockIoPin {
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.write(true)
+    }
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.write(false)
+    }
+}
+
+impl InputPin for MockIoPin {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        let state = (self.0).0.borrow();
+        if state.pin_fault {
+            return Err(ErrorKind::Other);
+        }
+        Ok(if state.io_output { state.io_level } else { state.echo_is_high() })
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        self.is_high().map(|high| !high)
+    }
+}
+
+impl IoPin for MockIoPin {
+    fn set_as_output(&mut self) -> Result<(), Self::Error> {
+        let mut state = (self.0).0.borrow_mut();
+        state.io_output = true;
+        state.io_level = false;
+        state.set_trigger(false);
+        Ok(())
+    }
+
+    fn set_as_input(&mut self) -> Result<(), Self::Error> {
+        let mut state = (self.0).0.borrow_mut();
+        state.io_output = false;
+        // Ligne relâchée : le tirage vers le bas du capteur la ramène à 0
+        state.set_trigger(false);
+        Ok(())
+    }
+}
+
+pub struct MockDelay(Bench);
+
+impl DelayNs for MockDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        self.0.advance(ns.div_ceil(1_000));
+    }
+}
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::delay::DelayNs for MockDelay {
+    async fn delay_ns(&mut self, ns: u32) {
+        let start = self.0.now_us();
+        let duration = ns.div_ceil(1_000);
+        std::future::poll_fn(|_| {
+            if self.0.now_us().wrapping_sub(start) >= duration {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+}
+
+/// Each reading moves the simulated time forward by 1 µs, so that busy
+/// loops polling the echo line make progress.
+pub struct MockClock(Bench);
+
+impl Clock for MockClock {
+    fn now_us(&mut self) -> u32 {
+        self.0.advance(1);
+        self.0.now_us()
+    }
+}
+
+#[derive(Default)]
+struct SerialState {
+    received: VecDeque<u8>,
+    sent: Vec<u8>,
+    replies: Vec<(u8, Vec<u8>)>,
+    fault: bool,
+}
+
+/// UART link to a frame-based sensor. Clones share the same line.
+#[derive(Clone, Default)]
+pub struct MockSerial(Rc<RefCell<SerialState>>);
+
+impl MockSerial {
+    /// Bytes sent by the sensor, waiting to be read.
+    pub fn receive(&self, bytes: &[u8]) {
+        self.0.borrow_mut().received.extend(bytes);
+    }
+
+    /// Makes the sensor answer `reply` each time `command` is written.
+    pub fn reply_to(&self, command: u8, reply: &[u8]) {
+        self.0.borrow_mut().replies.push((command, reply.to_vec()));
+    }
+
+    /// Bytes written to the sensor so far.
+    pub fn sent(&self) -> Vec<u8> {
+        self.0.borrow().sent.clone()
+    }
+
+    /// Makes every read fail, as on a receiver overrun.
+    pub fn break_line(&self) {
+        self.0.borrow_mut().fault = true;
+    }
+}
+
+impl serial::ErrorType for MockSerial {
+    type Error = serial::ErrorKind;
+}
+
+impl serial::Read for MockSerial {
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        let mut state = self.0.borrow_mut();
+        if state.fault {
+            return Err(nb::Error::Other(serial::ErrorKind::Overrun));
+        }
+        state.received.pop_front().ok_or(nb::Error::WouldBlock)
+    }
+}
+
+impl serial::Write for MockSerial {
+    fn write(&mut self, word: u8) -> nb::Result<(), Self::Error> {
+        let mut state = self.0.borrow_mut();
+        state.sent.push(word);
+        let reply = state.replies.iter().find(|(command, _)| *command == word).map(|(_, reply)| reply.clone());
+        if let Some(reply) = reply {
+            state.received.extend(reply);
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct I2cState {
+    writes: Vec<(SevenBitAddress, Vec<u8>)>,
+    reads: VecDeque<Vec<u8>>,
+    fault: bool,
+}
+
+/// I2C bus with one register-based sensor on it. Clones share the same bus.
+#[derive(Clone, Default)]
+pub struct MockI2c(Rc<RefCell<I2cState>>);
+
+impl MockI2c {
+    /// Queues the bytes returned by the next read.
+    pub fn answer(&self, bytes: &[u8]) {
+        self.0.borrow_mut().reads.push_back(bytes.to_vec());
+    }
+
+    /// Every write so far, with its address.
+    pub fn writes(&self) -> Vec<(SevenBitAddress, Vec<u8>)> {
+        self.0.borrow().writes.clone()
+    }
+
+    /// Makes every transfer fail, as with a missing device.
+    pub fn break_bus(&self) {
+        self.0.borrow_mut().fault = true;
+    }
+}
+
+impl i2c::ErrorType for MockI2c {
+    type Error = i2c::ErrorKind;
+}
+
+impl I2c for MockI2c {
+    fn transaction(&mut self, address: SevenBitAddress, operations: &mut [Operation<'_>]) -> Result<(), Self::Error> {
+        let mut state = self.0.borrow_mut();
+        if state.fault {
+            return Err(i2c::ErrorKind::NoAcknowledge(i2c::NoAcknowledgeSource::Address));
+        }
+        for operation in operations {
+            match operation {
+                Operation::Write(bytes) => state.writes.push((address, bytes.to_vec())),
+                Operation::Read(buffer) => {
+                    let answer = state.reads.pop_front().unwrap_or_default();
+                    for (byte, answer) in buffer.iter_mut().zip(answer.iter().chain(std::iter::repeat(&0))) {
+                        *byte = *answer;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A register write, as `(page, register, value)`.
+type RegisterWrite = (u8, u8, u8);
+
+#[derive(Default)]
+struct RegisterState {
+    /// Register values, by page and address.
+    registers: HashMap<(u8, u8), u8>,
+    page: u8,
+    writes: Vec<RegisterWrite>,
+    rules: Vec<(RegisterWrite, Vec<RegisterWrite>)>,
+    /// Values handed out by successive reads of a register, such as a
+    /// FIFO output, before it falls back to its stored value.
+    streams: HashMap<(u8, u8), VecDeque<u8>>,
+    /// Sub-address bit asking for auto-increment, if the device needs one.
+    increment_bit: Option<u8>,
+    fault: bool,
+}
+
+impl RegisterState {
+    fn read(&mut self, register: u8) -> u8 {
+        let key = (self.page, register);
+        match self.streams.get_mut(&key).and_then(VecDeque::pop_front) {
+            Some(value) => value,
+            None => self.registers.get(&key).copied().unwrap_or(0),
+        }
+    }
+
+    fn write(&mut self, register: u8, value: u8) {
+        let write = (self.page, register, value);
+        self.writes.push(write);
+        if register == MockRegisters::PAGE_SELECT {
+            self.page = value;
+        } else {
+            self.registers.insert((self.page, register), value);
+        }
+
+        let effects: Vec<_> = self.rules.iter().filter(|(trigger, _)| *trigger == write).flat_map(|(_, effects)| effects.clone()).collect();
+        for (page, register, value) in effects {
+            self.registers.insert((page, register), value);
+        }
+    }
+}
+
+/// I2C device made of 8-bit registers, as ST's sensors are: a write starts
+/// with the register address, and reads and writes then go through the
+/// following registers. Register 0xFF selects the page the others are in,
+/// as on the VL53L0X. Clones share the same device.
+#[derive(Clone, Default)]
+pub struct MockRegisters(Rc<RefCell<RegisterState>>);
+
+impl MockRegisters {
+    const PAGE_SELECT: u8 = 0xFF;
+
+    /// Device that only moves to the next register when `bit` is set in the
+    /// register address, as the HTS221 does with bit 7: without it, every
+    /// byte goes to the same register.
+    pub fn with_increment_bit(bit: u8) -> Self {
+        let device = Self::default();
+        device.0.borrow_mut().increment_bit = Some(bit);
+        device
+    }
+
+    /// Loads `values` into the registers starting at `first`, e.g. from a
+    /// register dump of a real device.
+    pub fn load(&self, page: u8, first: u8, values: &[u8]) {
+        for (register, value) in (first..).zip(values) {
+            self.set(page, register, *value);
+        }
+    }
+
+    pub fn set(&self, page: u8, register: u8, value: u8) {
+        self.0.borrow_mut().registers.insert((page, register), value);
+    }
+
+    pub fn get(&self, page: u8, register: u8) -> u8 {
+        self.0.borrow().registers.get(&(page, register)).copied().unwrap_or(0)
+    }
+
+    /// Queues values for successive reads of `register`.
+    pub fn stream(&self, page: u8, register: u8, values: &[u8]) {
+        self.0.borrow_mut().streams.entry((page, register)).or_default().extend(values);
+    }
+
+    /// Every register written so far, as `(page, register, value)`.
+    pub fn writes(&self) -> Vec<RegisterWrite> {
+        self.0.borrow().writes.clone()
+    }
+
+    /// Simulates the device reacting to a write: each time `value` is
+    /// written to `register`, the `effects` registers are updated.
+    pub fn when_written(&self, page: u8, register: u8, value: u8, effects: &[RegisterWrite]) {
+        self.0.borrow_mut().rules.push(((page, register, value), effects.to_vec()));
+    }
+
+    /// Makes every transfer fail, as with a missing device.
+    pub fn break_bus(&self) {
+        self.0.borrow_mut().fault = true;
+    }
+}
+
+impl i2c::ErrorType for MockRegisters {
+    type Error = i2c::ErrorKind;
+}
+
+impl I2c for MockRegisters {
+    fn transaction(&mut self, _address: SevenBitAddress, operations: &mut [Operation<'_>]) -> Result<(), Self::Error> {
+        let mut state = self.0.borrow_mut();
+        if state.fault {
+            return Err(i2c::ErrorKind::NoAcknowledge(i2c::NoAcknowledgeSource::Address));
+        }
+        let (mut pointer, mut step) = (0u8, 1u8);
+        for operation in operations {
+            match operation {
+                Operation::Write(bytes) => {
+                    let Some((&register, data)) = bytes.split_first() else {
+                        continue;
+                    };
+                    (pointer, step) = match state.increment_bit {
+                        Some(bit) => (register & !bit, (register & bit != 0) as u8),
+                        None => (register, 1),
+                    };
+                    for &value in data {
+                        state.write(pointer, value);
+                        pointer = pointer.wrapping_add(step);
+                    }
+                }
+                Operation::Read(buffer) => {
+                    for byte in buffer.iter_mut() {
+                        *byte = state.read(pointer);
+                        pointer = pointer.wrapping_add(step);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// PWM channel remembering its duty cycle. Clones share the same channel.
+#[derive(Clone)]
+pub struct MockPwm(Rc<RefCell<(u16, u16)>>);
+
+impl MockPwm {
+    pub fn new(max_duty: u16) -> Self {
+        Self(Rc::new(RefCell::new((max_duty, 0))))
+    }
+
+    pub fn duty(&self) -> u16 {
+        self.0.borrow().1
+    }
+}
+
+impl pwm::ErrorType for MockPwm {
+    type Error = Infallible;
+}
+
+impl SetDutyCycle for MockPwm {
+    fn max_duty_cycle(&self) -> u16 {
+        self.0.borrow().0
+    }
+
+    fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Self::Error> {
+        self.0.borrow_mut().1 = duty;
+        Ok(())
+    }
+}
@@ -0,0 +1,161 @@
+use embedded_hal::digital::{ErrorType, InputPin, OutputPin};
+
+use crate::SensorError;
+
+/// How an [`EchoSensor`](crate::EchoSensor) is wired: sends the trigger
+/// pulse and gives access to the echo line.
+pub trait EchoPins {
+    type Echo: InputPin;
+
+    /// Drives the trigger line.
+    fn set_trigger(&mut self, high: bool) -> Result<(), SensorError>;
+
+    /// Called once the trigger pulse has been sent, before the echo is
+    /// awaited.
+    fn listen(&mut self) -> Result<(), SensorError>;
+
+    fn echo(&mut self) -> &mut Self::Echo;
+}
+
+/// Separate trigger and echo pins, as on the HC-SR04.
+pub struct TwoPins<T, E> {
+    trigger: T,
+    echo: E,
+}
+
+impl<T, E> TwoPins<T, E> {
+    pub fn new(trigger: T, echo: E) -> Self {
+        Self { trigger, echo }
+    }
+
+    pub fn release(self) -> (T, E) {
+        (self.trigger, self.echo)
+    }
+}
+
+impl<T: OutputPin, E: InputPin> EchoPins for TwoPins<T, E> {
+    type Echo = E;
+
+    fn set_trigger(&mut self, high: bool) -> Result<(), SensorError> {
+        self.trigger.set_state(high.into()).map_err(|_| SensorError::PinError)
+    }
+
+    fn listen(&mut self) -> Result<(), SensorError> {
+        Ok(())
+    }
+
+    fn echo(&mut self) -> &mut E {
+        &mut self.echo
+    }
+}
+
+/// A GPIO whose direction can be switched at run time, such as a HAL's
+/// flexible (dynamic) pin.
+pub trait IoPin: OutputPin + InputPin {
+    /// Switches to output, driving the line low.
+    fn set_as_output(&mut self) -> Result<(), Self::Error>;
+
+    /// Switches to input, releasing the line.
+    fn set_as_input(&mut self) -> Result<(), Self::Error>;
+}
+
+/// An open-drain output used as an [`IoPin`]: driving it high releases the
+/// line, which can then be read. Needs a pull-up on the line, and the
+/// trigger pulse is only as steep as that pull-up allows.
+pub struct OpenDrain<P>(pub P);
+
+impl<P: ErrorType> ErrorType for OpenDrain<P> {
+    type Error = P::Error;
+}
+
+impl<P: OutputPin> OutputPin for OpenDrain<P> {
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.0.set_high()
+    }
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.0.set_low()
+    }
+}
+
+impl<P: InputPin> InputPin for OpenDrain<P> {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        self.0.is_high()
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        self.0.is_low()
+    }
+}
+
+impl<P: OutputPin + InputPin> IoPin for OpenDrain<P> {
+    fn set_as_output(&mut self) -> Result<(), Self::Error> {
+        self.0.set_low()
+    }
+
+    fn set_as_input(&mut self) -> Result<(), Self::Error> {
+        self.0.set_high()
+    }
+}
+
+#[cfg(feature = "async")]
+impl<P: embedded_hal_async::digital::Wait> embedded_hal_async::digital::Wait for OpenDrain<P> {
+    async fn wait_for_high(&mut self) -> Result<(), Self::Error> {
+        self.0.wait_for_high().await
+    }
+
+    async fn wait_for_low(&mut self) -> Result<(), Self::Error> {
+        self.0.wait_for_low().await
+    }
+
+    async fn wait_for_rising_edge(&mut self) -> Result<(), Self::Error> {
+        self.0.wait_for_rising_edge().await
+    }
+
+    async fn wait_for_falling_edge(&mut self) -> Result<(), Self::Error> {
+        self.0.wait_for_falling_edge().await
+    }
+
+    async fn wait_for_any_edge(&mut self) -> Result<(), Self::Error> {
+        self.0.wait_for_any_edge().await
+    }
+}
+
+/// One line used for both the trigger and the echo, as on the 3-wire
+/// Parallax PING))): the pin is switched to output for the trigger pulse,
+/// then back to input to time the echo.
+pub struct SinglePin<P> {
+    pin: P,
+    output: bool,
+}
+
+impl<P> SinglePin<P> {
+    pub fn new(pin: P) -> Self {
+        Self { pin, output: false }
+    }
+
+    pub fn release(self) -> P {
+        self.pin
+    }
+}
+
+impl<P: IoPin> EchoPins for SinglePin<P> {
+    type Echo = P;
+
+    fn set_trigger(&mut self, high: bool) -> Result<(), SensorError> {
+        if !self.output {
+            self.pin.set_as_output().map_err(|_| SensorError::PinError)?;
+            self.output = true;
+        }
+        self.pin.set_state(high.into()).map_err(|_| SensorError::PinError)
+    }
+
+    fn listen(&mut self) -> Result<(), SensorError> {
+        self.output = false;
+        self.pin.set_as_input().map_err(|_| SensorError::PinError)
+    }
+
+    fn echo(&mut self) -> &mut P {
+        &mut self.pin
+    }
+}
@@ -0,0 +1,95 @@
+use crate::Distance;
+
+/// Latest distance measured in each direction around the radar, on `BINS`
+/// sectors of equal width, the first one centred on 0°.
+///
+/// Fed with the heading of each reading while the sensor turns, it builds
+/// an obstacle map around the device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolarMap<const BINS: usize> {
+    bins: [Option<Distance>; BINS],
+}
+
+impl<const BINS: usize> PolarMap<BINS> {
+    pub const fn new() -> Self {
+        assert!(BINS > 0, "a polar map needs at least one bin");
+        Self { bins: [None; BINS] }
+    }
+
+    pub fn bin_width_deg(&self) -> f32 {
+        360.0 / BINS as f32
+    }
+
+    /// Sector the direction `angle_deg` falls in, any angle being accepted.
+    pub fn bin_of(&self, angle_deg: f32) -> usize {
+        let bin = libm::roundf(angle_deg / self.bin_width_deg()) as i64;
+        bin.rem_euclid(BINS as i64) as usize
+    }
+
+    /// Direction at the centre of `bin`.
+    pub fn angle_of(&self, bin: usize) -> f32 {
+        bin as f32 * self.bin_width_deg()
+    }
+
+    /// Records the reading taken towards `angle_deg`, `None` meaning that
+    /// nothing was in range.
+    pub fn update(&mut self, angle_deg: f32, distance: Option<Distance>) {
+        let bin = self.bin_of(angle_deg);
+        self.bins[bin] = distance;
+    }
+
+    pub fn get(&self, bin: usize) -> Option<Distance> {
+        self.bins.get(bin).copied().flatten()
+    }
+
+    /// Every sector, with the direction at its centre.
+    pub fn iter(&self) -> impl Iterator<Item = (f32, Option<Distance>)> + '_ {
+        self.bins.iter().enumerate().map(|(bin, distance)| (self.angle_of(bin), *distance))
+    }
+
+    /// Closest obstacle, with its direction.
+    pub fn nearest(&self) -> Option<(f32, Distance)> {
+        self.iter()
+            .filter_map(|(angle, distance)| Some((angle, distance?)))
+            .min_by_key(|(_, distance)| *distance)
+    }
+
+    pub fn clear(&mut self) {
+        self.bins = [None; BINS];
+    }
+}
+
+impl<const BINS: usize> Default for PolarMap<BINS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sorts_readings_by_direction() {
+        let mut map = PolarMap::<36>::new();
+        assert_eq!(map.bin_of(4.9), 0);
+        assert_eq!(map.bin_of(355.1), 0);
+        assert_eq!(map.bin_of(-90.0), 27);
+        assert_eq!(map.bin_of(725.0), 1);
+        assert_eq!(map.angle_of(27), 270.0);
+
+        map.update(91.0, Some(Distance::from_cm(120)));
+        map.update(182.0, Some(Distance::from_cm(45)));
+        map.update(359.0, Some(Distance::from_cm(300)));
+        assert_eq!(map.get(9), Some(Distance::from_cm(120)));
+        assert_eq!(map.nearest(), Some((180.0, Distance::from_cm(45))));
+
+        // La cible à 180° est partie
+        map.update(178.0, None);
+        assert_eq!(map.nearest(), Some((90.0, Distance::from_cm(120))));
+        assert_eq!(map.iter().filter(|(_, distance)| distance.is_some()).count(), 2);
+
+        map.clear();
+        assert_eq!(map.nearest(), None);
+    }
+}
@@ -0,0 +1,107 @@
+use crate::Distance;
+
+/// Timing and range limits of an ultrasonic module.
+///
+/// Readings closer than the blind zone are reported as
+/// [`SensorError::TooClose`](crate::SensorError::TooClose), further than the
+/// maximum range as [`SensorError::OutOfRange`](crate::SensorError::OutOfRange),
+/// and triggers closer together than the minimum cycle time are delayed (or
+/// refused in non-blocking mode) so that the previous burst has died out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SensorProfile {
+    /// Closest distance the module can measure: the transducer is still
+    /// ringing from the burst before that.
+    pub blind_zone: Distance,
+    pub max_range: Distance,
+    /// Width of the trigger pulse.
+    pub trigger_pulse_us: u32,
+    /// Minimum time between two triggers.
+    pub min_cycle_us: u32,
+}
+
+impl SensorProfile {
+    /// HC-SR04, the usual 2 cm - 4 m module.
+    pub const HC_SR04: Self = Self {
+        blind_zone: Distance::from_cm(2),
+        max_range: Distance::from_cm(400),
+        trigger_pulse_us: 10,
+        min_cycle_us: 60_000,
+    };
+
+    /// HY-SRF05, an HC-SR04 with a slightly longer range.
+    pub const HY_SRF05: Self = Self {
+        blind_zone: Distance::from_cm(2),
+        max_range: Distance::from_cm(450),
+        trigger_pulse_us: 10,
+        min_cycle_us: 50_000,
+    };
+
+    /// US-100 with its jumper removed (HC-SR04 compatible pulse mode).
+    pub const US_100: Self = Self {
+        blind_zone: Distance::from_cm(2),
+        max_range: Distance::from_cm(450),
+        trigger_pulse_us: 10,
+        min_cycle_us: 60_000,
+    };
+
+    /// Parallax PING))), 3-wire: its echo starts ~750 µs after the trigger
+    /// and the next measurement may follow 200 µs after the echo, i.e.
+    /// ~20 ms at full range.
+    pub const PING: Self = Self {
+        blind_zone: Distance::from_cm(2),
+        max_range: Distance::from_cm(300),
+        trigger_pulse_us: 5,
+        min_cycle_us: 20_000,
+    };
+
+    /// JSN-SR04T waterproof module. Its single transducer rings much longer,
+    /// hence the 20 cm blind zone, and recent boards miss 10 µs triggers.
+    pub const JSN_SR04T: Self = Self {
+        blind_zone: Distance::from_cm(20),
+        max_range: Distance::from_cm(600),
+        trigger_pulse_us: 20,
+        min_cycle_us: 100_000,
+    };
+
+    /// Whether `distance` is within the window the module can measure.
+    pub fn contains(&self, distance: Distance) -> bool {
+        (self.blind_zone..=self.max_range).contains(&distance)
+    }
+
+    /// Longest echo worth waiting for at `speed_mm_s`: the round trip to the
+    /// maximum range plus 1 ms of margin, so that an obstacle just beyond
+    /// the range is reported as out of range rather than as no echo.
+    pub const fn max_echo_us(&self, speed_mm_s: u32) -> u32 {
+        (self.max_range.as_mm() as u64 * 2_000_000 / speed_mm_s as u64) as u32 + 1_000
+    }
+}
+
+impl Default for SensorProfile {
+    fn default() -> Self {
+        Self::HC_SR04
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_window_excludes_the_blind_zone() {
+        let profile = SensorProfile::JSN_SR04T;
+
+        assert!(!profile.contains(Distance::from_cm(15)));
+        assert!(profile.contains(Distance::from_cm(20)));
+        assert!(profile.contains(Distance::from_cm(600)));
+        assert!(!profile.contains(Distance::from_mm(6_001)));
+        assert!(SensorProfile::HC_SR04.contains(Distance::from_cm(15)));
+    }
+
+    #[test]
+    fn echo_timeout_follows_range_and_speed() {
+        assert_eq!(SensorProfile::HC_SR04.max_echo_us(343_420), 24_295);
+        assert_eq!(SensorProfile::JSN_SR04T.max_echo_us(343_420), 35_942);
+        // L'air froid ralentit le son, il faut attendre plus longtemps
+        assert!(SensorProfile::HC_SR04.max_echo_us(319_180) > 25_000);
+    }
+}
@@ -0,0 +1,213 @@
+use embedded_hal::pwm::SetDutyCycle;
+
+use crate::Distance;
+
+/// Hobby servo driven by a PWM channel, positioned by its pulse width.
+pub struct Servo<P> {
+    pwm: P,
+    period_us: u16,
+    min_pulse_us: u16,
+    max_pulse_us: u16,
+    range_deg: u16,
+}
+
+impl<P> Servo<P> {
+    /// `pwm` must run at 50 Hz. 500 to 2 500 µs over 180°, as for an SG90.
+    pub fn new(pwm: P) -> Self {
+        Self {
+            pwm,
+            period_us: 20_000,
+            min_pulse_us: 500,
+            max_pulse_us: 2_500,
+            range_deg: 180,
+        }
+    }
+
+    /// Pulse widths at both ends of the servo's travel, `range_deg` apart.
+    pub fn with_pulses(mut self, min_pulse_us: u16, max_pulse_us: u16, range_deg: u16) -> Self {
+        assert!(min_pulse_us < max_pulse_us && max_pulse_us < self.period_us, "invalid servo pulse widths");
+        self.min_pulse_us = min_pulse_us;
+        self.max_pulse_us = max_pulse_us;
+        self.range_deg = range_deg;
+        self
+    }
+
+    /// PWM period, 20 ms by default.
+    pub fn with_period(mut self, period_us: u16) -> Self {
+        self.period_us = period_us;
+        self
+    }
+
+    pub fn release(self) -> P {
+        self.pwm
+    }
+
+    /// Pulse width for `angle_deg` from the centre, clamped to the travel.
+    pub fn pulse_us(&self, angle_deg: i16) -> u16 {
+        let half_range = self.range_deg as i32 / 2;
+        let angle = (angle_deg as i32).clamp(-half_range, half_range);
+        let span = (self.max_pulse_us - self.min_pulse_us) as i32;
+        (self.min_pulse_us as i32 + (angle + half_range) * span / self.range_deg as i32) as u16
+    }
+}
+
+impl<P: SetDutyCycle> Servo<P> {
+    /// Turns to `angle_deg` from the centre, negative to the left.
+    pub fn set_angle(&mut self, angle_deg: i16) -> Result<(), P::Error> {
+        self.pwm.set_duty_cycle_fraction(self.pulse_us(angle_deg), self.period_us)
+    }
+}
+
+/// One pass of a sweep: the distance measured at each step, `None` where
+/// nothing was in range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SweepFrame<const STEPS: usize> {
+    /// Increases by one with each pass.
+    pub sweep: u16,
+    pub first_deg: i16,
+    pub step_deg: i16,
+    pub distances: [Option<Distance>; STEPS],
+}
+
+impl<const STEPS: usize> SweepFrame<STEPS> {
+    pub fn angle_deg(&self, step: usize) -> i16 {
+        self.first_deg + step as i16 * self.step_deg
+    }
+
+    /// Every step, with its angle.
+    pub fn iter(&self) -> impl Iterator<Item = (i16, Option<Distance>)> + '_ {
+        self.distances.iter().enumerate().map(|(step, distance)| (self.angle_deg(step), *distance))
+    }
+
+    /// Closest obstacle, with its angle.
+    pub fn nearest(&self) -> Option<(i16, Distance)> {
+        self.iter()
+            .filter_map(|(angle, distance)| Some((angle, distance?)))
+            .min_by_key(|(_, distance)| *distance)
+    }
+}
+
+/// Back-and-forth sweep over `STEPS` angles, keeping the latest distance
+/// measured at each one.
+///
+/// Point the sensor at [`Self::angle_deg`], measure, then give the reading
+/// to [`Self::record`], which moves on to the next angle: the sensor must
+/// have turned before the next measurement. Each pass hands out the whole
+/// table as a [`SweepFrame`].
+pub struct Sweep<const STEPS: usize> {
+    frame: SweepFrame<STEPS>,
+    step: usize,
+    forward: bool,
+}
+
+impl<const STEPS: usize> Sweep<STEPS> {
+    /// Steps of `step_deg` from `first_deg`.
+    pub const fn new(first_deg: i16, step_deg: i16) -> Self {
+        assert!(STEPS >= 2, "a sweep needs at least 2 steps");
+        Self {
+            frame: SweepFrame {
+                sweep: 0,
+                first_deg,
+                step_deg,
+                distances: [None; STEPS],
+            },
+            step: 0,
+            forward: true,
+        }
+    }
+
+    /// `STEPS` angles spread evenly from `first_deg` to `last_deg`.
+    pub const fn between(first_deg: i16, last_deg: i16) -> Self {
+        Self::new(first_deg, (last_deg - first_deg) / (STEPS as i16 - 1))
+    }
+
+    /// Angle the next measurement must be taken at.
+    pub fn angle_deg(&self) -> i16 {
+        self.frame.angle_deg(self.step)
+    }
+
+    /// The table so far, the current pass included.
+    pub fn frame(&self) -> &SweepFrame<STEPS> {
+        &self.frame
+    }
+
+    /// Records the measurement taken at [`Self::angle_deg`], `None` if
+    /// nothing was in range, and moves on. Returns the table at the end of
+    /// each pass.
+    pub fn record(&mut self, distance: Option<Distance>) -> Option<SweepFrame<STEPS>> {
+        self.frame.distances[self.step] = distance;
+
+        // Demi-tour aux extrémités, sans remesurer l'angle extrême
+        let end = if self.forward { STEPS - 1 } else { 0 };
+        if self.step != end {
+            self.step = if self.forward { self.step + 1 } else { self.step - 1 };
+            return None;
+        }
+        self.forward = !self.forward;
+        self.step = if self.forward { 1 } else { STEPS - 2 };
+        let frame = self.frame;
+        self.frame.sweep = self.frame.sweep.wrapping_add(1);
+        Some(frame)
+    }
+
+    /// Forgets the table and starts again from the first angle.
+    pub fn restart(&mut self) {
+        self.frame.distances = [None; STEPS];
+        self.step = 0;
+        self.forward = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockPwm;
+
+    #[test]
+    fn positions_the_servo() {
+        let pwm = MockPwm::new(20_000);
+        let mut servo = Servo::new(pwm.clone());
+
+        servo.set_angle(0).unwrap();
+        assert_eq!(pwm.duty(), 1_500);
+        servo.set_angle(-90).unwrap();
+        assert_eq!(pwm.duty(), 500);
+        servo.set_angle(45).unwrap();
+        assert_eq!(pwm.duty(), 2_000);
+        // Butée
+        servo.set_angle(120).unwrap();
+        assert_eq!(pwm.duty(), 2_500);
+
+        let servo = Servo::new(pwm).with_pulses(1_000, 2_000, 90);
+        assert_eq!(servo.pulse_us(-45), 1_000);
+        assert_eq!(servo.pulse_us(9), 1_600);
+    }
+
+    #[test]
+    fn sweeps_back_and_forth() {
+        let mut sweep = Sweep::<5>::between(-60, 60);
+        let mut angles = Vec::new();
+        let mut frames = Vec::new();
+        for reading in 0..12 {
+            angles.push(sweep.angle_deg());
+            let distance = (reading != 2).then(|| Distance::from_cm(100 + reading));
+            frames.extend(sweep.record(distance));
+        }
+
+        assert_eq!(angles, [-60, -30, 0, 30, 60, 30, 0, -30, -60, -30, 0, 30]);
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].sweep, 0);
+        assert_eq!(frames[0].distances[2], None);
+        assert_eq!(frames[0].nearest(), Some((-60, Distance::from_cm(100))));
+        // Le retour met à jour tout le tableau, sauf l'extrémité de départ
+        assert_eq!(frames[1].sweep, 1);
+        assert_eq!(frames[1].distances[0], Some(Distance::from_cm(108)));
+        assert_eq!(frames[1].distances[4], Some(Distance::from_cm(104)));
+        assert_eq!(frames[1].iter().nth(2), Some((0, Some(Distance::from_cm(106)))));
+        assert_eq!(sweep.frame().distances[1], Some(Distance::from_cm(109)));
+
+        sweep.restart();
+        assert_eq!(sweep.angle_deg(), -60);
+        assert_eq!(sweep.frame().nearest(), None);
+    }
+}
@@ -0,0 +1,709 @@
+use embedded_hal::i2c::I2c;
+
+use crate::{Clock, Distance, DistanceSensor, SensorError};
+
+// Registres du VL53L0X (noms de l'API ST)
+const SYSRANGE_START: u8 = 0x00;
+const SYSTEM_SEQUENCE_CONFIG: u8 = 0x01;
+const SYSTEM_INTERMEASUREMENT_PERIOD: u8 = 0x04;
+const SYSTEM_INTERRUPT_CONFIG_GPIO: u8 = 0x0A;
+const SYSTEM_INTERRUPT_CLEAR: u8 = 0x0B;
+const RESULT_INTERRUPT_STATUS: u8 = 0x13;
+const RESULT_RANGE_STATUS: u8 = 0x14;
+const FINAL_RANGE_CONFIG_MIN_COUNT_RATE_RTN_LIMIT: u8 = 0x44;
+const MSRC_CONFIG_TIMEOUT_MACROP: u8 = 0x46;
+const DYNAMIC_SPAD_NUM_REQUESTED_REF_SPAD: u8 = 0x4E;
+const DYNAMIC_SPAD_REF_EN_START_OFFSET: u8 = 0x4F;
+const PRE_RANGE_CONFIG_VCSEL_PERIOD: u8 = 0x50;
+const PRE_RANGE_CONFIG_TIMEOUT_MACROP_HI: u8 = 0x51;
+const MSRC_CONFIG_CONTROL: u8 = 0x60;
+const FINAL_RANGE_CONFIG_VCSEL_PERIOD: u8 = 0x70;
+const FINAL_RANGE_CONFIG_TIMEOUT_MACROP_HI: u8 = 0x71;
+const GPIO_HV_MUX_ACTIVE_HIGH: u8 = 0x84;
+const VHV_CONFIG_PAD_SCL_SDA_EXTSUP_HV: u8 = 0x89;
+const I2C_SLAVE_DEVICE_ADDRESS: u8 = 0x8A;
+const GLOBAL_CONFIG_SPAD_ENABLES_REF_0: u8 = 0xB0;
+const GLOBAL_CONFIG_REF_EN_START_SELECT: u8 = 0xB6;
+const IDENTIFICATION_MODEL_ID: u8 = 0xC0;
+const OSC_CALIBRATE_VAL: u8 = 0xF8;
+const PAGE_SELECT: u8 = 0xFF;
+
+const MODEL_ID: u8 = 0xEE;
+/// Final range signal rate limit: 0.25 MCPS in 9.7 fixed point.
+const SIGNAL_RATE_LIMIT: u16 = 32;
+
+// Surcoûts des étapes de la séquence de mesure, en µs (API ST)
+const START_OVERHEAD_US: u32 = 1_910;
+const END_OVERHEAD_US: u32 = 960;
+const MSRC_OVERHEAD_US: u32 = 660;
+const TCC_OVERHEAD_US: u32 = 590;
+const DSS_OVERHEAD_US: u32 = 690;
+const PRE_RANGE_OVERHEAD_US: u32 = 660;
+const FINAL_RANGE_OVERHEAD_US: u32 = 550;
+
+/// Device range status of a valid measurement.
+const RANGE_VALID: u8 = 11;
+/// Device range statuses of a measurement without target: signal too weak,
+/// phase out of bounds.
+const RANGE_NO_TARGET: [u8; 3] = [4, 6, 9];
+
+/// Register writes of ST's default tuning settings (`vl53l0x_tuning.h`).
+const TUNING_SETTINGS: &[(u8, u8)] = &[
+    (0xFF, 0x01), (0x00, 0x00),
+    (0xFF, 0x00), (0x09, 0x00), (0x10, 0x00), (0x11, 0x00), (0x24, 0x01), (0x25, 0xFF), (0x75, 0x00),
+    (0xFF, 0x01), (0x4E, 0x2C), (0x48, 0x00), (0x30, 0x20),
+    (0xFF, 0x00), (0x30, 0x09), (0x54, 0x00), (0x31, 0x04), (0x32, 0x03), (0x40, 0x83), (0x46, 0x25),
+    (0x60, 0x00), (0x27, 0x00), (0x50, 0x06), (0x51, 0x00), (0x52, 0x96), (0x56, 0x08), (0x57, 0x30),
+    (0x61, 0x00), (0x62, 0x00), (0x64, 0x00), (0x65, 0x00), (0x66, 0xA0),
+    (0xFF, 0x01), (0x22, 0x32), (0x47, 0x14), (0x49, 0xFF), (0x4A, 0x00),
+    (0xFF, 0x00), (0x7A, 0x0A), (0x7B, 0x00), (0x78, 0x21),
+    (0xFF, 0x01), (0x23, 0x34), (0x42, 0x00), (0x44, 0xFF), (0x45, 0x26), (0x46, 0x05), (0x40, 0x40),
+    (0x0E, 0x06), (0x20, 0x1A), (0x43, 0x40),
+    (0xFF, 0x00), (0x34, 0x03), (0x35, 0x44),
+    (0xFF, 0x01), (0x31, 0x04), (0x4B, 0x09), (0x4C, 0x05), (0x4D, 0x04),
+    (0xFF, 0x00), (0x44, 0x00), (0x45, 0x20), (0x47, 0x08), (0x48, 0x28), (0x67, 0x00), (0x70, 0x04),
+    (0x71, 0x01), (0x72, 0xFE), (0x76, 0x00), (0x77, 0x00),
+    (0xFF, 0x01), (0x0D, 0x01),
+    (0xFF, 0x00), (0x80, 0x01), (0x01, 0xF8),
+    (0xFF, 0x01), (0x8E, 0x01), (0x00, 0x01), (0xFF, 0x00), (0x80, 0x00),
+];
+
+/// Steps of the ranging sequence enabled in `SYSTEM_SEQUENCE_CONFIG`.
+#[derive(Debug, Clone, Copy)]
+struct SequenceSteps {
+    tcc: bool,
+    dss: bool,
+    msrc: bool,
+    pre_range: bool,
+    final_range: bool,
+}
+
+impl SequenceSteps {
+    fn from_config(config: u8) -> Self {
+        Self {
+            tcc: config & 0x10 != 0,
+            dss: config & 0x08 != 0,
+            msrc: config & 0x04 != 0,
+            pre_range: config & 0x40 != 0,
+            final_range: config & 0x80 != 0,
+        }
+    }
+}
+
+/// Timeouts of the sequence steps, in macro periods (MCLKs) and µs.
+#[derive(Debug, Clone, Copy)]
+struct SequenceTimeouts {
+    final_range_vcsel_period: u8,
+    msrc_dss_tcc_us: u32,
+    pre_range_mclks: u32,
+    pre_range_us: u32,
+    final_range_us: u32,
+}
+
+/// Macro period in ns for a VCSEL period given in PCLKs.
+fn macro_period_ns(vcsel_period: u8) -> u32 {
+    (2_304 * vcsel_period as u32 * 1_655 + 500) / 1_000
+}
+
+// Les timeouts relus du capteur ne sont pas bornés : calculs sur 64 bits,
+// résultats saturés
+fn mclks_to_us(mclks: u32, vcsel_period: u8) -> u32 {
+    let us = (mclks as u64 * macro_period_ns(vcsel_period) as u64 + 500) / 1_000;
+    us.min(u32::MAX as u64) as u32
+}
+
+fn us_to_mclks(us: u32, vcsel_period: u8) -> u32 {
+    let period_ns = macro_period_ns(vcsel_period) as u64;
+    ((us as u64 * 1_000 + period_ns / 2) / period_ns).min(u32::MAX as u64) as u32
+}
+
+/// Decodes a timeout register, stored as `(lsb << msb) + 1` MCLKs.
+fn decode_timeout(register: u16) -> u32 {
+    let (lsb, msb) = ((register & 0xFF) as u32, (register >> 8) as u32);
+    match lsb.checked_shl(msb) {
+        _ if lsb == 0 => 1,
+        Some(mclks) if mclks >> msb == lsb => mclks + 1,
+        _ => u32::MAX,
+    }
+}
+
+fn encode_timeout(mclks: u32) -> u16 {
+    if mclks == 0 {
+        return 0;
+    }
+    let mut lsb = mclks - 1;
+    let mut msb = 0;
+    while lsb > 0xFF {
+        lsb >>= 1;
+        msb += 1;
+    }
+    (msb << 8) | lsb as u16
+}
+
+/// VCSEL period in PCLKs, stored as `period / 2 - 1`.
+fn decode_vcsel_period(register: u8) -> u8 {
+    register.saturating_add(1).saturating_mul(2)
+}
+
+/// ST VL53L0X time-of-flight sensor, ranging up to ~1.2 m with a 25° cone.
+///
+/// The driver follows the register sequence of ST's API: [`Self::init`]
+/// must be called once after power-up, then distances are measured either
+/// one at a time through [`DistanceSensor::measure_distance`], or
+/// continuously with [`Self::start_continuous`] and polled with
+/// [`Self::read_range`].
+pub struct Vl53l0x<I> {
+    i2c: I,
+    address: u8,
+    stop_variable: u8,
+    timing_budget_us: u32,
+}
+
+impl<I> Vl53l0x<I> {
+    pub const DEFAULT_ADDRESS: u8 = 0x29;
+    /// Timing budget set by [`Self::init`], enough for ~1.2 m in the dark.
+    pub const DEFAULT_TIMING_BUDGET_US: u32 = 33_000;
+    const MIN_TIMING_BUDGET_US: u32 = 20_000;
+    /// Longest wait for the device to finish an operation.
+    const TIMEOUT_US: u32 = 500_000;
+    /// Readings above this are the device's "no target" values.
+    const MAX_MM: u32 = 2_000;
+
+    pub fn new(i2c: I) -> Self {
+        Self {
+            i2c,
+            address: Self::DEFAULT_ADDRESS,
+            stop_variable: 0,
+            timing_budget_us: Self::DEFAULT_TIMING_BUDGET_US,
+        }
+    }
+
+    pub fn with_address(mut self, address: u8) -> Self {
+        self.address = address;
+        self
+    }
+
+    pub fn release(self) -> I {
+        self.i2c
+    }
+
+    /// Time a measurement takes, as set with
+    /// [`Self::set_measurement_timing_budget`].
+    pub fn measurement_timing_budget_us(&self) -> u32 {
+        self.timing_budget_us
+    }
+}
+
+impl<I: I2c> Vl53l0x<I> {
+    /// Checks the device's identity, then loads ST's default settings and
+    /// runs the reference calibrations. The I/Os are set to 2.8 V, as on
+    /// the breakout boards and the B-L475E-IOT01A.
+    pub fn init<C: Clock>(&mut self, clock: &mut C) -> Result<(), SensorError> {
+        if self.read(IDENTIFICATION_MODEL_ID)? != MODEL_ID {
+            return Err(SensorError::WrongDevice);
+        }
+
+        // DataInit
+        let pad = self.read(VHV_CONFIG_PAD_SCL_SDA_EXTSUP_HV)?;
+        self.write(VHV_CONFIG_PAD_SCL_SDA_EXTSUP_HV, pad | 0x01)?;
+        // I2C en mode standard
+        self.write(0x88, 0x00)?;
+
+        self.write_all(&[(0x80, 0x01), (PAGE_SELECT, 0x01), (0x00, 0x00)])?;
+        self.stop_variable = self.read(0x91)?;
+        self.write_all(&[(0x00, 0x01), (PAGE_SELECT, 0x00), (0x80, 0x00)])?;
+
+        // Désactiver les contrôles de signal MSRC et pré-range
+        let control = self.read(MSRC_CONFIG_CONTROL)?;
+        self.write(MSRC_CONFIG_CONTROL, control | 0x12)?;
+        self.write_u16(FINAL_RANGE_CONFIG_MIN_COUNT_RATE_RTN_LIMIT, SIGNAL_RATE_LIMIT)?;
+        self.write(SYSTEM_SEQUENCE_CONFIG, 0xFF)?;
+
+        // StaticInit
+        let (spad_count, spad_is_aperture) = self.spad_info(clock)?;
+        self.set_reference_spads(spad_count, spad_is_aperture)?;
+        self.write_all(TUNING_SETTINGS)?;
+
+        // Interruption « nouvelle mesure », GPIO1 actif à l'état bas
+        self.write(SYSTEM_INTERRUPT_CONFIG_GPIO, 0x04)?;
+        let mux = self.read(GPIO_HV_MUX_ACTIVE_HIGH)?;
+        self.write(GPIO_HV_MUX_ACTIVE_HIGH, mux & !0x10)?;
+        self.write(SYSTEM_INTERRUPT_CLEAR, 0x01)?;
+
+        let budget_us = self.read_timing_budget()?;
+        // Sans MSRC ni TCC par défaut, puis recalculer le budget
+        self.write(SYSTEM_SEQUENCE_CONFIG, 0xE8)?;
+        self.set_measurement_timing_budget(budget_us)?;
+
+        // Calibrations de référence (VHV puis phase)
+        self.write(SYSTEM_SEQUENCE_CONFIG, 0x01)?;
+        self.single_ref_calibration(clock, 0x40)?;
+        self.write(SYSTEM_SEQUENCE_CONFIG, 0x02)?;
+        self.single_ref_calibration(clock, 0x00)?;
+        self.write(SYSTEM_SEQUENCE_CONFIG, 0xE8)
+    }
+
+    /// Changes the device's I2C address until its next power-up, e.g. to
+    /// put several sensors on one bus by bringing them out of shutdown one
+    /// at a time.
+    ///
+    /// Fails with [`SensorError::InvalidConfig`] if `address` does not fit
+    /// in 7 bits.
+    pub fn set_address(&mut self, address: u8) -> Result<(), SensorError> {
+        if address > 0x7F {
+            return Err(SensorError::InvalidConfig);
+        }
+        self.write(I2C_SLAVE_DEVICE_ADDRESS, address)?;
+        self.address = address;
+        Ok(())
+    }
+
+    /// Sets the time allowed for one measurement: longer budgets give more
+    /// accurate and longer-range measurements.
+    ///
+    /// Fails with [`SensorError::InvalidConfig`] if `budget_us` is shorter
+    /// than 20 ms, or than the enabled sequence steps take.
+    pub fn set_measurement_timing_budget(&mut self, budget_us: u32) -> Result<(), SensorError> {
+        if budget_us < Self::MIN_TIMING_BUDGET_US {
+            return Err(SensorError::InvalidConfig);
+        }
+
+        let steps = SequenceSteps::from_config(self.read(SYSTEM_SEQUENCE_CONFIG)?);
+        let timeouts = self.sequence_timeouts(steps)?;
+
+        let mut used_us = START_OVERHEAD_US + END_OVERHEAD_US;
+        if steps.tcc {
+            used_us += timeouts.msrc_dss_tcc_us + TCC_OVERHEAD_US;
+        }
+        if steps.dss {
+            used_us += 2 * (timeouts.msrc_dss_tcc_us + DSS_OVERHEAD_US);
+        } else if steps.msrc {
+            used_us += timeouts.msrc_dss_tcc_us + MSRC_OVERHEAD_US;
+        }
+        if steps.pre_range {
+            used_us = used_us.saturating_add(timeouts.pre_range_us.saturating_add(PRE_RANGE_OVERHEAD_US));
+        }
+
+        if steps.final_range {
+            used_us = used_us.saturating_add(FINAL_RANGE_OVERHEAD_US);
+            if used_us > budget_us {
+                return Err(SensorError::InvalidConfig);
+            }
+
+            let mut final_range_mclks = us_to_mclks(budget_us - used_us, timeouts.final_range_vcsel_period);
+            if steps.pre_range {
+                final_range_mclks = final_range_mclks.saturating_add(timeouts.pre_range_mclks);
+            }
+            self.write_u16(FINAL_RANGE_CONFIG_TIMEOUT_MACROP_HI, encode_timeout(final_range_mclks))?;
+            self.timing_budget_us = budget_us;
+        }
+        Ok(())
+    }
+
+    /// Starts ranging continuously: back-to-back when `period_ms` is 0,
+    /// otherwise one measurement every `period_ms`. Results are read with
+    /// [`Self::read_range`].
+    ///
+    /// Fails with [`SensorError::InvalidConfig`] if `period_ms` is too long
+    /// for the device's period register.
+    pub fn start_continuous(&mut self, period_ms: u32) -> Result<(), SensorError> {
+        self.restore_stop_variable()?;
+
+        if period_ms == 0 {
+            return self.write(SYSRANGE_START, 0x02);
+        }
+
+        // La période est comptée en cycles de l'oscillateur interne
+        let calibration = self.read_u16(OSC_CALIBRATE_VAL)?;
+        let period = match calibration {
+            0 => period_ms,
+            calibration => period_ms.checked_mul(calibration as u32).ok_or(SensorError::InvalidConfig)?,
+        };
+        self.write_u32(SYSTEM_INTERMEASUREMENT_PERIOD, period)?;
+        self.write(SYSRANGE_START, 0x04)
+    }
+
+    pub fn stop_continuous(&mut self) -> Result<(), SensorError> {
+        self.write(SYSRANGE_START, 0x01)?;
+        self.write_all(&[(PAGE_SELECT, 0x01), (0x00, 0x00), (0x91, 0x00), (0x00, 0x01), (PAGE_SELECT, 0x00)])
+    }
+
+    /// Returns the result of the last measurement once it is available,
+    /// without waiting, e.g. from a periodic task or the GPIO1 interrupt.
+    pub fn read_range(&mut self) -> Option<Result<Distance, SensorError>> {
+        match self.read(RESULT_INTERRUPT_STATUS) {
+            Ok(status) if status & 0x07 == 0 => None,
+            Ok(_) => Some(self.read_result()),
+            Err(error) => Some(Err(error)),
+        }
+    }
+
+    fn read_result(&mut self) -> Result<Distance, SensorError> {
+        let mut result = [0; 12];
+        self.read_into(RESULT_RANGE_STATUS, &mut result)?;
+        self.write(SYSTEM_INTERRUPT_CLEAR, 0x01)?;
+
+        let distance = Distance::from_mm(u16::from_be_bytes([result[10], result[11]]) as u32);
+        match (result[0] >> 3) & 0x0F {
+            RANGE_VALID if distance.as_mm() <= Self::MAX_MM => Ok(distance),
+            RANGE_VALID => Err(SensorError::OutOfRange(distance)),
+            status if RANGE_NO_TARGET.contains(&status) => Err(SensorError::OutOfRange(distance)),
+            // Défaut matériel, cible sous la portée minimale... : la distance
+            // ne dit rien
+            _ => Err(SensorError::InvalidReading),
+        }
+    }
+
+    /// Reads the SPAD count and type from the device's NVM.
+    fn spad_info<C: Clock>(&mut self, clock: &mut C) -> Result<(u8, bool), SensorError> {
+        self.write_all(&[(0x80, 0x01), (PAGE_SELECT, 0x01), (0x00, 0x00), (PAGE_SELECT, 0x06)])?;
+        let value = self.read(0x83)?;
+        self.write(0x83, value | 0x04)?;
+        self.write_all(&[(PAGE_SELECT, 0x07), (0x81, 0x01), (0x80, 0x01), (0x94, 0x6B), (0x83, 0x00)])?;
+        self.wait_for(clock, |sensor| Ok(sensor.read(0x83)? != 0))?;
+        self.write(0x83, 0x01)?;
+        let info = self.read(0x92)?;
+
+        self.write_all(&[(0x81, 0x00), (PAGE_SELECT, 0x06)])?;
+        let value = self.read(0x83)?;
+        self.write(0x83, value & !0x04)?;
+        self.write_all(&[(PAGE_SELECT, 0x01), (0x00, 0x01), (PAGE_SELECT, 0x00), (0x80, 0x00)])?;
+
+        Ok((info & 0x7F, info & 0x80 != 0))
+    }
+
+    /// Enables the first `count` good reference SPADs, starting at the
+    /// first aperture SPAD when those are used.
+    fn set_reference_spads(&mut self, count: u8, is_aperture: bool) -> Result<(), SensorError> {
+        let mut map = [0; 6];
+        self.read_into(GLOBAL_CONFIG_SPAD_ENABLES_REF_0, &mut map)?;
+
+        self.write_all(&[
+            (PAGE_SELECT, 0x01),
+            (DYNAMIC_SPAD_REF_EN_START_OFFSET, 0x00),
+            (DYNAMIC_SPAD_NUM_REQUESTED_REF_SPAD, 0x2C),
+            (PAGE_SELECT, 0x00),
+            (GLOBAL_CONFIG_REF_EN_START_SELECT, 0xB4),
+        ])?;
+
+        let first = if is_aperture { 12 } else { 0 };
+        let mut enabled = 0;
+        for spad in 0..48 {
+            let (byte, bit) = (spad / 8, 1 << (spad % 8));
+            if spad < first || enabled == count {
+                map[byte] &= !bit;
+            } else if map[byte] & bit != 0 {
+                enabled += 1;
+            }
+        }
+
+        let mut data = [0; 7];
+        data[0] = GLOBAL_CONFIG_SPAD_ENABLES_REF_0;
+        data[1..].copy_from_slice(&map);
+        self.i2c.write(self.address, &data).map_err(|_| SensorError::BusError)
+    }
+
+    fn single_ref_calibration<C: Clock>(&mut self, clock: &mut C, vhv_init: u8) -> Result<(), SensorError> {
+        self.write(SYSRANGE_START, 0x01 | vhv_init)?;
+        self.wait_for(clock, |sensor| Ok(sensor.read(RESULT_INTERRUPT_STATUS)? & 0x07 != 0))?;
+        self.write(SYSTEM_INTERRUPT_CLEAR, 0x01)?;
+        self.write(SYSRANGE_START, 0x00)
+    }
+
+    fn read_timing_budget(&mut self) -> Result<u32, SensorError> {
+        let steps = SequenceSteps::from_config(self.read(SYSTEM_SEQUENCE_CONFIG)?);
+        let timeouts = self.sequence_timeouts(steps)?;
+
+        let mut budget_us = START_OVERHEAD_US + END_OVERHEAD_US;
+        if steps.tcc {
+            budget_us += timeouts.msrc_dss_tcc_us + TCC_OVERHEAD_US;
+        }
+        if steps.dss {
+            budget_us += 2 * (timeouts.msrc_dss_tcc_us + DSS_OVERHEAD_US);
+        } else if steps.msrc {
+            budget_us += timeouts.msrc_dss_tcc_us + MSRC_OVERHEAD_US;
+        }
+        if steps.pre_range {
+            budget_us = budget_us.saturating_add(timeouts.pre_range_us.saturating_add(PRE_RANGE_OVERHEAD_US));
+        }
+        if steps.final_range {
+            budget_us = budget_us.saturating_add(timeouts.final_range_us.saturating_add(FINAL_RANGE_OVERHEAD_US));
+        }
+        Ok(budget_us)
+    }
+
+    fn sequence_timeouts(&mut self, steps: SequenceSteps) -> Result<SequenceTimeouts, SensorError> {
+        let pre_range_vcsel_period = decode_vcsel_period(self.read(PRE_RANGE_CONFIG_VCSEL_PERIOD)?);
+        let msrc_dss_tcc_mclks = self.read(MSRC_CONFIG_TIMEOUT_MACROP)? as u32 + 1;
+        let pre_range_mclks = decode_timeout(self.read_u16(PRE_RANGE_CONFIG_TIMEOUT_MACROP_HI)?);
+
+        let final_range_vcsel_period = decode_vcsel_period(self.read(FINAL_RANGE_CONFIG_VCSEL_PERIOD)?);
+        let mut final_range_mclks = decode_timeout(self.read_u16(FINAL_RANGE_CONFIG_TIMEOUT_MACROP_HI)?);
+        // Le timeout final inclut celui du pré-range quand il est actif
+        if steps.pre_range {
+            final_range_mclks = final_range_mclks.saturating_sub(pre_range_mclks);
+        }
+
+        Ok(SequenceTimeouts {
+            final_range_vcsel_period,
+            msrc_dss_tcc_us: mclks_to_us(msrc_dss_tcc_mclks, pre_range_vcsel_period),
+            pre_range_mclks,
+            pre_range_us: mclks_to_us(pre_range_mclks, pre_range_vcsel_period),
+            final_range_us: mclks_to_us(final_range_mclks, final_range_vcsel_period),
+        })
+    }
+
+    fn restore_stop_variable(&mut self) -> Result<(), SensorError> {
+        self.write_all(&[(0x80, 0x01), (PAGE_SELECT, 0x01), (0x00, 0x00)])?;
+        self.write(0x91, self.stop_variable)?;
+        self.write_all(&[(0x00, 0x01), (PAGE_SELECT, 0x00), (0x80, 0x00)])
+    }
+
+    /// Polls `ready` until it holds, for at most [`Self::TIMEOUT_US`].
+    fn wait_for<C: Clock>(&mut self, clock: &mut C, mut ready: impl FnMut(&mut Self) -> Result<bool, SensorError>) -> Result<(), SensorError> {
+        let start = clock.now_us();
+        while !ready(self)? {
+            if clock.now_us().wrapping_sub(start) > Self::TIMEOUT_US {
+                return Err(SensorError::NoResponse);
+            }
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, register: u8, value: u8) -> Result<(), SensorError> {
+        self.i2c.write(self.address, &[register, value]).map_err(|_| SensorError::BusError)
+    }
+
+    fn write_all(&mut self, writes: &[(u8, u8)]) -> Result<(), SensorError> {
+        writes.iter().try_for_each(|&(register, value)| self.write(register, value))
+    }
+
+    fn write_u16(&mut self, register: u8, value: u16) -> Result<(), SensorError> {
+        let [high, low] = value.to_be_bytes();
+        self.i2c.write(self.address, &[register, high, low]).map_err(|_| SensorError::BusError)
+    }
+
+    fn write_u32(&mut self, register: u8, value: u32) -> Result<(), SensorError> {
+        let [b0, b1, b2, b3] = value.to_be_bytes();
+        self.i2c.write(self.address, &[register, b0, b1, b2, b3]).map_err(|_| SensorError::BusError)
+    }
+
+    fn read(&mut self, register: u8) -> Result<u8, SensorError> {
+        let mut value = [0];
+        self.read_into(register, &mut value)?;
+        Ok(value[0])
+    }
+
+    fn read_u16(&mut self, register: u8) -> Result<u16, SensorError> {
+        let mut value = [0; 2];
+        self.read_into(register, &mut value)?;
+        Ok(u16::from_be_bytes(value))
+    }
+
+    fn read_into(&mut self, register: u8, buffer: &mut [u8]) -> Result<(), SensorError> {
+        self.i2c.write_read(self.address, &[register], buffer).map_err(|_| SensorError::BusError)
+    }
+}
+
+impl<I: I2c> DistanceSensor for Vl53l0x<I> {
+    /// Runs a single-shot measurement and waits for its result.
+    fn measure_distance<C: Clock>(&mut self, clock: &mut C) -> Result<Distance, SensorError> {
+        self.restore_stop_variable()?;
+        self.write(SYSRANGE_START, 0x01)?;
+        self.wait_for(clock, |sensor| Ok(sensor.read(SYSRANGE_START)? & 0x01 == 0))?;
+
+        let start = clock.now_us();
+        loop {
+            if let Some(result) = self.read_range() {
+                return result;
+            }
+            if clock.now_us().wrapping_sub(start) > Self::TIMEOUT_US {
+                return Err(SensorError::NoResponse);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{Bench, EchoScript, MockRegisters};
+
+    const STOP_VARIABLE: u8 = 0x3C;
+
+    /// Registres d'un VL53L0X tel qu'il sort de l'usine, qui termine ses
+    /// opérations dès qu'elles sont lancées.
+    fn device() -> MockRegisters {
+        let device = MockRegisters::default();
+        device.set(0, IDENTIFICATION_MODEL_ID, MODEL_ID);
+        device.set(1, 0x91, STOP_VARIABLE);
+        // 5 SPADs de référence, de type ouverture
+        device.set(7, 0x92, 0x85);
+        for register in GLOBAL_CONFIG_SPAD_ENABLES_REF_0..GLOBAL_CONFIG_SPAD_ENABLES_REF_0 + 6 {
+            device.set(0, register, 0xFF);
+        }
+
+        device.when_written(7, 0x83, 0x00, &[(7, 0x83, 0x10)]);
+        for start in [0x01, 0x41] {
+            device.when_written(0, SYSRANGE_START, start, &[(0, SYSRANGE_START, 0x00), (0, RESULT_INTERRUPT_STATUS, 0x07)]);
+        }
+        device.when_written(0, SYSTEM_INTERRUPT_CLEAR, 0x01, &[(0, RESULT_INTERRUPT_STATUS, 0x00)]);
+        device
+    }
+
+    fn set_result(device: &MockRegisters, status: u8, mm: u16) {
+        let [high, low] = mm.to_be_bytes();
+        device.set(0, RESULT_RANGE_STATUS, status << 3);
+        device.set(0, RESULT_RANGE_STATUS + 10, high);
+        device.set(0, RESULT_RANGE_STATUS + 11, low);
+    }
+
+    #[test]
+    fn encodes_timeouts() {
+        assert_eq!(decode_timeout(0x01FE), 509);
+        assert_eq!(encode_timeout(578), 0x0290);
+        assert_eq!(decode_timeout(encode_timeout(257)), 257);
+        assert_eq!(encode_timeout(0), 0);
+        assert_eq!(macro_period_ns(14), 53_384);
+
+        // Registres aberrants : saturés, sans débordement
+        assert_eq!(decode_timeout(0x1F01), (1 << 31) + 1);
+        assert_eq!(decode_timeout(0x2001), u32::MAX);
+        assert_eq!(decode_timeout(0xFF00), 1);
+        assert_eq!(mclks_to_us(u32::MAX, 14), u32::MAX);
+        assert_eq!(us_to_mclks(u32::MAX, 14), 80_454_205);
+        assert_eq!(decode_vcsel_period(0xFF), 0xFF);
+    }
+
+    #[test]
+    fn runs_the_init_sequence() {
+        let bench = Bench::new(EchoScript::Silent);
+        let device = device();
+        let mut sensor = Vl53l0x::new(device.clone());
+
+        assert_eq!(sensor.init(&mut bench.clock()), Ok(()));
+
+        // Les 12 premiers SPADs (non ouverture) sont coupés, puis seuls les
+        // 5 suivants restent actifs
+        let map: Vec<_> = (0..6).map(|offset| device.get(0, GLOBAL_CONFIG_SPAD_ENABLES_REF_0 + offset)).collect();
+        assert_eq!(map, [0x00, 0xF0, 0x01, 0x00, 0x00, 0x00]);
+
+        // Budget relu avec toutes les étapes (~33 ms), puis timeout final
+        // recalculé sans MSRC ni TCC
+        assert_eq!(sensor.measurement_timing_budget_us(), 33_849);
+        assert_eq!((device.get(0, 0x71), device.get(0, 0x72)), (0x02, 0x90));
+
+        let writes = device.writes();
+        assert!(writes.contains(&(0, VHV_CONFIG_PAD_SCL_SDA_EXTSUP_HV, 0x01)));
+        assert!(writes.contains(&(0, SYSRANGE_START, 0x41)));
+        assert_eq!(writes.last(), Some(&(0, SYSTEM_SEQUENCE_CONFIG, 0xE8)));
+    }
+
+    #[test]
+    fn measures_a_single_shot() {
+        let bench = Bench::new(EchoScript::Silent);
+        let device = device();
+        let mut sensor = Vl53l0x::new(device.clone());
+        sensor.init(&mut bench.clock()).unwrap();
+        let init_writes = device.writes().len();
+
+        set_result(&device, RANGE_VALID, 523);
+        assert_eq!(sensor.measure_distance(&mut bench.clock()), Ok(Distance::from_mm(523)));
+
+        let writes = &device.writes()[init_writes..];
+        assert!(writes.contains(&(1, 0x91, STOP_VARIABLE)));
+        assert_eq!(writes.last(), Some(&(0, SYSTEM_INTERRUPT_CLEAR, 0x01)));
+    }
+
+    #[test]
+    fn reports_invalid_ranges_and_devices() {
+        let bench = Bench::new(EchoScript::Silent);
+        let device = device();
+        let mut sensor = Vl53l0x::new(device.clone());
+        sensor.init(&mut bench.clock()).unwrap();
+
+        // Pas de cible : signal trop faible et valeur 8190
+        set_result(&device, 4, 8_190);
+        assert_eq!(sensor.measure_distance(&mut bench.clock()), Err(SensorError::OutOfRange(Distance::from_mm(8_190))));
+        set_result(&device, 9, 1_800);
+        assert_eq!(sensor.measure_distance(&mut bench.clock()), Err(SensorError::OutOfRange(Distance::from_mm(1_800))));
+
+        // Défaut matériel ou cible trop proche : pas une absence de cible
+        for status in [1, 8] {
+            set_result(&device, status, 20);
+            assert_eq!(sensor.measure_distance(&mut bench.clock()), Err(SensorError::InvalidReading));
+        }
+
+        let other = MockRegisters::default();
+        other.set(0, IDENTIFICATION_MODEL_ID, 0xAA);
+        assert_eq!(Vl53l0x::new(other).init(&mut bench.clock()), Err(SensorError::WrongDevice));
+
+        device.break_bus();
+        assert_eq!(sensor.measure_distance(&mut bench.clock()), Err(SensorError::BusError));
+    }
+
+    #[test]
+    fn rejects_timing_budgets_too_short() {
+        let bench = Bench::new(EchoScript::Silent);
+        let device = device();
+        let mut sensor = Vl53l0x::new(device.clone());
+        sensor.init(&mut bench.clock()).unwrap();
+
+        assert_eq!(sensor.set_measurement_timing_budget(15_000), Err(SensorError::InvalidConfig));
+        assert_eq!(sensor.set_measurement_timing_budget(20_000), Ok(()));
+
+        // Timeout de pré-range aberrant : plus long que n'importe quel budget
+        device.set(0, PRE_RANGE_CONFIG_TIMEOUT_MACROP_HI, 0xFF);
+        assert_eq!(sensor.set_measurement_timing_budget(200_000), Err(SensorError::InvalidConfig));
+        assert_eq!(sensor.measurement_timing_budget_us(), 20_000);
+    }
+
+    #[test]
+    fn rejects_addresses_above_7_bits() {
+        let device = device();
+        let mut sensor = Vl53l0x::new(device.clone());
+
+        assert_eq!(sensor.set_address(0x80), Err(SensorError::InvalidConfig));
+        assert!(device.writes().is_empty());
+        assert_eq!(sensor.set_address(0x30), Ok(()));
+        assert_eq!(device.writes(), [(0, I2C_SLAVE_DEVICE_ADDRESS, 0x30)]);
+    }
+
+    #[test]
+    fn times_out_when_the_device_hangs() {
+        let bench = Bench::new(EchoScript::Silent);
+        let device = MockRegisters::default();
+        device.set(0, IDENTIFICATION_MODEL_ID, MODEL_ID);
+        let mut sensor = Vl53l0x::new(device);
+
+        assert_eq!(sensor.init(&mut bench.clock()), Err(SensorError::NoResponse));
+        assert!(bench.now_us() > 500_000);
+    }
+
+    #[test]
+    fn ranges_continuously() {
+        let bench = Bench::new(EchoScript::Silent);
+        let device = device();
+        let mut sensor = Vl53l0x::new(device.clone());
+        sensor.init(&mut bench.clock()).unwrap();
+        // Oscillateur calibré à 3232 cycles par ms
+        device.set(0, OSC_CALIBRATE_VAL, 0x0C);
+        device.set(0, OSC_CALIBRATE_VAL + 1, 0xA0);
+
+        sensor.start_continuous(100).unwrap();
+        let period: Vec<_> = (0..4).map(|offset| device.get(0, SYSTEM_INTERMEASUREMENT_PERIOD + offset)).collect();
+        assert_eq!(period, 323_200u32.to_be_bytes());
+        assert_eq!(device.get(0, SYSRANGE_START), 0x04);
+        // Plus de 2^32 cycles : refusé avant de relancer
+        assert_eq!(sensor.start_continuous(2_000_000), Err(SensorError::InvalidConfig));
+        assert_eq!(sensor.read_range(), None);
+
+        set_result(&device, RANGE_VALID, 87);
+        device.set(0, RESULT_INTERRUPT_STATUS, 0x04);
+        assert_eq!(sensor.read_range(), Some(Ok(Distance::from_mm(87))));
+        assert_eq!(sensor.read_range(), None);
+
+        sensor.stop_continuous().unwrap();
+        assert!(device.writes().ends_with(&[(0, SYSRANGE_START, 0x01), (0, PAGE_SELECT, 0x01), (1, 0x00, 0x00), (1, 0x91, 0x00), (1, 0x00, 0x01), (1, PAGE_SELECT, 0x00)]));
+    }
+}
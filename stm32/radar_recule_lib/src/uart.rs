@@ -0,0 +1,238 @@
+use embedded_hal_nb::{
+    nb,
+    serial::{Read, Write},
+};
+
+use crate::{Clock, Distance, DistanceSensor, SensorError};
+
+/// Reads one byte, giving up `timeout_us` after `start_us`.
+fn read_byte<S: Read, C: Clock>(serial: &mut S, clock: &mut C, start_us: u32, timeout_us: u32) -> Result<u8, SensorError> {
+    loop {
+        // Avant chaque octet, et pas seulement sur une ligne muette : du
+        // bruit sans trame valable ne doit pas bloquer la mesure
+        if clock.now_us().wrapping_sub(start_us) > timeout_us {
+            return Err(SensorError::NoResponse);
+        }
+        match serial.read() {
+            Ok(byte) => return Ok(byte),
+            Err(nb::Error::WouldBlock) => {}
+            Err(nb::Error::Other(_)) => return Err(SensorError::BusError),
+        }
+    }
+}
+
+fn check_range(mm: u16, min_mm: u16, max_mm: u16) -> Result<Distance, SensorError> {
+    let distance = Distance::from_mm(mm as u32);
+    if mm < min_mm {
+        return Err(SensorError::TooClose(distance));
+    }
+    if mm > max_mm {
+        return Err(SensorError::OutOfRange(distance));
+    }
+    Ok(distance)
+}
+
+/// DFRobot A02YYUW (and other `0xFF, high, low, sum` modules such as the
+/// JSN-SR04T in mode 2), streaming a distance frame every 100 ms at
+/// 9600 baud.
+///
+/// Bytes can be fed one by one from the UART interrupt with
+/// [`Self::on_byte`], or read in a blocking way through
+/// [`DistanceSensor::measure_distance`].
+pub struct A02yyuw<S> {
+    serial: S,
+    frame: [u8; 4],
+    len: usize,
+}
+
+impl<S> A02yyuw<S> {
+    const HEADER: u8 = 0xFF;
+    const MIN_MM: u16 = 30;
+    const MAX_MM: u16 = 4_500;
+    /// Two frame periods.
+    const TIMEOUT_US: u32 = 250_000;
+
+    pub fn new(serial: S) -> Self {
+        Self {
+            serial,
+            frame: [0; 4],
+            len: 0,
+        }
+    }
+
+    pub fn release(self) -> S {
+        self.serial
+    }
+
+    /// Handles a received byte. Returns the result once a whole frame has
+    /// been received.
+    pub fn on_byte(&mut self, byte: u8) -> Option<Result<Distance, SensorError>> {
+        if self.len == 0 && byte != Self::HEADER {
+            return None;
+        }
+
+        self.frame[self.len] = byte;
+        self.len += 1;
+        if self.len < self.frame.len() {
+            return None;
+        }
+
+        let frame = self.frame;
+        self.len = 0;
+
+        let sum = frame[..3].iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+        if sum != frame[3] {
+            // L'en-tête était peut-être un octet de données : se resynchroniser
+            // sur un 0xFF suivant avant de conclure à une trame corrompue
+            if let Some(position) = frame[1..].iter().position(|&byte| byte == Self::HEADER) {
+                let rest = &frame[1 + position..];
+                self.frame[..rest.len()].copy_from_slice(rest);
+                self.len = rest.len();
+                return None;
+            }
+            return Some(Err(SensorError::BadChecksum));
+        }
+
+        Some(check_range(u16::from_be_bytes([frame[1], frame[2]]), Self::MIN_MM, Self::MAX_MM))
+    }
+}
+
+impl<S: Read> DistanceSensor for A02yyuw<S> {
+    /// Waits for the next complete frame.
+    fn measure_distance<C: Clock>(&mut self, clock: &mut C) -> Result<Distance, SensorError> {
+        self.len = 0;
+        let start = clock.now_us();
+        loop {
+            let byte = read_byte(&mut self.serial, clock, start, Self::TIMEOUT_US)?;
+            if let Some(result) = self.on_byte(byte) {
+                return result;
+            }
+        }
+    }
+}
+
+/// US-100 with its mode jumper fitted (UART mode, 9600 baud): writing 0x55
+/// starts a measurement, answered by the distance in mm on two bytes. The
+/// module compensates for temperature itself.
+pub struct Us100<S> {
+    serial: S,
+}
+
+impl<S> Us100<S> {
+    const MEASURE: u8 = 0x55;
+    const MIN_MM: u16 = 20;
+    const MAX_MM: u16 = 4_500;
+    /// Echo flight time at full range plus the module's processing.
+    const TIMEOUT_US: u32 = 100_000;
+
+    pub fn new(serial: S) -> Self {
+        Self { serial }
+    }
+
+    pub fn release(self) -> S {
+        self.serial
+    }
+}
+
+impl<S: Read + Write> DistanceSensor for Us100<S> {
+    fn measure_distance<C: Clock>(&mut self, clock: &mut C) -> Result<Distance, SensorError> {
+        // Vider les octets restés d'une réponse précédente
+        while self.serial.read().is_ok() {}
+
+        nb::block!(self.serial.write(Self::MEASURE)).map_err(|_| SensorError::BusError)?;
+
+        let start = clock.now_us();
+        let high = read_byte(&mut self.serial, clock, start, Self::TIMEOUT_US)?;
+        let low = read_byte(&mut self.serial, clock, start, Self::TIMEOUT_US)?;
+
+        check_range(u16::from_be_bytes([high, low]), Self::MIN_MM, Self::MAX_MM)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{Bench, EchoScript, MockSerial};
+
+    fn frame(mm: u16) -> [u8; 4] {
+        let [high, low] = mm.to_be_bytes();
+        [0xFF, high, low, 0xFFu8.wrapping_add(high).wrapping_add(low)]
+    }
+
+    #[test]
+    fn parses_a02yyuw_frames() {
+        let mut sensor = A02yyuw::new(MockSerial::default());
+        let results: Vec<_> = [frame(1_234), frame(300)].concat().into_iter().filter_map(|byte| sensor.on_byte(byte)).collect();
+
+        assert_eq!(results, [Ok(Distance::from_mm(1_234)), Ok(Distance::from_mm(300))]);
+    }
+
+    #[test]
+    fn rejects_corrupted_frames() {
+        let mut sensor = A02yyuw::new(MockSerial::default());
+        let mut corrupted = frame(1_234);
+        corrupted[2] ^= 0x04;
+
+        let results: Vec<_> = corrupted.into_iter().filter_map(|byte| sensor.on_byte(byte)).collect();
+        assert_eq!(results, [Err(SensorError::BadChecksum)]);
+
+        let results: Vec<_> = frame(10).into_iter().filter_map(|byte| sensor.on_byte(byte)).collect();
+        assert_eq!(results, [Err(SensorError::TooClose(Distance::from_mm(10)))]);
+    }
+
+    #[test]
+    fn resynchronises_mid_stream() {
+        let serial = MockSerial::default();
+        // Réception commencée au milieu d'une trame dont les deux derniers
+        // octets valent 0xFF, comme l'en-tête
+        assert_eq!(frame(511)[2..], [0xFF, 0xFF]);
+        serial.receive(&frame(511)[2..]);
+        serial.receive(&frame(2_000));
+
+        let bench = Bench::new(EchoScript::Silent);
+        let mut sensor = A02yyuw::new(serial);
+        assert_eq!(sensor.measure_distance(&mut bench.clock()), Ok(Distance::from_mm(2_000)));
+    }
+
+    #[test]
+    fn times_out_without_frames() {
+        let serial = MockSerial::default();
+        let bench = Bench::new(EchoScript::Silent);
+        let mut sensor = A02yyuw::new(serial.clone());
+
+        assert_eq!(sensor.measure_distance(&mut bench.clock()), Err(SensorError::NoResponse));
+        assert!(bench.now_us() > 250_000);
+
+        serial.break_line();
+        assert_eq!(sensor.measure_distance(&mut bench.clock()), Err(SensorError::BusError));
+    }
+
+    #[test]
+    fn times_out_on_a_noisy_line() {
+        // Un flot d'octets sans trame valable, plus long que le délai
+        let mut serial = MockSerial::default();
+        serial.receive(&[0x00; 300_000]);
+        let bench = Bench::new(EchoScript::Silent);
+        let mut sensor = A02yyuw::new(serial.clone());
+
+        assert_eq!(sensor.measure_distance(&mut bench.clock()), Err(SensorError::NoResponse));
+        assert!(bench.now_us() > 250_000);
+        // Abandonnée avant la fin du flot
+        assert!(serial.read().is_ok());
+    }
+
+    #[test]
+    fn queries_the_us100() {
+        let serial = MockSerial::default();
+        let bench = Bench::new(EchoScript::Silent);
+        serial.receive(&[0x12]);
+        serial.reply_to(0x55, &[0x03, 0xE8]);
+        let mut sensor = Us100::new(serial.clone());
+
+        assert_eq!(sensor.measure_distance(&mut bench.clock()), Ok(Distance::from_mm(1_000)));
+        assert_eq!(serial.sent(), [0x55]);
+
+        let mut silent = Us100::new(MockSerial::default());
+        assert_eq!(silent.measure_distance(&mut bench.clock()), Err(SensorError::NoResponse));
+    }
+}
@@ -0,0 +1,339 @@
+use crate::{Distance, SensorError};
+
+/// How close the nearest obstacle is, from the furthest to the closest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Zone {
+    /// Nothing within the far threshold, or no echo at all.
+    Clear,
+    Far,
+    Near,
+    Critical,
+}
+
+impl Zone {
+    /// Number of LEDs lit on a bar graph, from 0 (clear) to 3 (critical).
+    pub const fn bar_level(self) -> u8 {
+        self as u8
+    }
+}
+
+/// Upper bounds of the far, near and critical zones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ZoneThresholds {
+    far: Distance,
+    near: Distance,
+    critical: Distance,
+}
+
+impl ZoneThresholds {
+    pub const fn new(far: Distance, near: Distance, critical: Distance) -> Self {
+        assert!(
+            far.as_mm() > near.as_mm() && near.as_mm() > critical.as_mm(),
+            "zone thresholds must decrease from far to critical"
+        );
+        Self { far, near, critical }
+    }
+
+    pub const fn far(&self) -> Distance {
+        self.far
+    }
+
+    pub const fn near(&self) -> Distance {
+        self.near
+    }
+
+    pub const fn critical(&self) -> Distance {
+        self.critical
+    }
+
+    /// Zone of a reading, `None` when the reading tells nothing about the
+    /// obstacle: sensor fault, measurement started too soon.
+    ///
+    /// An echo that never came back, or one beyond the maximum range, means
+    /// nothing is in range. A reading in the blind zone of the sensor is
+    /// critical whatever the thresholds: the obstacle is as close as it gets.
+    pub fn classify(&self, reading: Result<Distance, SensorError>) -> Option<Zone> {
+        match reading {
+            Ok(distance) if distance <= self.critical => Some(Zone::Critical),
+            Err(SensorError::TooClose(_)) => Some(Zone::Critical),
+            Ok(distance) if distance <= self.near => Some(Zone::Near),
+            Ok(distance) if distance <= self.far => Some(Zone::Far),
+            Ok(_) | Err(SensorError::EchoTooLong | SensorError::OutOfRange(_)) => Some(Zone::Clear),
+            Err(_) => None,
+        }
+    }
+}
+
+impl Default for ZoneThresholds {
+    /// 150 cm, 80 cm and 30 cm, for a car bumper.
+    fn default() -> Self {
+        Self::new(Distance::from_cm(150), Distance::from_cm(80), Distance::from_cm(30))
+    }
+}
+
+/// A change of zone: the obstacle left `left` and entered `entered`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ZoneTransition {
+    pub left: Zone,
+    pub entered: Zone,
+}
+
+impl ZoneTransition {
+    /// The obstacle got closer.
+    pub fn is_approaching(&self) -> bool {
+        self.entered > self.left
+    }
+}
+
+/// Debounced zone tracking.
+///
+/// A distance sitting right on a threshold would otherwise make the zone
+/// flicker at every measurement. Two mechanisms prevent it:
+/// - hysteresis: a zone is entered as soon as the distance crosses its
+///   threshold, but left only once the distance is beyond the threshold plus
+///   the hysteresis of that boundary;
+/// - dwell: the new zone must be seen on `min_dwell` consecutive readings
+///   before the change is reported.
+pub struct ZoneClassifier {
+    thresholds: ZoneThresholds,
+    /// Hysteresis of the far, near and critical boundaries.
+    hysteresis: [Distance; 3],
+    min_dwell: u8,
+    zone: Zone,
+    candidate: Option<(Zone, u8)>,
+}
+
+impl ZoneClassifier {
+    /// No hysteresis and no dwell: every zone change is reported right away.
+    pub fn new(thresholds: ZoneThresholds) -> Self {
+        Self {
+            thresholds,
+            hysteresis: [Distance::ZERO; 3],
+            min_dwell: 1,
+            zone: Zone::Clear,
+            candidate: None,
+        }
+    }
+
+    pub fn with_hysteresis(mut self, far: Distance, near: Distance, critical: Distance) -> Self {
+        self.hysteresis = [far, near, critical];
+        self
+    }
+
+    /// Number of consecutive readings a new zone must be seen on, at least 1.
+    pub fn with_min_dwell(mut self, readings: u8) -> Self {
+        self.min_dwell = readings.max(1);
+        self
+    }
+
+    pub fn thresholds(&self) -> &ZoneThresholds {
+        &self.thresholds
+    }
+
+    /// Moves the zone boundaries. The current zone is kept until the next
+    /// readings confirm a change, with the usual hysteresis and dwell.
+    pub fn set_thresholds(&mut self, thresholds: ZoneThresholds) {
+        self.thresholds = thresholds;
+        self.candidate = None;
+    }
+
+    /// Current (debounced) zone.
+    pub fn zone(&self) -> Zone {
+        self.zone
+    }
+
+    /// Feeds a reading. Returns the transition when the zone changes.
+    ///
+    /// A reading that tells nothing about the obstacle (see
+    /// [`ZoneThresholds::classify`]) holds the current zone and leaves the
+    /// dwell count where it was.
+    pub fn update(&mut self, reading: Result<Distance, SensorError>) -> Option<ZoneTransition> {
+        let observed = self.observe(reading)?;
+
+        if observed == self.zone {
+            self.candidate = None;
+            return None;
+        }
+
+        let seen = match self.candidate {
+            Some((zone, seen)) if zone == observed => seen + 1,
+            _ => 1,
+        };
+
+        if seen < self.min_dwell {
+            self.candidate = Some((observed, seen));
+            return None;
+        }
+
+        let transition = ZoneTransition { left: self.zone, entered: observed };
+        self.zone = observed;
+        self.candidate = None;
+        Some(transition)
+    }
+
+    pub fn reset(&mut self) {
+        self.zone = Zone::Clear;
+        self.candidate = None;
+    }
+
+    /// Zone of `reading` seen from the current zone: the boundaries already
+    /// crossed are pushed outwards by their hysteresis.
+    fn observe(&self, reading: Result<Distance, SensorError>) -> Option<Zone> {
+        let widen = |threshold: Distance, zone: Zone, hysteresis: Distance| {
+            if self.zone >= zone {
+                Distance::from_mm(threshold.as_mm() + hysteresis.as_mm())
+            } else {
+                threshold
+            }
+        };
+
+        let [far, near, critical] = self.hysteresis;
+        let thresholds = ZoneThresholds {
+            far: widen(self.thresholds.far, Zone::Far, far),
+            near: widen(self.thresholds.near, Zone::Near, near),
+            critical: widen(self.thresholds.critical, Zone::Critical, critical),
+        };
+        thresholds.classify(reading)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SensorProfile;
+
+    #[test]
+    fn classifies_distances() {
+        let thresholds = ZoneThresholds::default();
+        let zone = |cm| thresholds.classify(Ok(Distance::from_cm(cm))).unwrap();
+
+        assert_eq!(zone(300), Zone::Clear);
+        assert_eq!(zone(151), Zone::Clear);
+        assert_eq!(zone(150), Zone::Far);
+        assert_eq!(zone(81), Zone::Far);
+        assert_eq!(zone(80), Zone::Near);
+        assert_eq!(zone(30), Zone::Critical);
+        assert_eq!(zone(2), Zone::Critical);
+    }
+
+    #[test]
+    fn classifies_failed_readings_by_cause() {
+        let thresholds = ZoneThresholds::default();
+
+        // Zone aveugle : l'obstacle touche presque le capteur
+        assert_eq!(thresholds.classify(Err(SensorError::TooClose(Distance::from_cm(1)))), Some(Zone::Critical));
+        assert_eq!(thresholds.classify(Err(SensorError::OutOfRange(Distance::from_cm(450)))), Some(Zone::Clear));
+        assert_eq!(thresholds.classify(Err(SensorError::EchoTooLong)), Some(Zone::Clear));
+
+        for error in [SensorError::NoEchoStart, SensorError::PinError, SensorError::BusError, SensorError::TooSoon] {
+            assert_eq!(thresholds.classify(Err(error)), None, "{error:?}");
+        }
+    }
+
+    #[test]
+    fn blind_zone_is_critical_below_the_critical_threshold() {
+        // JSN-SR04T (zone aveugle de 20 cm) avec un seuil critique à 10 cm
+        let thresholds = ZoneThresholds::new(Distance::from_cm(150), Distance::from_cm(80), Distance::from_cm(10));
+        let blind = SensorProfile::JSN_SR04T.blind_zone;
+        assert!(thresholds.critical() < blind);
+
+        let too_close = Err(SensorError::TooClose(Distance::from_cm(15)));
+        assert_eq!(thresholds.classify(too_close), Some(Zone::Critical));
+        assert_eq!(thresholds.classify(Ok(blind)), Some(Zone::Near));
+    }
+
+    #[test]
+    fn bar_level_grows_with_proximity() {
+        let levels = [Zone::Clear, Zone::Far, Zone::Near, Zone::Critical].map(Zone::bar_level);
+
+        assert_eq!(levels, [0, 1, 2, 3]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_unordered_thresholds() {
+        ZoneThresholds::new(Distance::from_cm(50), Distance::from_cm(80), Distance::from_cm(30));
+    }
+
+    fn classifier() -> ZoneClassifier {
+        ZoneClassifier::new(ZoneThresholds::default())
+            .with_hysteresis(Distance::from_cm(10), Distance::from_cm(5), Distance::from_cm(5))
+    }
+
+    fn feed(classifier: &mut ZoneClassifier, cm: &[u32]) -> Vec<Option<ZoneTransition>> {
+        cm.iter().map(|&cm| classifier.update(Ok(Distance::from_cm(cm)))).collect()
+    }
+
+    fn transition(left: Zone, entered: Zone) -> Option<ZoneTransition> {
+        Some(ZoneTransition { left, entered })
+    }
+
+    #[test]
+    fn hysteresis_stops_flicker_on_a_boundary() {
+        let mut plain = ZoneClassifier::new(ZoneThresholds::default());
+        let mut zones = classifier();
+        // Obstacle immobile sur la limite des 80 cm, mesure bruitée de ±1 cm
+        let boundary = [79, 81, 80, 81, 79, 81, 80, 81];
+
+        assert_eq!(feed(&mut plain, &boundary).iter().flatten().count(), 8);
+
+        let events = feed(&mut zones, &boundary);
+        assert_eq!(events[0], transition(Zone::Clear, Zone::Near));
+        assert!(events[1..].iter().all(Option::is_none), "{events:?}");
+        assert_eq!(zones.zone(), Zone::Near);
+
+        // Il faut dépasser 80 + 5 cm pour revenir dans la zone lointaine
+        assert_eq!(feed(&mut zones, &[85, 86]), [None, transition(Zone::Near, Zone::Far)]);
+    }
+
+    #[test]
+    fn waits_the_dwell_count_before_switching() {
+        let mut zones = classifier().with_min_dwell(3);
+
+        assert_eq!(feed(&mut zones, &[100, 100]), [None, None]);
+        assert_eq!(feed(&mut zones, &[100]), [transition(Zone::Clear, Zone::Far)]);
+
+        // Un relevé isolé dans une autre zone remet le compteur à zéro
+        assert_eq!(feed(&mut zones, &[20, 20, 100, 20, 20]), [None; 5]);
+        assert_eq!(zones.update(Ok(Distance::from_cm(20))), transition(Zone::Far, Zone::Critical));
+        assert!(transition(Zone::Far, Zone::Critical).unwrap().is_approaching());
+    }
+
+    #[test]
+    fn failed_readings_hold_the_zone() {
+        let mut zones = classifier().with_min_dwell(2);
+        feed(&mut zones, &[50, 50]);
+
+        // Capteur en défaut : la zone ne bouge pas, même répété
+        for _ in 0..5 {
+            assert_eq!(zones.update(Err(SensorError::NoEchoStart)), None);
+        }
+        assert_eq!(zones.zone(), Zone::Near);
+
+        // Ni ne compte, ni n'interrompt la confirmation d'une nouvelle zone
+        assert_eq!(feed(&mut zones, &[20]), [None]);
+        assert_eq!(zones.update(Err(SensorError::BusError)), None);
+        assert_eq!(feed(&mut zones, &[20]), [transition(Zone::Near, Zone::Critical)]);
+
+        // Plus d'écho du tout : la voie est libre
+        assert_eq!(zones.update(Err(SensorError::EchoTooLong)), None);
+        assert_eq!(zones.update(Err(SensorError::EchoTooLong)), transition(Zone::Critical, Zone::Clear));
+        assert!(!transition(Zone::Critical, Zone::Clear).unwrap().is_approaching());
+    }
+
+    #[test]
+    fn applies_new_thresholds_on_the_next_readings() {
+        let mut zones = classifier();
+        feed(&mut zones, &[100]);
+        assert_eq!(zones.zone(), Zone::Far);
+
+        // Seuils d'un garage étroit : 1 m devient la zone critique
+        zones.set_thresholds(ZoneThresholds::new(
+            Distance::from_cm(200),
+            Distance::from_cm(150),
+            Distance::from_cm(100),
+        ));
+        assert_eq!(zones.zone(), Zone::Far);
+        assert_eq!(feed(&mut zones, &[100]), [transition(Zone::Far, Zone::Critical)]);
+    }
+}
@@ -0,0 +1,112 @@
+//! Consistent Overhead Byte Stuffing: removes every zero from a frame, so
+//! that a zero can delimit the frames on the link.
+
+use crate::Error;
+
+/// Worst-case length of `len` bytes once encoded, delimiter excluded.
+pub const fn max_encoded_len(len: usize) -> usize {
+    len + len / 254 + 1
+}
+
+/// Encodes `data` into `out` and returns the encoded length. The output
+/// holds no zero and no delimiter.
+pub fn encode(data: &[u8], out: &mut [u8]) -> Result<usize, Error> {
+    if out.len() < max_encoded_len(data.len()) {
+        return Err(Error::BufferTooSmall);
+    }
+
+    // Chaque bloc commence par la distance jusqu'au zéro suivant
+    let mut code_index = 0;
+    let mut code = 1u8;
+    let mut write = 1;
+    for &byte in data {
+        if byte != 0 {
+            out[write] = byte;
+            write += 1;
+            code += 1;
+        }
+        if byte == 0 || code == 0xFF {
+            out[code_index] = code;
+            code_index = write;
+            write += 1;
+            code = 1;
+        }
+    }
+    out[code_index] = code;
+    Ok(write)
+}
+
+/// Decodes one frame, delimiter excluded, into `out` and returns the
+/// decoded length.
+pub fn decode(data: &[u8], out: &mut [u8]) -> Result<usize, Error> {
+    let mut read = 0;
+    let mut write = 0;
+    while read < data.len() {
+        let code = data[read] as usize;
+        let end = read + code;
+        if code == 0 || end > data.len() {
+            return Err(Error::Cobs);
+        }
+        let block = &data[read + 1..end];
+        if block.contains(&0) {
+            return Err(Error::Cobs);
+        }
+        out.get_mut(write..write + block.len()).ok_or(Error::BufferTooSmall)?.copy_from_slice(block);
+        write += block.len();
+        read = end;
+
+        // Un bloc plein (0xFF) n'est pas suivi d'un zéro, le dernier non plus
+        if code != 0xFF && read < data.len() {
+            *out.get_mut(write).ok_or(Error::BufferTooSmall)? = 0;
+            write += 1;
+        }
+    }
+    Ok(write)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(data: &[u8]) -> Vec<u8> {
+        let mut encoded = vec![0; max_encoded_len(data.len())];
+        let len = encode(data, &mut encoded).unwrap();
+        encoded.truncate(len);
+        assert!(!encoded.contains(&0));
+
+        let mut decoded = vec![0; data.len()];
+        assert_eq!(decode(&encoded, &mut decoded), Ok(data.len()));
+        assert_eq!(decoded, data);
+        encoded
+    }
+
+    #[test]
+    fn encodes_the_reference_vectors() {
+        assert_eq!(round_trip(&[]), [0x01]);
+        assert_eq!(round_trip(&[0x00]), [0x01, 0x01]);
+        assert_eq!(round_trip(&[0x00, 0x00]), [0x01, 0x01, 0x01]);
+        assert_eq!(round_trip(&[0x11, 0x22, 0x00, 0x33]), [0x03, 0x11, 0x22, 0x02, 0x33]);
+        assert_eq!(round_trip(&[0x11, 0x00, 0x00, 0x00]), [0x02, 0x11, 0x01, 0x01, 0x01]);
+    }
+
+    #[test]
+    fn splits_long_runs() {
+        let data: Vec<u8> = (1..=254).collect();
+        let encoded = round_trip(&data);
+        assert_eq!(encoded.len(), 256);
+        assert_eq!((encoded[0], encoded[255]), (0xFF, 0x01));
+
+        let data: Vec<u8> = (0..600).map(|i| (i % 7) as u8).collect();
+        round_trip(&data);
+    }
+
+    #[test]
+    fn rejects_malformed_frames() {
+        let mut out = [0; 8];
+        assert_eq!(decode(&[0x05, 0x11, 0x22], &mut out), Err(Error::Cobs));
+        assert_eq!(decode(&[0x03, 0x11, 0x00], &mut out), Err(Error::Cobs));
+        assert_eq!(decode(&[0x00], &mut out), Err(Error::Cobs));
+        assert_eq!(decode(&[0x03, 0x11, 0x22], &mut out[..1]), Err(Error::BufferTooSmall));
+        assert_eq!(encode(&[1, 2, 3], &mut out[..3]), Err(Error::BufferTooSmall));
+    }
+}
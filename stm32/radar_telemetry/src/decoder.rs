@@ -0,0 +1,127 @@
+use crate::{cobs, Error, Message, MAX_FRAME_LEN};
+
+/// Rebuilds the messages from the bytes received, whatever their
+/// chunking. A corrupted frame gives one error, the next frame decodes
+/// normally.
+pub struct Decoder {
+    frame: [u8; MAX_FRAME_LEN],
+    len: usize,
+    overflow: bool,
+}
+
+impl Decoder {
+    pub const fn new() -> Self {
+        Self {
+            frame: [0; MAX_FRAME_LEN],
+            len: 0,
+            overflow: false,
+        }
+    }
+
+    /// Feeds one byte, and returns the message it completes if any.
+    pub fn push(&mut self, byte: u8) -> Option<Result<Message, Error>> {
+        if byte != 0 {
+            match self.frame.get_mut(self.len) {
+                Some(slot) => {
+                    *slot = byte;
+                    self.len += 1;
+                }
+                None => self.overflow = true,
+            }
+            return None;
+        }
+
+        let (len, overflow) = (self.len, self.overflow);
+        self.len = 0;
+        self.overflow = false;
+        if overflow {
+            return Some(Err(Error::FrameTooLong));
+        }
+        // Plusieurs délimiteurs de suite : rien entre eux
+        if len == 0 {
+            return None;
+        }
+        let mut payload = [0; MAX_FRAME_LEN];
+        Some(cobs::decode(&self.frame[..len], &mut payload).and_then(|len| Message::from_payload(&payload[..len])))
+    }
+
+    /// Feeds a chunk of bytes, and returns the messages it completes.
+    pub fn decode<'a>(&'a mut self, bytes: &'a [u8]) -> impl Iterator<Item = Result<Message, Error>> + 'a {
+        bytes.iter().filter_map(|&byte| self.push(byte))
+    }
+
+    /// Drops the partial frame, e.g. after the link was reopened.
+    pub fn reset(&mut self) {
+        self.len = 0;
+        self.overflow = false;
+    }
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Heartbeat, Sample};
+
+    fn frame(message: &Message) -> Vec<u8> {
+        let mut frame = [0; MAX_FRAME_LEN];
+        let len = message.encode(&mut frame).unwrap();
+        frame[..len].to_vec()
+    }
+
+    #[test]
+    fn decodes_a_chunked_stream() {
+        let first = Message::Sample(Sample {
+            sensor: 0,
+            timestamp_us: 100,
+            distance_mm: Some(0),
+        });
+        let second = Message::Heartbeat(Heartbeat {
+            uptime_ms: 1_000,
+            period_ms: 100,
+        });
+        let mut stream = vec![0x00];
+        stream.extend(frame(&first));
+        stream.extend(frame(&second));
+
+        let mut decoder = Decoder::new();
+        let mut messages = Vec::new();
+        for chunk in stream.chunks(3) {
+            messages.extend(decoder.decode(chunk));
+        }
+        assert_eq!(messages, [Ok(first), Ok(second)]);
+    }
+
+    #[test]
+    fn resynchronises_after_garbage() {
+        let message = Message::Heartbeat(Heartbeat {
+            uptime_ms: 5,
+            period_ms: 100,
+        });
+        let mut corrupted = frame(&message);
+        // Période de 100 ms lue 101 ms
+        let period = corrupted.iter().position(|&byte| byte == 100).unwrap();
+        corrupted[period] = 101;
+
+        // Fin d'une trame prise en cours de route, trame corrompue, trame
+        // trop longue, puis une trame valide
+        let mut stream = vec![0x42, 0x17, 0x00];
+        stream.extend(&corrupted);
+        stream.extend([0x55; MAX_FRAME_LEN + 5]);
+        stream.push(0x00);
+        stream.extend(frame(&message));
+
+        let mut decoder = Decoder::new();
+        let results: Vec<_> = decoder.decode(&stream).collect();
+        assert_eq!(results.len(), 4);
+        assert!(results[0].is_err());
+        assert_eq!(results[1], Err(Error::Crc));
+        assert_eq!(results[2], Err(Error::FrameTooLong));
+        assert_eq!(results[3], Ok(message));
+    }
+}
@@ -0,0 +1,86 @@
+//! Binary telemetry sent by the radar to the host.
+//!
+//! Each [`Message`] travels in its own frame: a type byte, the fields in
+//! little endian and a CRC-16, COBS-encoded and followed by a zero. A zero
+//! never appears inside a frame, so a receiver joining mid-stream or
+//! losing bytes resynchronises on the next one.
+//!
+//! The firmware encodes with [`Message::encode`]; the host feeds the bytes
+//! received to a [`Decoder`], or with the `std` feature reads them from
+//! any [`std::io::Read`] with [`FrameReader`].
+
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+
+pub mod cobs;
+mod decoder;
+mod message;
+#[cfg(feature = "std")]
+mod reader;
+
+pub use decoder::Decoder;
+pub use message::{
+    ConfigAck, Fault, FaultCode, Heartbeat, Message, Sample, Setting, Sweep, MAX_FRAME_LEN, MAX_SWEEP_STEPS,
+};
+#[cfg(feature = "std")]
+pub use reader::{FrameReader, ReadError};
+
+/// Reasons why a frame could not be encoded or decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The output buffer cannot hold the result.
+    BufferTooSmall,
+    /// The frame is not valid COBS.
+    Cobs,
+    /// The CRC does not match: the frame was corrupted.
+    Crc,
+    /// The frame is shorter than its message type requires.
+    Truncated,
+    /// The frame carries a message type this version does not know.
+    UnknownType(u8),
+    /// A field is out of range, or the frame is longer than its message.
+    InvalidValue,
+    /// No delimiter within [`MAX_FRAME_LEN`] bytes.
+    FrameTooLong,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::BufferTooSmall => f.write_str("buffer too small"),
+            Self::Cobs => f.write_str("malformed COBS frame"),
+            Self::Crc => f.write_str("CRC mismatch"),
+            Self::Truncated => f.write_str("truncated frame"),
+            Self::UnknownType(kind) => write!(f, "unknown message type {kind:#04x}"),
+            Self::InvalidValue => f.write_str("invalid field value"),
+            Self::FrameTooLong => f.write_str("frame too long"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+/// CRC-16/CCITT-FALSE: polynomial 0x1021, initial value 0xFFFF.
+pub fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0xFFFF, |crc, &byte| {
+        (0..8).fold(crc ^ (byte as u16) << 8, |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn computes_the_crc() {
+        // Valeur de contrôle du catalogue des CRC
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+        assert_eq!(crc16(&[]), 0xFFFF);
+    }
+}
@@ -0,0 +1,415 @@
+use crate::{cobs, crc16, Error};
+
+const SAMPLE: u8 = 0x01;
+const FAULT: u8 = 0x02;
+const HEARTBEAT: u8 = 0x03;
+const CONFIG_ACK: u8 = 0x04;
+const SWEEP: u8 = 0x05;
+
+/// Distance sent when nothing was in range.
+const NO_DISTANCE: u16 = 0xFFFF;
+
+/// Most steps a [`Sweep`] can carry.
+pub const MAX_SWEEP_STEPS: usize = 32;
+/// Longest payload: type, sweep header and distances, CRC.
+const MAX_PAYLOAD_LEN: usize = 1 + 7 + 2 * MAX_SWEEP_STEPS + 2;
+/// Longest frame on the link, delimiter included.
+pub const MAX_FRAME_LEN: usize = cobs::max_encoded_len(MAX_PAYLOAD_LEN) + 1;
+
+/// One distance reading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sample {
+    /// Index of the sensor on the radar.
+    pub sensor: u8,
+    /// Free-running µs clock of the radar, wraps around every ~71 min.
+    pub timestamp_us: u32,
+    /// `None` when nothing was in range.
+    pub distance_mm: Option<u16>,
+}
+
+/// What went wrong, as reported in a [`Fault`].
+///
+/// Codes follow the sensor errors, which may grow: matches outside this
+/// crate need a wildcard arm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FaultCode {
+    NoEchoStart,
+    EchoTooLong,
+    OutOfRange,
+    TooSoon,
+    PinError,
+    NoResponse,
+    BadChecksum,
+    BusError,
+    WrongDevice,
+    InvalidReading,
+    InvalidConfig,
+    /// The sensor moved from its mounting position.
+    Misaligned,
+    /// A code this version does not know.
+    Other(u8),
+}
+
+impl FaultCode {
+    pub fn to_u8(self) -> u8 {
+        match self {
+            Self::NoEchoStart => 1,
+            Self::EchoTooLong => 2,
+            Self::OutOfRange => 3,
+            Self::TooSoon => 4,
+            Self::PinError => 5,
+            Self::NoResponse => 6,
+            Self::BadChecksum => 7,
+            Self::BusError => 8,
+            Self::WrongDevice => 9,
+            Self::InvalidReading => 10,
+            Self::InvalidConfig => 11,
+            Self::Misaligned => 16,
+            Self::Other(code) => code,
+        }
+    }
+
+    pub fn from_u8(code: u8) -> Self {
+        match code {
+            1 => Self::NoEchoStart,
+            2 => Self::EchoTooLong,
+            3 => Self::OutOfRange,
+            4 => Self::TooSoon,
+            5 => Self::PinError,
+            6 => Self::NoResponse,
+            7 => Self::BadChecksum,
+            8 => Self::BusError,
+            9 => Self::WrongDevice,
+            10 => Self::InvalidReading,
+            11 => Self::InvalidConfig,
+            16 => Self::Misaligned,
+            code => Self::Other(code),
+        }
+    }
+}
+
+/// A failed measurement or a radar fault.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fault {
+    pub sensor: u8,
+    pub timestamp_us: u32,
+    pub code: FaultCode,
+}
+
+/// Sent periodically, so that the host can tell a silent radar from a
+/// radar with nothing in range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Heartbeat {
+    pub uptime_ms: u32,
+    /// Current measurement period.
+    pub period_ms: u16,
+}
+
+/// A configurable radar setting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Setting {
+    PeriodMs,
+    /// Zone threshold changed; the value is the far threshold in cm.
+    Zones,
+    /// Air temperature used for the speed of sound, in tenths of °C.
+    TemperatureDc,
+    Reset,
+    Other(u8),
+}
+
+impl Setting {
+    pub fn to_u8(self) -> u8 {
+        match self {
+            Self::PeriodMs => 1,
+            Self::Zones => 2,
+            Self::TemperatureDc => 3,
+            Self::Reset => 4,
+            Self::Other(code) => code,
+        }
+    }
+
+    pub fn from_u8(code: u8) -> Self {
+        match code {
+            1 => Self::PeriodMs,
+            2 => Self::Zones,
+            3 => Self::TemperatureDc,
+            4 => Self::Reset,
+            code => Self::Other(code),
+        }
+    }
+}
+
+/// Answer to a configuration command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConfigAck {
+    pub setting: Setting,
+    /// Value in effect after the command.
+    pub value: i32,
+    /// `false` when the command was rejected and the setting left as is.
+    pub accepted: bool,
+}
+
+/// One pass of a scanning radar: the distance measured at each angle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sweep {
+    pub sweep: u16,
+    pub first_deg: i16,
+    pub step_deg: i16,
+    steps: u8,
+    distances_cm: [u16; MAX_SWEEP_STEPS],
+}
+
+impl Sweep {
+    /// `None` if there are more than [`MAX_SWEEP_STEPS`] distances.
+    pub fn new(sweep: u16, first_deg: i16, step_deg: i16, distances_cm: impl IntoIterator<Item = Option<u16>>) -> Option<Self> {
+        let mut frame = Self {
+            sweep,
+            first_deg,
+            step_deg,
+            steps: 0,
+            distances_cm: [NO_DISTANCE; MAX_SWEEP_STEPS],
+        };
+        for distance in distances_cm {
+            *frame.distances_cm.get_mut(frame.steps as usize)? = distance.unwrap_or(NO_DISTANCE);
+            frame.steps += 1;
+        }
+        Some(frame)
+    }
+
+    pub fn len(&self) -> usize {
+        self.steps as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps == 0
+    }
+
+    /// Every step with its angle, `None` where nothing was in range.
+    pub fn iter(&self) -> impl Iterator<Item = (i16, Option<u16>)> + '_ {
+        self.distances_cm[..self.len()].iter().enumerate().map(|(step, &distance)| {
+            let angle = self.first_deg.wrapping_add((step as i16).wrapping_mul(self.step_deg));
+            (angle, (distance != NO_DISTANCE).then_some(distance))
+        })
+    }
+}
+
+/// Everything the radar sends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+    Sample(Sample),
+    Fault(Fault),
+    Heartbeat(Heartbeat),
+    ConfigAck(ConfigAck),
+    Sweep(Sweep),
+}
+
+impl Message {
+    /// Writes the whole frame, delimiter included, to `out` and returns its
+    /// length. `out` should hold [`MAX_FRAME_LEN`] bytes.
+    pub fn encode(&self, out: &mut [u8]) -> Result<usize, Error> {
+        let mut payload = Writer {
+            buffer: [0; MAX_PAYLOAD_LEN],
+            len: 0,
+        };
+        match self {
+            Self::Sample(sample) => {
+                payload.put(&[SAMPLE, sample.sensor]);
+                payload.put(&sample.timestamp_us.to_le_bytes());
+                payload.put(&sample.distance_mm.unwrap_or(NO_DISTANCE).to_le_bytes());
+            }
+            Self::Fault(fault) => {
+                payload.put(&[FAULT, fault.sensor]);
+                payload.put(&fault.timestamp_us.to_le_bytes());
+                payload.put(&[fault.code.to_u8()]);
+            }
+            Self::Heartbeat(heartbeat) => {
+                payload.put(&[HEARTBEAT]);
+                payload.put(&heartbeat.uptime_ms.to_le_bytes());
+                payload.put(&heartbeat.period_ms.to_le_bytes());
+            }
+            Self::ConfigAck(ack) => {
+                payload.put(&[CONFIG_ACK, ack.setting.to_u8()]);
+                payload.put(&ack.value.to_le_bytes());
+                payload.put(&[ack.accepted as u8]);
+            }
+            Self::Sweep(sweep) => {
+                payload.put(&[SWEEP]);
+                payload.put(&sweep.sweep.to_le_bytes());
+                payload.put(&sweep.first_deg.to_le_bytes());
+                payload.put(&sweep.step_deg.to_le_bytes());
+                payload.put(&[sweep.steps]);
+                for distance in &sweep.distances_cm[..sweep.len()] {
+                    payload.put(&distance.to_le_bytes());
+                }
+            }
+        }
+        let crc = crc16(&payload.buffer[..payload.len]);
+        payload.put(&crc.to_le_bytes());
+
+        let len = cobs::encode(&payload.buffer[..payload.len], out)?;
+        *out.get_mut(len).ok_or(Error::BufferTooSmall)? = 0;
+        Ok(len + 1)
+    }
+
+    /// Reads a message from a frame already COBS-decoded, CRC included.
+    pub fn from_payload(payload: &[u8]) -> Result<Self, Error> {
+        let (body, crc) = payload.split_last_chunk::<2>().ok_or(Error::Truncated)?;
+        if crc16(body) != u16::from_le_bytes(*crc) {
+            return Err(Error::Crc);
+        }
+
+        let mut reader = Reader(body);
+        let message = match reader.u8()? {
+            SAMPLE => Self::Sample(Sample {
+                sensor: reader.u8()?,
+                timestamp_us: u32::from_le_bytes(reader.take()?),
+                distance_mm: Some(u16::from_le_bytes(reader.take()?)).filter(|&mm| mm != NO_DISTANCE),
+            }),
+            FAULT => Self::Fault(Fault {
+                sensor: reader.u8()?,
+                timestamp_us: u32::from_le_bytes(reader.take()?),
+                code: FaultCode::from_u8(reader.u8()?),
+            }),
+            HEARTBEAT => Self::Heartbeat(Heartbeat {
+                uptime_ms: u32::from_le_bytes(reader.take()?),
+                period_ms: u16::from_le_bytes(reader.take()?),
+            }),
+            CONFIG_ACK => Self::ConfigAck(ConfigAck {
+                setting: Setting::from_u8(reader.u8()?),
+                value: i32::from_le_bytes(reader.take()?),
+                accepted: reader.u8()? != 0,
+            }),
+            SWEEP => {
+                let sweep = u16::from_le_bytes(reader.take()?);
+                let first_deg = i16::from_le_bytes(reader.take()?);
+                let step_deg = i16::from_le_bytes(reader.take()?);
+                let steps = reader.u8()? as usize;
+                if steps > MAX_SWEEP_STEPS {
+                    return Err(Error::InvalidValue);
+                }
+                let mut distances = [None; MAX_SWEEP_STEPS];
+                for distance in &mut distances[..steps] {
+                    *distance = Some(u16::from_le_bytes(reader.take()?)).filter(|&cm| cm != NO_DISTANCE);
+                }
+                Self::Sweep(Sweep::new(sweep, first_deg, step_deg, distances[..steps].iter().copied()).ok_or(Error::InvalidValue)?)
+            }
+            kind => return Err(Error::UnknownType(kind)),
+        };
+        if !reader.0.is_empty() {
+            return Err(Error::InvalidValue);
+        }
+        Ok(message)
+    }
+}
+
+struct Writer {
+    buffer: [u8; MAX_PAYLOAD_LEN],
+    len: usize,
+}
+
+impl Writer {
+    fn put(&mut self, bytes: &[u8]) {
+        self.buffer[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let (bytes, rest) = self.0.split_first_chunk::<N>().ok_or(Error::Truncated)?;
+        self.0 = rest;
+        Ok(*bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take::<1>()?[0])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(message: Message) -> Vec<u8> {
+        let mut frame = [0; MAX_FRAME_LEN];
+        let len = message.encode(&mut frame).unwrap();
+        let frame = &frame[..len];
+        // Un seul zéro, en fin de trame
+        assert_eq!(frame.iter().position(|&byte| byte == 0), Some(len - 1));
+
+        let mut payload = [0; MAX_FRAME_LEN];
+        let payload_len = cobs::decode(&frame[..len - 1], &mut payload).unwrap();
+        assert_eq!(Message::from_payload(&payload[..payload_len]), Ok(message));
+        payload[..payload_len].to_vec()
+    }
+
+    #[test]
+    fn round_trips_every_message() {
+        let payload = round_trip(Message::Sample(Sample {
+            sensor: 1,
+            timestamp_us: 0x1234_5678,
+            distance_mm: Some(1_500),
+        }));
+        assert_eq!(payload[..8], [SAMPLE, 1, 0x78, 0x56, 0x34, 0x12, 0xDC, 0x05]);
+
+        round_trip(Message::Sample(Sample {
+            sensor: 0,
+            timestamp_us: 0,
+            distance_mm: None,
+        }));
+        round_trip(Message::Fault(Fault {
+            sensor: 2,
+            timestamp_us: 42,
+            code: FaultCode::Misaligned,
+        }));
+        round_trip(Message::Fault(Fault {
+            sensor: 2,
+            timestamp_us: 42,
+            code: FaultCode::Other(99),
+        }));
+        round_trip(Message::Heartbeat(Heartbeat {
+            uptime_ms: 60_000,
+            period_ms: 100,
+        }));
+        round_trip(Message::ConfigAck(ConfigAck {
+            setting: Setting::TemperatureDc,
+            value: -5,
+            accepted: true,
+        }));
+
+        let sweep = Sweep::new(7, -60, 10, (0..13).map(|step| (step != 4).then_some(100 + step))).unwrap();
+        assert_eq!(sweep.iter().nth(4), Some((-20, None)));
+        assert_eq!(sweep.iter().last(), Some((60, Some(112))));
+        let payload = round_trip(Message::Sweep(sweep));
+        assert_eq!(payload.len(), 1 + 7 + 2 * 13 + 2);
+    }
+
+    #[test]
+    fn rejects_corrupted_payloads() {
+        let mut frame = [0; MAX_FRAME_LEN];
+        let message = Message::Heartbeat(Heartbeat {
+            uptime_ms: 1,
+            period_ms: 100,
+        });
+        let len = message.encode(&mut frame).unwrap();
+        let mut payload = [0; MAX_FRAME_LEN];
+        let payload_len = cobs::decode(&frame[..len - 1], &mut payload).unwrap();
+
+        payload[2] ^= 0x10;
+        assert_eq!(Message::from_payload(&payload[..payload_len]), Err(Error::Crc));
+        assert_eq!(Message::from_payload(&[0x42]), Err(Error::Truncated));
+
+        let unknown = [0x7F, 0, 0];
+        let crc = crc16(&unknown).to_le_bytes();
+        assert_eq!(Message::from_payload(&[0x7F, 0, 0, crc[0], crc[1]]), Err(Error::UnknownType(0x7F)));
+        // Trop court pour son type
+        let crc = crc16(&[HEARTBEAT, 0]).to_le_bytes();
+        assert_eq!(Message::from_payload(&[HEARTBEAT, 0, crc[0], crc[1]]), Err(Error::Truncated));
+
+        assert!(Sweep::new(0, 0, 1, [Some(1); MAX_SWEEP_STEPS + 1]).is_none());
+        assert_eq!(message.encode(&mut frame[..4]), Err(Error::BufferTooSmall));
+    }
+}
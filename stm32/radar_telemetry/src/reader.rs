@@ -0,0 +1,119 @@
+use std::{fmt, io};
+
+use crate::{Decoder, Error, Message};
+
+/// Why [`FrameReader`] could not return a message.
+#[derive(Debug)]
+pub enum ReadError {
+    /// Reading the stream failed; the reader stops.
+    Io(io::Error),
+    /// A frame was dropped; the reader goes on with the next one.
+    Frame(Error),
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "read failed: {error}"),
+            Self::Frame(error) => write!(f, "bad frame: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::Frame(error) => Some(error),
+        }
+    }
+}
+
+/// Messages read from a serial port, a file or any other byte stream.
+///
+/// The iteration ends at the end of the stream, or after an I/O error. A
+/// read timeout (as returned by a serial port with no data) is not an
+/// error: the reader simply tries again.
+pub struct FrameReader<R> {
+    inner: R,
+    decoder: Decoder,
+    buffer: [u8; 256],
+    start: usize,
+    end: usize,
+    failed: bool,
+}
+
+impl<R: io::Read> FrameReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            decoder: Decoder::new(),
+            buffer: [0; 256],
+            start: 0,
+            end: 0,
+            failed: false,
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: io::Read> Iterator for FrameReader<R> {
+    type Item = Result<Message, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.failed {
+            while self.start < self.end {
+                let byte = self.buffer[self.start];
+                self.start += 1;
+                if let Some(result) = self.decoder.push(byte) {
+                    return Some(result.map_err(ReadError::Frame));
+                }
+            }
+
+            match self.inner.read(&mut self.buffer) {
+                Ok(0) => return None,
+                Ok(len) => (self.start, self.end) = (0, len),
+                Err(error) if matches!(error.kind(), io::ErrorKind::Interrupted | io::ErrorKind::TimedOut) => {}
+                Err(error) => {
+                    self.failed = true;
+                    return Some(Err(ReadError::Io(error)));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Fault, FaultCode, MAX_FRAME_LEN};
+
+    #[test]
+    fn reads_messages_from_a_stream() {
+        let message = Message::Fault(Fault {
+            sensor: 0,
+            timestamp_us: 7,
+            code: FaultCode::NoEchoStart,
+        });
+        let mut stream = Vec::new();
+        for _ in 0..100 {
+            let mut frame = [0; MAX_FRAME_LEN];
+            let len = message.encode(&mut frame).unwrap();
+            stream.extend(&frame[..len]);
+        }
+        // Une trame incomplète en fin de fichier est ignorée
+        stream.extend([0x03, 0x02]);
+
+        let messages: Vec<_> = FrameReader::new(stream.as_slice()).collect::<Result<_, _>>().unwrap();
+        assert_eq!(messages.len(), 100);
+        assert!(messages.iter().all(|read| *read == message));
+    }
+}
@@ -0,0 +1,234 @@
+//! Board support for the B-L475E-IOT01A Discovery kit: [`Board::new`]
+//! configures the clocks and hands out the on-board resources already set
+//! up.
+
+use board_sensors::QspiBus;
+use cortex_m::peripheral::DWT;
+use embedded_hal::{
+    delay::DelayNs,
+    i2c::{self as i2c1, ErrorKind, Operation},
+};
+use stm32l4xx_hal::{
+    gpio::{
+        Alternate, Edge, ExtiPin, Floating, Input, OpenDrain, Output, PushPull, PA5, PB10, PB11, PB14, PB6, PB7, PC13, PC6, PE10,
+        PE11, PE12, PE13, PE14, PE15,
+    },
+    hal::blocking::i2c::{Read, Write, WriteRead},
+    i2c::{self, I2c},
+    pac::{self, EXTI, I2C2, USART1},
+    prelude::*,
+    qspi::{AddressSize, Qspi, QspiConfig, QspiError, QspiMode, QspiReadCommand, QspiWriteCommand},
+    rcc::Clocks,
+    serial::{self, Serial},
+};
+
+/// SYSCLK, also the rate of the DWT cycle counter.
+pub const SYSCLK_MHZ: u32 = 80;
+/// Interrupt raised by the user button.
+pub const BUTTON_INTERRUPT: pac::Interrupt = pac::Interrupt::EXTI15_10;
+
+/// LED1, green, next to the Arduino connector.
+pub type Led1 = PA5<Output<PushPull>>;
+/// LED2, green, next to the USB OTG connector.
+pub type Led2 = PB14<Output<PushPull>>;
+/// Blue user button, low when pressed (external pull-up).
+pub type UserButton = PC13<Input<Floating>>;
+/// USART1, wired to the ST-LINK virtual COM port at 115 200 baud.
+pub type Vcp = Serial<USART1, (PB6<Alternate<PushPull, 7>>, PB7<Alternate<PushPull, 7>>)>;
+/// I2C2, shared by the on-board sensors (VL53L0X, HTS221, LPS22HB,
+/// LSM6DSL, LIS3MDL).
+pub type SensorBus = I2cBus<I2c<I2C2, (PB10<Alternate<OpenDrain, 4>>, PB11<Alternate<OpenDrain, 4>>)>>;
+/// XSHUT of the VL53L0X: low keeps it in shutdown.
+pub type TofShutdown = PC6<Output<PushPull>>;
+/// QUADSPI, wired to the MX25R6435F NOR flash.
+pub type FlashBus = QspiFlashBus<
+    Qspi<(
+        PE10<Alternate<PushPull, 10>>,
+        PE11<Alternate<PushPull, 10>>,
+        PE12<Alternate<PushPull, 10>>,
+        PE13<Alternate<PushPull, 10>>,
+        PE14<Alternate<PushPull, 10>>,
+        PE15<Alternate<PushPull, 10>>,
+    )>,
+>;
+
+/// The HAL implements the embedded-hal 0.2 I2C traits, the drivers expect
+/// the 1.0 one.
+pub struct I2cBus<I>(pub I);
+
+impl<I> i2c1::ErrorType for I2cBus<I> {
+    type Error = ErrorKind;
+}
+
+impl<I, E> i2c1::I2c for I2cBus<I>
+where
+    I: Read<Error = E> + Write<Error = E> + WriteRead<Error = E>,
+{
+    /// Only the transactions the HAL can run without a STOP in between: a
+    /// single write or read, or a write followed by a read. Any other
+    /// sequence fails without touching the bus.
+    fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Self::Error> {
+        match operations {
+            [] => Ok(()),
+            [Operation::Write(bytes)] => self.0.write(address, bytes),
+            [Operation::Read(buffer)] => self.0.read(address, buffer),
+            [Operation::Write(bytes), Operation::Read(buffer)] => self.0.write_read(address, bytes, buffer),
+            _ => return Err(ErrorKind::Other),
+        }
+        .map_err(|_| ErrorKind::Other)
+    }
+
+    fn write_read(&mut self, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), Self::Error> {
+        // Avec un start répété, comme l'attendent les capteurs ST
+        self.0.write_read(address, write, read).map_err(|_| ErrorKind::Other)
+    }
+}
+
+/// Runs the flash commands on the QUADSPI peripheral, all phases on a
+/// single line.
+pub struct QspiFlashBus<Q>(pub Q);
+
+impl QspiBus for FlashBus {
+    type Error = QspiError;
+
+    fn read(&mut self, instruction: u8, address: Option<u32>, dummy_cycles: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        let command = QspiReadCommand {
+            instruction: Some((instruction, QspiMode::SingleChannel)),
+            address: address.map(|address| (address, QspiMode::SingleChannel)),
+            alternative_bytes: None,
+            dummy_cycles,
+            data_mode: QspiMode::SingleChannel,
+            receive_length: buffer.len() as u32,
+            double_data_rate: false,
+        };
+        self.0.transfer(command, buffer)
+    }
+
+    fn write(&mut self, instruction: u8, address: Option<u32>, data: &[u8]) -> Result<(), Self::Error> {
+        let command = QspiWriteCommand {
+            instruction: Some((instruction, QspiMode::SingleChannel)),
+            address: address.map(|address| (address, QspiMode::SingleChannel)),
+            alternative_bytes: None,
+            dummy_cycles: 0,
+            data: (!data.is_empty()).then_some((data, QspiMode::SingleChannel)),
+            double_data_rate: false,
+        };
+        self.0.write(command)
+    }
+}
+
+/// Busy-wait delay counted in core cycles.
+pub struct CycleDelay;
+
+impl DelayNs for CycleDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        cortex_m::asm::delay((u64::from(ns) * u64::from(SYSCLK_MHZ)).div_ceil(1_000) as u32);
+    }
+}
+
+pub struct Board {
+    pub clocks: Clocks,
+    pub led1: Led1,
+    pub led2: Led2,
+    /// Interrupts on the falling edge (press), on [`BUTTON_INTERRUPT`]
+    /// once unmasked.
+    pub button: UserButton,
+    pub exti: EXTI,
+    pub vcp: Vcp,
+    pub sensor_bus: SensorBus,
+    /// Already driven high: the VL53L0X is powered up.
+    pub tof_shutdown: TofShutdown,
+    pub flash_bus: FlashBus,
+}
+
+impl Board {
+    /// Runs the core at 80 MHz from the MSI through the PLL, and starts the
+    /// DWT cycle counter used by [`monotonic_us`].
+    pub fn new(cp: &mut cortex_m::Peripherals, dp: pac::Peripherals) -> Self {
+        let mut flash = dp.FLASH.constrain();
+        let mut rcc = dp.RCC.constrain();
+        let mut pwr = dp.PWR.constrain(&mut rcc.apb1r1);
+        let clocks = rcc
+            .cfgr
+            .sysclk(SYSCLK_MHZ.MHz())
+            .pclk1(SYSCLK_MHZ.MHz())
+            .pclk2(SYSCLK_MHZ.MHz())
+            .freeze(&mut flash.acr, &mut pwr);
+
+        cp.DCB.enable_trace();
+        cp.DWT.enable_cycle_counter();
+
+        let mut gpioa = dp.GPIOA.split(&mut rcc.ahb2);
+        let mut gpiob = dp.GPIOB.split(&mut rcc.ahb2);
+        let mut gpioc = dp.GPIOC.split(&mut rcc.ahb2);
+        let mut gpioe = dp.GPIOE.split(&mut rcc.ahb2);
+
+        let led1 = gpioa.pa5.into_push_pull_output(&mut gpioa.moder, &mut gpioa.otyper);
+        let led2 = gpiob.pb14.into_push_pull_output(&mut gpiob.moder, &mut gpiob.otyper);
+
+        let mut exti = dp.EXTI;
+        let mut syscfg = dp.SYSCFG;
+        let mut button = gpioc.pc13.into_floating_input(&mut gpioc.moder, &mut gpioc.pupdr);
+        button.make_interrupt_source(&mut syscfg, &mut rcc.apb2);
+        button.trigger_on_edge(&mut exti, Edge::Falling);
+        button.enable_interrupt(&mut exti);
+
+        let tx = gpiob.pb6.into_alternate(&mut gpiob.moder, &mut gpiob.otyper, &mut gpiob.afrl);
+        let rx = gpiob.pb7.into_alternate(&mut gpiob.moder, &mut gpiob.otyper, &mut gpiob.afrl);
+        let vcp = Serial::usart1(dp.USART1, (tx, rx), serial::Config::default().baudrate(115_200.bps()), clocks, &mut rcc.apb2);
+
+        let mut scl = gpiob.pb10.into_alternate_open_drain(&mut gpiob.moder, &mut gpiob.otyper, &mut gpiob.afrh);
+        scl.internal_pull_up(&mut gpiob.pupdr, true);
+        let mut sda = gpiob.pb11.into_alternate_open_drain(&mut gpiob.moder, &mut gpiob.otyper, &mut gpiob.afrh);
+        sda.internal_pull_up(&mut gpiob.pupdr, true);
+        let i2c = I2c::i2c2(dp.I2C2, (scl, sda), i2c::Config::new(400.kHz(), clocks), &mut rcc.apb1r1);
+
+        let mut tof_shutdown = gpioc.pc6.into_push_pull_output(&mut gpioc.moder, &mut gpioc.otyper);
+        tof_shutdown.set_high();
+        // Démarrage du VL53L0X : 1,2 ms max
+        cortex_m::asm::delay(2 * 1_000 * SYSCLK_MHZ);
+
+        let clk = gpioe.pe10.into_alternate(&mut gpioe.moder, &mut gpioe.otyper, &mut gpioe.afrh);
+        let ncs = gpioe.pe11.into_alternate(&mut gpioe.moder, &mut gpioe.otyper, &mut gpioe.afrh);
+        let io0 = gpioe.pe12.into_alternate(&mut gpioe.moder, &mut gpioe.otyper, &mut gpioe.afrh);
+        let io1 = gpioe.pe13.into_alternate(&mut gpioe.moder, &mut gpioe.otyper, &mut gpioe.afrh);
+        let io2 = gpioe.pe14.into_alternate(&mut gpioe.moder, &mut gpioe.otyper, &mut gpioe.afrh);
+        let io3 = gpioe.pe15.into_alternate(&mut gpioe.moder, &mut gpioe.otyper, &mut gpioe.afrh);
+        // 20 MHz : en mode basse consommation, la mémoire ne lit pas plus
+        // vite sur une seule ligne ; 8 Mio = 2^(22 + 1)
+        let qspi_config = QspiConfig::default()
+            .clock_prescaler(3)
+            .flash_size(22)
+            .address_size(AddressSize::Addr24Bit);
+        let qspi = Qspi::new(dp.QUADSPI, (clk, ncs, io0, io1, io2, io3), &mut rcc.ahb3, qspi_config);
+
+        Self {
+            clocks,
+            led1,
+            led2,
+            button,
+            exti,
+            vcp,
+            sensor_bus: I2cBus(i2c),
+            tof_shutdown,
+            flash_bus: QspiFlashBus(qspi),
+        }
+    }
+}
+
+/// Free-running µs time base from the DWT cycle counter. The counter wraps
+/// every 53 s, so the elapsed cycles are accumulated rather than divided:
+/// the clock must be read at least that often.
+pub fn monotonic_us() -> impl FnMut() -> u32 {
+    let mut last = DWT::cycle_count();
+    let mut now_us = 0u32;
+    let mut remainder = 0u32;
+    move || {
+        let cycles = DWT::cycle_count();
+        let elapsed = cycles.wrapping_sub(last) + remainder;
+        last = cycles;
+        now_us = now_us.wrapping_add(elapsed / SYSCLK_MHZ);
+        remainder = elapsed % SYSCLK_MHZ;
+        now_us
+    }
+}
@@ -7,14 +7,321 @@
 use panic_halt as _;
 
 
-use cortex_m_rt::entry;
-use stm32l4xx_hal::{
-    pac,
-    prelude::*,
+// Toutes les ressources de la carte ne servent pas à chaque démo
+#[allow(dead_code)]
+mod bsp;
+
+use core::cell::RefCell;
+
+use board_sensors::{
+    heading_deg, AccelScale, Alignment, AlignmentMonitor, Axes, Fault, HardIronCalibration, Hts221, Hts221Rate, ImuRate,
+    Lis3mdl, Lps22hb, Lps22hbRate, Lsm6dsl, MagRate, MagScale, Mx25r6435f, Record, RecordLog,
 };
+use cortex_m_rt::entry;
+use embedded_hal_bus::i2c::RefCellDevice;
+use rtt_target::{rprintln, rtt_init_print};
+use stm32l4xx_hal::{gpio::ExtiPin, pac, prelude::*};
+use ultrasonic_sensor::{BeepPattern, Beeper, Distance, DistanceSensor, PolarMap, SensorError, Vl53l0x, ZoneThresholds};
+
+use crate::bsp::{Board, CycleDelay, FlashBus};
+
+type History = RecordLog<Mx25r6435f<FlashBus, CycleDelay>>;
+
+/// One measurement every 50 ms, the 33 ms timing budget included.
+const RANGING_PERIOD_MS: u32 = 50;
+const BLINK_TICK_MS: u32 = 10;
+const AMBIENT_PERIOD_US: u32 = 5_000_000;
+/// The FIFO holds ~13 accelerometer samples (26 Hz) at each check.
+const TILT_PERIOD_US: u32 = 500_000;
+const MAX_TILT_DEG: f32 = 10.0;
+/// The history takes the first 16 sectors (64 Kio, ~3 800 records) of the
+/// NOR flash.
+const HISTORY_SECTORS: u32 = 16;
+const HISTORY_PERIOD_MS: u32 = 1_000;
+/// 10° sectors for the obstacle map.
+const MAP_BINS: usize = 36;
+
+/// Opens the history kept in the NOR flash and prints what it holds.
+fn mount_history(flash_bus: FlashBus) -> Option<History> {
+    let mut flash = Mx25r6435f::new(flash_bus, CycleDelay);
+    let mounted = flash.init().and_then(|()| RecordLog::mount(flash, 0, HISTORY_SECTORS));
+    let mut history = match mounted {
+        Ok(history) => history,
+        Err(error) => {
+            rprintln!("History unavailable: {:?}", error);
+            return None;
+        }
+    };
+
+    let (mut boots, mut records) = (0, 0);
+    let mut last_distance = None;
+    for entry in history.entries() {
+        match entry {
+            Ok(entry) => {
+                records += 1;
+                match entry.record {
+                    Record::Boot => boots += 1,
+                    Record::Distance(distance) => last_distance = Some(distance),
+                    Record::Fault(fault) => rprintln!("History: #{} at {} ms, {:?}", entry.sequence, entry.timestamp_ms, fault),
+                }
+            }
+            Err(error) => rprintln!("History read failed: {:?}", error),
+        }
+    }
+    rprintln!("History: {} records, {} boots", records, boots);
+    if let Some(distance) = last_distance {
+        rprintln!("History: last distance before reset {}", distance);
+    }
+    Some(history)
+}
+
+/// The history is a nice-to-have: a write failure, or a record refused while
+/// the next sector is being erased, is only reported.
+fn record(history: &mut Option<History>, uptime_ms: u32, record: Record) {
+    if let Some(log) = history {
+        if let Err(error) = log.append(uptime_ms, record) {
+            rprintln!("History write failed: {:?}", error);
+        }
+    }
+}
+
+/// A sensor command that failed is reported and recorded; the radar goes on,
+/// the readings will tell whether the sensor recovered.
+fn check(history: &mut Option<History>, uptime_ms: u32, action: &str, result: Result<(), SensorError>) {
+    if let Err(error) = result {
+        rprintln!("{} failed: {:?}", action, error);
+        record(history, uptime_ms, Record::Fault(Fault::Sensor(error)));
+    }
+}
 
-#[allow(clippy::empty_loop)]
 #[entry]
 fn main() -> ! {
-    loop {}
+    rtt_init_print!();
+
+    let mut cp = cortex_m::Peripherals::take().unwrap();
+    let dp = pac::Peripherals::take().unwrap();
+
+    // LED1 : cible détectée, LED2 : clignote de plus en plus vite à
+    // l'approche, bouton bleu : pause
+    let Board {
+        mut led1,
+        mut led2,
+        mut button,
+        sensor_bus,
+        flash_bus,
+        ..
+    } = Board::new(&mut cp, dp);
+    let mut clock = bsp::monotonic_us();
+
+    // Historique des distances et des défauts, conservé d'un reset à
+    // l'autre ; l'horodatage compte les ms depuis le démarrage
+    let mut history = mount_history(flash_bus);
+    let mut uptime_ms = 0u32;
+    let mut last_history_ms = 0u32;
+    // Dernière distance valable, que LED1 garde sur une mesure douteuse
+    let mut last_distance = None;
+    // Distance en attente d'enregistrement, prise chaque seconde
+    let mut history_distance = None;
+    let mut sensor_fault = None;
+    record(&mut history, uptime_ms, Record::Boot);
+
+    // Le bus I2C2 est partagé par tous les capteurs de la carte
+    let sensor_bus = RefCell::new(sensor_bus);
+
+    let mut hygrometer = Hts221::new(RefCellDevice::new(&sensor_bus));
+    if let Err(error) = hygrometer.init(Hts221Rate::Hz1) {
+        rprintln!("HTS221 init failed: {:?}", error);
+    }
+    let mut barometer = Lps22hb::new(RefCellDevice::new(&sensor_bus));
+    if let Err(error) = barometer.init(Lps22hbRate::Hz1) {
+        rprintln!("LPS22HB init failed: {:?}", error);
+    }
+
+    // LSM6DSL : vérifie que la carte (et donc le capteur) n'a pas bougé
+    // depuis le démarrage
+    let mut imu = Lsm6dsl::new(RefCellDevice::new(&sensor_bus))
+        .with_accelerometer(ImuRate::Hz26, AccelScale::G2)
+        .with_fifo();
+    if let Err(error) = imu.init() {
+        rprintln!("LSM6DSL init failed: {:?}", error);
+    }
+    // 3 contrôles de suite (1,5 s) avant de lever le défaut
+    let mut alignment = AlignmentMonitor::new(MAX_TILT_DEG).with_min_samples(3);
+    let mut accel_samples = [Axes::default(); 32];
+    let mut last_accel = None;
+
+    // LIS3MDL : cap de chaque mesure, pour construire une carte des
+    // obstacles quand la carte tourne. Le cap suppose la carte à plat, ce
+    // que surveille le LSM6DSL
+    let mut magnetometer = Lis3mdl::new(RefCellDevice::new(&sensor_bus)).with_rate(MagRate::Hz20, MagScale::Gauss4);
+    if let Err(error) = magnetometer.init() {
+        rprintln!("LIS3MDL init failed: {:?}", error);
+    }
+    // Compensation du fer dur dès que la carte a fait un tour complet
+    let mut hard_iron = Some(HardIronCalibration::new());
+    rprintln!("Compass: turn the board a full turn to calibrate");
+    let mut obstacles = PolarMap::<MAP_BINS>::new();
+
+    let mut sensor = Vl53l0x::new(RefCellDevice::new(&sensor_bus));
+    check(&mut history, uptime_ms, "VL53L0X init", sensor.init(&mut clock));
+
+    // Mesures de courte portée : une cible à moins de 10 cm allume LED2 en
+    // continu
+    let mut beeper = Beeper::new(ZoneThresholds::new(Distance::from_cm(60), Distance::from_cm(30), Distance::from_cm(10)))
+        .with_timing(40, 600, 80);
+
+    // Une mesure bloquante pour vérifier la chaîne, puis en continu
+    match sensor.measure_distance(&mut clock) {
+        Ok(distance) => rprintln!("First measurement: {}", distance),
+        Err(error) => rprintln!("First measurement failed: {:?}", error),
+    }
+    check(&mut history, uptime_ms, "VL53L0X start", sensor.start_continuous(RANGING_PERIOD_MS));
+
+    let mut paused = false;
+    let mut last_tick_us = clock();
+    let mut last_ambient_us = last_tick_us.wrapping_sub(AMBIENT_PERIOD_US);
+    let mut last_tilt_us = last_tick_us;
+    loop {
+        // Le front est mémorisé par l'EXTI, même sans interruption activée
+        if button.check_interrupt() {
+            button.clear_interrupt_pending_bit();
+            // En défaut, le bouton valide la nouvelle position du capteur
+            if let (Alignment::Misaligned, Some(accel)) = (alignment.alignment(), last_accel) {
+                alignment.recalibrate(accel);
+                beeper.set_pattern(BeepPattern::Silent);
+                rprintln!("New mounting position accepted");
+                continue;
+            }
+            paused = !paused;
+            if paused {
+                check(&mut history, uptime_ms, "VL53L0X stop", sensor.stop_continuous());
+                beeper.set_pattern(BeepPattern::Silent);
+                led1.set_low();
+                led2.set_low();
+                rprintln!("Paused");
+            } else {
+                check(&mut history, uptime_ms, "VL53L0X start", sensor.start_continuous(RANGING_PERIOD_MS));
+                rprintln!("Resumed");
+            }
+        }
+        // En pause, la boucle continue : l'horloge doit être lue toutes les
+        // 53 s au plus
+        let reading = if paused { None } else { sensor.read_range() };
+        if let Some(result) = reading {
+            let heading = match magnetometer.read_field() {
+                Ok(field) => {
+                    if let Some(calibration) = &mut hard_iron {
+                        calibration.update(field);
+                        if let Some(offset) = calibration.offset() {
+                            magnetometer.set_hard_iron(offset);
+                            hard_iron = None;
+                            rprintln!("Compass calibrated, hard-iron offset {:?} mG", offset);
+                        }
+                    }
+                    heading_deg(field)
+                }
+                Err(error) => {
+                    rprintln!("Magnetometer error: {:?}", error);
+                    None
+                }
+            };
+            let distance = match result {
+                Ok(distance) => {
+                    match heading {
+                        Some(heading) => rprintln!("Distance: {} at {:.0}°", distance, heading),
+                        None => rprintln!("Distance: {}", distance),
+                    }
+                    sensor_fault = None;
+                    Some(distance)
+                }
+                Err(SensorError::OutOfRange(_)) => None,
+                // Mesure douteuse : LED1 et le buzzer gardent leur état
+                Err(SensorError::InvalidReading) => last_distance,
+                Err(error) => {
+                    rprintln!("Sensor error: {:?}", error);
+                    // Une seule entrée tant que l'erreur se répète
+                    if sensor_fault.replace(error) != Some(error) {
+                        record(&mut history, uptime_ms, Record::Fault(Fault::Sensor(error)));
+                    }
+                    None
+                }
+            };
+            last_distance = distance;
+            if !matches!(result, Err(SensorError::InvalidReading)) {
+                history_distance = result.ok();
+            }
+            // Rien à portée dans cette direction efface l'ancien obstacle
+            if let (Some(heading), Ok(_) | Err(SensorError::OutOfRange(_))) = (heading, result) {
+                obstacles.update(heading, distance);
+            }
+            if distance.is_some() {
+                led1.set_high();
+            } else {
+                led1.set_low();
+            }
+            // En défaut, LED2 reste allumée
+            if alignment.alignment() == Alignment::Aligned {
+                beeper.update(result);
+            }
+        }
+
+        let now_us = clock();
+        if now_us.wrapping_sub(last_ambient_us) >= AMBIENT_PERIOD_US {
+            last_ambient_us = now_us;
+            match (hygrometer.measure(), barometer.read_pressure_hpa()) {
+                (Ok((celsius, humidity)), Ok(pressure)) => {
+                    rprintln!("Ambient: {:.1} °C, {:.0} %, {:.1} hPa", celsius, humidity, pressure)
+                }
+                (Err(error), _) | (_, Err(error)) => rprintln!("Ambient sensors error: {:?}", error),
+            }
+            if let Some((heading, distance)) = obstacles.nearest() {
+                let mapped = obstacles.iter().filter(|(_, distance)| distance.is_some()).count();
+                rprintln!("Map: {} obstacles, nearest {} at {:.0}°", mapped, distance, heading);
+            }
+        }
+
+        if now_us.wrapping_sub(last_tilt_us) >= TILT_PERIOD_US {
+            last_tilt_us = now_us;
+            match imu.read_fifo(&mut accel_samples) {
+                Ok(count) => {
+                    if let Some(accel) = Axes::mean(&accel_samples[..count]) {
+                        last_accel = Some(accel);
+                        match alignment.update(accel) {
+                            Some(Alignment::Misaligned) => {
+                                let tilt_deg = alignment.tilt_deg();
+                                rprintln!("Fault: sensor misaligned by {:.1}°, press the button to accept", tilt_deg);
+                                beeper.set_pattern(BeepPattern::Continuous);
+                                let fault = Fault::Misaligned { tilt_deg: tilt_deg as u8 };
+                                record(&mut history, uptime_ms, Record::Fault(fault));
+                            }
+                            Some(Alignment::Aligned) => {
+                                rprintln!("Sensor realigned");
+                                beeper.set_pattern(BeepPattern::Silent);
+                            }
+                            None => {}
+                        }
+                    }
+                }
+                Err(error) => rprintln!("IMU error: {:?}", error),
+            }
+        }
+
+        if now_us.wrapping_sub(last_tick_us) >= BLINK_TICK_MS * 1_000 {
+            last_tick_us = last_tick_us.wrapping_add(BLINK_TICK_MS * 1_000);
+            uptime_ms = uptime_ms.wrapping_add(BLINK_TICK_MS);
+            if beeper.tick(BLINK_TICK_MS) {
+                led2.set_high();
+            } else {
+                led2.set_low();
+            }
+
+            // Une distance par seconde au plus, s'il y a une cible
+            if uptime_ms.wrapping_sub(last_history_ms) >= HISTORY_PERIOD_MS {
+                if let Some(distance) = history_distance.take() {
+                    last_history_ms = uptime_ms;
+                    record(&mut history, uptime_ms, Record::Distance(distance));
+                }
+            }
+        }
+    }
 }